@@ -1,6 +1,7 @@
 pub mod ambisonics;
 pub mod convolver;
 pub mod delay;
+pub mod dynamics;
 pub mod envelopes;
 pub mod filters;
 pub mod interpolation;
@@ -8,6 +9,7 @@ pub mod interpolation;
 pub mod bitcrusher;
 pub mod modulator;
 pub mod oscillators;
+pub mod random;
 pub mod reverb;
 pub mod routing;
 pub mod sampler;
@@ -133,6 +135,24 @@ pub enum SynthParameterLabel {
     BitcrusherDownsampling,   // 52
     BitcrusherMode,           // 53
     NumHarmonics,             // 54
+    EnvelopeGate, // 55 (> 0.0 means the envelope holds its sustain segment until released)
+    RandomSeed,   // 56 (integer seed for noise sources)
+    AuxSend,      // 57 (send level, the index is the aux bus: 0 = master reverb, 1 = master delay)
+    CompressorActive, // 58 (> 0.0 means on)
+    CompressorThreshold, // 59 (dB)
+    CompressorRatio, // 60
+    CompressorAttack, // 61 (seconds)
+    CompressorRelease, // 62 (seconds)
+    CompressorMakeupGain, // 63 (dB)
+    LimiterActive, // 64 (> 0.0 means on)
+    LimiterCeiling, // 65 (dB)
+    LimiterRelease, // 66 (seconds)
+    AmbisonicYaw, // 67 (radians, rotation of the ambisonic sound field)
+    AmbisonicPitch, // 68 (radians)
+    AmbisonicRoll, // 69 (radians)
+    AmbisonicDistance, // 70 (meters, of an ambisonic source)
+    AmbisonicDoppler, // 71 (> 0.0 means on)
+    VbapSpread,   // 72 (radians, half-angle of the cone, 0.0 is a point source)
 }
 
 /// the value operation is defined on parameters
@@ -205,6 +225,71 @@ pub enum ValueOrModulator<const BUFSIZE: usize> {
     Mod(f32, Modulator<BUFSIZE>),
 }
 
+/// Setting a modulator on a building block hands back what's left of it:
+/// `Ok` with the modulator that was replaced, if any, or `Err` with the
+/// given modulator if the block doesn't have such a parameter, so it can be
+/// offered to the next block. That way, modulators are neither cloned nor
+/// dropped in the audio thread.
+pub type ModulatorResult<const BUFSIZE: usize> =
+    Result<Option<Modulator<BUFSIZE>>, Modulator<BUFSIZE>>;
+
+/// Hands the modulator that was replaced, or that no block took, to `leftover`.
+pub fn pass_leftover<const BUFSIZE: usize>(
+    result: ModulatorResult<BUFSIZE>,
+    leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+) {
+    if let Ok(Some(modulator)) | Err(modulator) = result {
+        leftover(ValueOrModulator::Mod(0.0, modulator));
+    }
+}
+
+/// Copies of a modulator, for the parts of a synth that need one each
+/// (the effects in a chain, both channels of a stereo synth, etc.).
+/// They're made on the control side, see `Synth::modulator_copies`.
+pub fn copy_modulator<const BUFSIZE: usize>(
+    val_or_mod: &ValueOrModulator<BUFSIZE>,
+    num_copies: usize,
+) -> Vec<Modulator<BUFSIZE>> {
+    match val_or_mod {
+        ValueOrModulator::Mod(_, modulator) => (0..num_copies).map(|_| modulator.clone()).collect(),
+        ValueOrModulator::Val(_) => Vec::new(),
+    }
+}
+
+/// Offers a modulator to a chain of effects. Every effect that takes it needs its
+/// own copy, the ones after the first are taken from `copies`.
+/// Returns `Err` with the modulator if none of them takes it.
+pub fn set_modulator_on_effects<const BUFSIZE: usize>(
+    effects: &mut [Box<dyn MonoEffect<BUFSIZE> + Send + Sync>],
+    par: SynthParameterLabel,
+    init: f32,
+    modulator: Modulator<BUFSIZE>,
+    copies: &mut Vec<Modulator<BUFSIZE>>,
+    leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+) -> ModulatorResult<BUFSIZE> {
+    let mut modulator = Some(modulator);
+    let mut taken = false;
+
+    for ef in effects.iter_mut() {
+        let m = match modulator.take().or_else(|| copies.pop()) {
+            Some(m) => m,
+            None => break, // no copies left
+        };
+        match ef.set_modulator(par, init, m) {
+            Ok(replaced) => {
+                taken = true;
+                pass_leftover(Ok(replaced), leftover);
+            }
+            Err(m) => modulator = Some(m),
+        }
+    }
+
+    match modulator {
+        Some(modulator) if !taken => Err(modulator),
+        unused => Ok(unused),
+    }
+}
+
 pub fn resolve_parameter_value<const BUFSIZE: usize>(
     par: SynthParameterLabel,
     val: &SynthParameterValue,
@@ -556,17 +641,28 @@ pub fn resolve_parameter_value<const BUFSIZE: usize>(
 /// oscillators, the sampler, etc are sources
 pub trait MonoSource<const BUFSIZE: usize>: MonoSourceClone<BUFSIZE> {
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue);
-    fn set_modulator(&mut self, par: SynthParameterLabel, init: f32, modulator: Modulator<BUFSIZE>);
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE>;
 
-    /// default impl so we have a common interface ...
+    /// default impl so we have a common interface,
+    /// returns the modulator that was replaced or not taken ...
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterLabel,
         val_or_mod: ValueOrModulator<BUFSIZE>,
-    ) {
+    ) -> Option<Modulator<BUFSIZE>> {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                None
+            }
+            ValueOrModulator::Mod(init, modulator) => self
+                .set_modulator(par, init, modulator)
+                .unwrap_or_else(Some),
         }
     }
 
@@ -602,17 +698,28 @@ impl<const BUFSIZE: usize> Clone for Box<dyn MonoSource<BUFSIZE> + Send + Sync>
 /// so far only for stereo sampler
 pub trait StereoSource<const BUFSIZE: usize>: StereoSourceClone<BUFSIZE> {
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue);
-    fn set_modulator(&mut self, par: SynthParameterLabel, init: f32, modulator: Modulator<BUFSIZE>);
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE>;
 
-    /// default impl so we have a common interface ...
+    /// default impl so we have a common interface,
+    /// returns the modulator that was replaced or not taken ...
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterLabel,
         val_or_mod: ValueOrModulator<BUFSIZE>,
-    ) {
+    ) -> Option<Modulator<BUFSIZE>> {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                None
+            }
+            ValueOrModulator::Mod(init, modulator) => self
+                .set_modulator(par, init, modulator)
+                .unwrap_or_else(Some),
         }
     }
 
@@ -650,17 +757,28 @@ pub trait MonoEffect<const BUFSIZE: usize> {
     fn finish(&mut self);
     fn is_finished(&self) -> bool;
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue);
-    fn set_modulator(&mut self, par: SynthParameterLabel, init: f32, modulator: Modulator<BUFSIZE>);
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE>;
 
-    /// default impl so we have a common interface ...
+    /// default impl so we have a common interface,
+    /// returns the modulator that was replaced or not taken ...
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterLabel,
         val_or_mod: ValueOrModulator<BUFSIZE>,
-    ) {
+    ) -> Option<Modulator<BUFSIZE>> {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                None
+            }
+            ValueOrModulator::Mod(init, modulator) => self
+                .set_modulator(par, init, modulator)
+                .unwrap_or_else(Some),
         }
     }
 
@@ -677,22 +795,45 @@ pub trait MonoEffect<const BUFSIZE: usize> {
     fn maybe_process_sample(&mut self, sample: f32) -> f32 {
         sample
     }
+
+    /// clear the internal state, also only needed for
+    /// the filters, so the delay can be flushed
+    fn reset(&mut self) {}
 }
 
 /// there's a freeverb- and a convolution-based implementation
 pub trait MultichannelReverb<const BUFSIZE: usize, const NCHAN: usize> {
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue);
+    /// the value (or the modulator) is handed to `leftover` once it's set
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterLabel,
         val_or_mod: ValueOrModulator<BUFSIZE>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(_, _) => {} // no modulators possible so far
-        }
+        if let ValueOrModulator::Val(ref val) = val_or_mod {
+            self.set_parameter(par, val);
+        } // no modulators possible so far
+        leftover(val_or_mod);
     }
     fn process(&mut self, block: [[f32; BUFSIZE]; NCHAN]) -> [[f32; BUFSIZE]; NCHAN];
+    /// clear the internal buffers, so the reverb tail stops immediately
+    fn clear(&mut self);
+    /// Process only the samples from `start_sample` (inclusive) to `end_sample` (exclusive),
+    /// so parameters can be changed at precise positions inside the block.
+    /// Reverbs that can only work on whole blocks process the block on the first segment,
+    /// so parameter changes take effect on the next block.
+    fn process_segment(
+        &mut self,
+        block: &[[f32; BUFSIZE]; NCHAN],
+        out_buf: &mut [[f32; BUFSIZE]; NCHAN],
+        start_sample: usize,
+        _end_sample: usize,
+    ) {
+        if start_sample == 0 {
+            *out_buf = self.process(*block);
+        }
+    }
 }
 
 // we need some more info in case a synth can have more than one
@@ -723,28 +864,55 @@ impl SynthParameterLabel {
 /// This is where the building blocks come together
 pub trait Synth<const BUFSIZE: usize, const NCHAN: usize> {
     fn set_parameter(&mut self, par: SynthParameterAddress, value: &SynthParameterValue);
+
+    /// Parts of the synth that need their own copy of the modulator take
+    /// one from `copies`. Modulators that were replaced, or that no part
+    /// of the synth takes, are handed to `leftover`, so they can be dropped
+    /// outside of the audio thread.
     fn set_modulator(
         &mut self,
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     );
 
-    /// default impl so we have a common interface ...
+    /// default impl so we have a common interface,
+    /// the value is handed to `leftover` once it's set ...
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterAddress,
         val_or_mod: ValueOrModulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                leftover(ValueOrModulator::Val(val));
+            }
+            ValueOrModulator::Mod(init, modulator) => {
+                self.set_modulator(par, init, modulator, copies, leftover)
+            }
         }
     }
 
+    /// The number of copies of a modulator the synth needs at most, in addition
+    /// to the modulator itself, as there are parts that need one each (effects,
+    /// both channels of a stereo synth). The copies are made on the control side,
+    /// so no modulator needs to be cloned in the audio thread.
+    fn modulator_copies(&self) -> usize {
+        0
+    }
+
     fn finish(&mut self);
     fn is_finished(&self) -> bool;
 
+    /// note-off for synths with a gated envelope,
+    /// synths without a gated envelope just ignore it ...
+    fn release(&mut self) {}
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
@@ -1,3 +1,140 @@
-pub mod binauralizer_o1;
-pub mod encoder_o1;
-//pub use encoder_o1;
+pub mod binauralizer;
+pub mod decoder;
+pub mod distance;
+pub mod encoder;
+pub mod rotator;
+
+/// The highest supported ambisonic order.
+pub const MAX_AMBISONIC_ORDER: usize = 3;
+
+/// The number of channels of the highest supported order, which is
+/// the width of the ambisonic bus. Lower orders only use the first channels.
+pub const MAX_AMBISONIC_CHANNELS: usize = 16;
+
+/// The weighting of the ambisonic orders when decoding to loudspeakers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecoderWeighting {
+    Basic, // plain sampling decoder, best for a listener in the sweet spot
+    MaxRe, // maximizes the energy vector, better localization across a larger area
+}
+
+/// number of channels of an ambisonic order, i.e. 4, 9 and 16 for orders 1, 2 and 3
+pub const fn ambisonic_channels(order: usize) -> usize {
+    (order + 1) * (order + 1)
+}
+
+/// the highest (supported) order that fits into the given number of channels
+pub fn ambisonic_order(channels: usize) -> usize {
+    ((channels as f32).sqrt() as usize)
+        .saturating_sub(1)
+        .clamp(1, MAX_AMBISONIC_ORDER)
+}
+
+/// the order an (ACN) channel belongs to
+pub(crate) fn channel_order(acn: usize) -> usize {
+    (acn as f32).sqrt() as usize
+}
+
+/// The encoding coefficients (ACN channel order, SN3D normalization) for a
+/// direction, azimuth and elevation in radians, with the same conventions as
+/// the encoder parameters. Orders above the third are left at zero.
+pub fn sh_coefficients<const ACH: usize>(azimuth: f32, elevation: f32) -> [f32; ACH] {
+    let [x, y, z] = direction_vector(azimuth, elevation);
+
+    let sqrt_3 = 3.0_f32.sqrt();
+    let sqrt_15 = 15.0_f32.sqrt();
+    let sqrt_3_8 = (3.0_f32 / 8.0).sqrt();
+    let sqrt_5_8 = (5.0_f32 / 8.0).sqrt();
+
+    let all = [
+        // order 0
+        1.0,
+        // order 1
+        y,
+        z,
+        x,
+        // order 2
+        sqrt_3 * x * y,
+        sqrt_3 * y * z,
+        0.5 * (3.0 * z * z - 1.0),
+        sqrt_3 * x * z,
+        0.5 * sqrt_3 * (x * x - y * y),
+        // order 3
+        sqrt_5_8 * y * (3.0 * x * x - y * y),
+        sqrt_15 * x * y * z,
+        sqrt_3_8 * y * (5.0 * z * z - 1.0),
+        0.5 * z * (5.0 * z * z - 3.0),
+        sqrt_3_8 * x * (5.0 * z * z - 1.0),
+        0.5 * sqrt_15 * z * (x * x - y * y),
+        sqrt_5_8 * x * (x * x - 3.0 * y * y),
+    ];
+
+    let mut coefs = [0.0; ACH];
+    for (c, a) in coefs.iter_mut().zip(all.iter()) {
+        *c = *a;
+    }
+    coefs
+}
+
+/// The unit vector (x front, y left, z up) of a direction given
+/// with the conventions of the encoder parameters.
+pub(crate) fn direction_vector(azimuth: f32, elevation: f32) -> [f32; 3] {
+    [
+        -azimuth.cos() * elevation.cos(),
+        -azimuth.sin() * elevation.cos(),
+        elevation.sin(),
+    ]
+}
+
+/// The (azimuth, elevation) of a unit vector, the inverse of `direction_vector`.
+pub(crate) fn direction_angles(v: [f32; 3]) -> (f32, f32) {
+    ((-v[1]).atan2(-v[0]), v[2].clamp(-1.0, 1.0).asin())
+}
+
+/// The max-rE weights per order for a 3D layout, which concentrate the energy
+/// in the direction of the source (Legendre polynomials at the largest
+/// root of the polynomial one order above).
+pub(crate) fn max_re_weights_3d(order: usize) -> [f32; MAX_AMBISONIC_ORDER + 1] {
+    let r_e: f32 = match order {
+        1 => 0.577_350_3,
+        2 => 0.774_596_7,
+        _ => 0.861_136_3,
+    };
+    [
+        1.0,
+        r_e,
+        0.5 * (3.0 * r_e * r_e - 1.0),
+        0.5 * (5.0 * r_e * r_e * r_e - 3.0 * r_e),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sh_coefficients() {
+        assert_eq!(ambisonic_channels(2), 9);
+        assert_eq!(ambisonic_order(16), 3);
+        assert_eq!(ambisonic_order(10), 2);
+        assert_eq!(channel_order(8), 2);
+
+        // with SN3D, the coefficients of each order never exceed one
+        // and add up to one (squared) for sectoral and full orders alike
+        for (azi, ele) in [(0.3, 0.0), (2.0, 0.7), (-1.2, -0.4)] {
+            let coefs = sh_coefficients::<16>(azi, ele);
+            for order in 0..=3 {
+                let energy: f32 = coefs[order * order..(order + 1) * (order + 1)]
+                    .iter()
+                    .map(|c| c * c)
+                    .sum();
+                assert!((energy - 1.0).abs() < 0.0001);
+            }
+        }
+
+        // the first order matches the first channels of a higher order
+        let o1 = sh_coefficients::<4>(1.0, 0.2);
+        let o3 = sh_coefficients::<16>(1.0, 0.2);
+        assert_eq!(o1, o3[0..4]);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, channel_order, max_re_weights_3d, sh_coefficients, MAX_AMBISONIC_CHANNELS,
+    MAX_AMBISONIC_ORDER,
+};
+use crate::building_blocks::convolver::block_convolver::BlockConvolver;
+use crate::building_blocks::convolver::uniform_partitioned_convolution::UniformPartitionedConvolution;
+use rubato::{FftFixedIn, Resampler};
+
+// 4x128 points @ 44100kHz, raw f32 ...
+const DEFAULT_FILTER: &[u8] = include_bytes!("../../../binaural_filter/default.raw");
+
+// filters that fit into a block are convolved in one go,
+// longer ones are split into partitions
+enum EarFilter<const BUFSIZE: usize> {
+    Block(BlockConvolver<BUFSIZE>),
+    Partitioned(UniformPartitionedConvolution<BUFSIZE>),
+}
+
+impl<const BUFSIZE: usize> EarFilter<BUFSIZE> {
+    fn from_ir(ir: &[f32]) -> Self {
+        if ir.len() <= BUFSIZE {
+            EarFilter::Block(BlockConvolver::from_ir(ir))
+        } else {
+            EarFilter::Partitioned(UniformPartitionedConvolution::with_ir(ir.to_vec()))
+        }
+    }
+
+    fn convolve(&mut self, input: [f32; BUFSIZE]) -> [f32; BUFSIZE] {
+        match self {
+            EarFilter::Block(conv) => conv.convolve(input),
+            EarFilter::Partitioned(conv) => conv.convolve(input),
+        }
+    }
+}
+
+/**
+ * A simple convolution binauralizer, with one pair of filters per ambisonic channel.
+ * The order is determined by the number of channels (4, 9 or 16 for
+ * first, second or third order), or by the number of filters it's given.
+ */
+pub struct Binauralizer<const BUFSIZE: usize, const ACH: usize> {
+    left: Vec<EarFilter<BUFSIZE>>,
+    right: Vec<EarFilter<BUFSIZE>>,
+}
+
+pub type BinauralizerO1<const BUFSIZE: usize> = Binauralizer<BUFSIZE, 4>;
+pub type BinauralizerO2<const BUFSIZE: usize> = Binauralizer<BUFSIZE, 9>;
+pub type BinauralizerO3<const BUFSIZE: usize> = Binauralizer<BUFSIZE, 16>;
+
+impl<const BUFSIZE: usize, const ACH: usize> Binauralizer<BUFSIZE, ACH> {
+    /// the default filter for the order that matches the number of channels
+    pub fn default_filter(samplerate: f32) -> Self {
+        Self::default_filter_with_order(super::ambisonic_order(ACH), samplerate)
+    }
+
+    /// The default filter for the given order. First order uses measured
+    /// filters. There are no measured filters for the higher orders, so
+    /// second and third order use a synthetic spherical head model instead,
+    /// which has interaural time and level differences, but no pinna or
+    /// elevation cues, so sources above, below and behind the listener are
+    /// hard to tell apart. Use `from_ir` with measured filters if that matters.
+    pub fn default_filter_with_order(order: usize, samplerate: f32) -> Self {
+        if order <= 1 {
+            Self::from_ir(measured_filter_o1(samplerate))
+        } else {
+            Self::from_ir(spherical_head_filter(
+                order.min(MAX_AMBISONIC_ORDER),
+                samplerate,
+                BUFSIZE,
+            ))
+        }
+    }
+
+    /// One left/right pair of impulse responses per ambisonic channel.
+    /// Channels without a filter are ignored. Filters can be longer than
+    /// a block, at the cost of some more processing.
+    pub fn from_ir(ir: Vec<(Vec<f32>, Vec<f32>)>) -> Self {
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+
+        for i in ir.iter().take(ACH) {
+            left.push(EarFilter::from_ir(&i.0));
+            right.push(EarFilter::from_ir(&i.1))
+        }
+
+        Binauralizer { left, right }
+    }
+
+    pub fn binauralize(&mut self, input: &[[f32; BUFSIZE]; ACH]) -> [[f32; BUFSIZE]; 2] {
+        let mut bin_block = [[0.0; BUFSIZE]; 2];
+
+        for (ach, i) in input.iter().enumerate().take(self.left.len()) {
+            let lch = self.left[ach].convolve(*i);
+            let rch = self.right[ach].convolve(*i);
+            for fr in 0..BUFSIZE {
+                bin_block[0][fr] += lch[fr];
+                bin_block[1][fr] += rch[fr];
+            }
+        }
+
+        bin_block
+    }
+}
+
+fn measured_filter_o1(samplerate: f32) -> Vec<(Vec<f32>, Vec<f32>)> {
+    let mut ir: Vec<f32> = DEFAULT_FILTER
+        .chunks(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+
+    debug_assert!(ir.len() == 1024);
+
+    // lower gain a little
+    for s in ir.iter_mut() {
+        *s *= 0.45;
+    }
+
+    let mut ir_proc: Vec<(Vec<f32>, Vec<f32>)> = vec![
+        (ir[0..128].to_vec(), ir[512..640].to_vec()),
+        (ir[128..256].to_vec(), ir[640..768].to_vec()),
+        (ir[256..384].to_vec(), ir[768..896].to_vec()),
+        (ir[384..512].to_vec(), ir[896..1024].to_vec()),
+    ];
+
+    if samplerate != 44100.0 {
+        ir_proc = resample_filter(&ir_proc, 44100.0, samplerate);
+    }
+
+    ir_proc
+}
+
+/// Resample a binaural filter (one left/right pair of impulse
+/// responses per ambisonic channel) to another samplerate.
+pub fn resample_filter(
+    ir: &[(Vec<f32>, Vec<f32>)],
+    from_samplerate: f32,
+    to_samplerate: f32,
+) -> Vec<(Vec<f32>, Vec<f32>)> {
+    if from_samplerate == to_samplerate {
+        return ir.to_vec();
+    }
+
+    let resample = |ch: &Vec<f32>| {
+        if ch.is_empty() {
+            return Vec::new();
+        }
+        let mut resampler = FftFixedIn::<f32>::new(
+            from_samplerate as usize,
+            to_samplerate as usize,
+            ch.len(),
+            1,
+            1,
+        );
+        resampler.process(&[ch]).unwrap().remove(0)
+    };
+
+    ir.iter().map(|(l, r)| (resample(l), resample(r))).collect()
+}
+
+// head radius (m) and speed of sound (m/s) for the head model
+const HEAD_RADIUS: f32 = 0.0875;
+const SPEED_OF_SOUND: f32 = 343.0;
+
+// the response of an ear to a plane wave arriving at the given angle
+// from the ear axis, after Brown & Duda's spherical head model:
+// a head shadow shelf filter plus the travel time around the head
+fn head_model_hrir(incidence: f32, samplerate: f32, len: usize) -> Vec<f32> {
+    let w0 = SPEED_OF_SOUND / HEAD_RADIUS;
+    let alpha_min = 0.1;
+    let theta_min = 150.0_f32.to_radians();
+    let alpha = (1.0 + alpha_min / 2.0)
+        + (1.0 - alpha_min / 2.0) * (incidence / theta_min * std::f32::consts::PI).cos();
+
+    let delay_secs = if incidence < std::f32::consts::FRAC_PI_2 {
+        HEAD_RADIUS / SPEED_OF_SOUND * (1.0 - incidence.cos())
+    } else {
+        HEAD_RADIUS / SPEED_OF_SOUND * (1.0 + incidence - std::f32::consts::FRAC_PI_2)
+    };
+    let delay = delay_secs * samplerate;
+    let delay_int = delay.floor() as usize;
+    let delay_frac = delay - delay.floor();
+
+    // the (fractionally) delayed impulse ...
+    let mut ir = vec![0.0; len];
+    if delay_int < len {
+        ir[delay_int] = 1.0 - delay_frac;
+    }
+    if delay_int + 1 < len {
+        ir[delay_int + 1] = delay_frac;
+    }
+
+    // ... through the shelf (bilinear transform)
+    let k = 2.0 * samplerate;
+    let b0 = (2.0 * w0 + alpha * k) / (2.0 * w0 + k);
+    let b1 = (2.0 * w0 - alpha * k) / (2.0 * w0 + k);
+    let a1 = (2.0 * w0 - k) / (2.0 * w0 + k);
+    let mut x1 = 0.0;
+    let mut y1 = 0.0;
+    for s in ir.iter_mut() {
+        let y = b0 * *s + b1 * x1 - a1 * y1;
+        x1 = *s;
+        y1 = y;
+        *s = y;
+    }
+
+    ir
+}
+
+/// Binaural filters for the given order, derived from a spherical head model.
+/// The sound field is decoded to virtual speakers spread evenly around
+/// the head, each of them is rendered with the head model.
+pub(crate) fn spherical_head_filter(
+    order: usize,
+    samplerate: f32,
+    max_len: usize,
+) -> Vec<(Vec<f32>, Vec<f32>)> {
+    let channels = ambisonic_channels(order);
+    // a few milliseconds are enough for the delay and the shelf
+    let len = ((0.0025 * samplerate) as usize).clamp(1, max_len.max(1));
+
+    // virtual speakers on a fibonacci sphere
+    let num_speakers = 50;
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+
+    let weights = max_re_weights_3d(order);
+    let mut filters = vec![(vec![0.0; len], vec![0.0; len]); channels];
+
+    for i in 0..num_speakers {
+        let elevation = (1.0 - 2.0 * (i as f32 + 0.5) / num_speakers as f32).asin();
+        let azimuth = i as f32 * golden_angle;
+
+        let coefs: [f32; MAX_AMBISONIC_CHANNELS] = sh_coefficients(azimuth, elevation);
+        // the left ear is at y = 1, see the encoder conventions
+        let y = coefs[1];
+        let left = head_model_hrir(y.clamp(-1.0, 1.0).acos(), samplerate, len);
+        let right = head_model_hrir((-y).clamp(-1.0, 1.0).acos(), samplerate, len);
+
+        for (acn, (fl, fr)) in filters.iter_mut().enumerate() {
+            let n = channel_order(acn);
+            let gain = (2 * n + 1) as f32 * weights[n] * coefs[acn] / num_speakers as f32;
+            for s in 0..len {
+                fl[s] += left[s] * gain;
+                fr[s] += right[s] * gain;
+            }
+        }
+    }
+
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    #[test]
+    fn test_load() {
+        let _bin = BinauralizerO1::<128>::default_filter(44100.0);
+    }
+
+    #[test]
+    fn test_head_model() {
+        let mut bin = BinauralizerO3::<128>::default_filter(48000.0);
+        assert_eq!(bin.left.len(), 16);
+
+        // a click from the right
+        let coefs: [f32; 16] = sh_coefficients(std::f32::consts::PI / 2.0, 0.0);
+        let mut input = [[0.0; 128]; 16];
+        for (ch, c) in input.iter_mut().zip(coefs.iter()) {
+            ch[0] = *c;
+        }
+
+        let out = bin.binauralize(&input);
+        let energy = |ch: &[f32; 128]| ch.iter().map(|s| s * s).sum::<f32>();
+        assert!(energy(&out[1]) > 2.0 * energy(&out[0]));
+
+        // the left ear hears it later
+        let onset = |ch: &[f32; 128]| ch.iter().position(|s| s.abs() > 0.05).unwrap();
+        assert!(onset(&out[0]) > onset(&out[1]));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, channel_order, max_re_weights_3d, sh_coefficients, DecoderWeighting,
+    MAX_AMBISONIC_CHANNELS, MAX_AMBISONIC_ORDER,
+};
+
+/**
+ * An ambisonics decoder for loudspeaker arrays, the order is determined by the
+ * number of channels (4, 9 or 16 for first, second or third order).
+ *
+ * This is a sampling decoder, each speaker gets the sound field as "seen"
+ * from its direction. Works best with regular layouts (rings, domes).
+ * If all speakers are on the horizontal plane, the decoder is 2D, otherwise 3D.
+ */
+pub struct AmbisonicDecoder<const BUFSIZE: usize, const ACH: usize, const NCHAN: usize> {
+    matrix: [[f32; ACH]; NCHAN],
+}
+
+pub type DecoderO1<const BUFSIZE: usize, const NCHAN: usize> = AmbisonicDecoder<BUFSIZE, 4, NCHAN>;
+pub type DecoderO2<const BUFSIZE: usize, const NCHAN: usize> = AmbisonicDecoder<BUFSIZE, 9, NCHAN>;
+pub type DecoderO3<const BUFSIZE: usize, const NCHAN: usize> = AmbisonicDecoder<BUFSIZE, 16, NCHAN>;
+
+impl<const BUFSIZE: usize, const ACH: usize, const NCHAN: usize>
+    AmbisonicDecoder<BUFSIZE, ACH, NCHAN>
+{
+    /// The layout is one (azimuth, elevation) pair per output channel, in radians.
+    /// Channels without a speaker position stay silent.
+    pub fn new(layout: &[(f32, f32)], weighting: DecoderWeighting) -> Self {
+        Self::with_order(super::ambisonic_order(ACH), layout, weighting)
+    }
+
+    /// Decode only up to the given order, the higher input channels are ignored.
+    /// A ring of speakers needs at least 2 * order + 1 speakers, a 3D layout
+    /// at least (order + 1)^2, otherwise the sound field can't be reproduced.
+    pub fn with_order(order: usize, layout: &[(f32, f32)], weighting: DecoderWeighting) -> Self {
+        let order = order.clamp(1, MAX_AMBISONIC_ORDER);
+        let channels = ambisonic_channels(order).min(ACH);
+        let num_speakers = layout.len().min(NCHAN) as f32;
+        let is_2d = layout.iter().all(|(_, ele)| ele.abs() < 0.001);
+
+        // gain of each order, relative to the omni component
+        let mut order_gains = [0.0; MAX_AMBISONIC_ORDER + 1];
+        let weights = if weighting == DecoderWeighting::MaxRe {
+            if is_2d {
+                let mut w = [1.0; MAX_AMBISONIC_ORDER + 1];
+                for (n, w) in w.iter_mut().enumerate() {
+                    *w = (n as f32 * std::f32::consts::PI / (2 * order + 2) as f32).cos();
+                }
+                w
+            } else {
+                max_re_weights_3d(order)
+            }
+        } else {
+            [1.0; MAX_AMBISONIC_ORDER + 1]
+        };
+
+        for (n, gain) in order_gains.iter_mut().enumerate().take(order + 1) {
+            *gain = if n == 0 {
+                1.0
+            } else if is_2d {
+                // only the sectoral components are used, which
+                // need to be scaled up to the circular harmonics
+                2.0 / sectoral_norm_squared(n) * weights[n]
+            } else {
+                (2 * n + 1) as f32 * weights[n]
+            };
+        }
+
+        let mut matrix = [[0.0; ACH]; NCHAN];
+        for (gains, (azi, ele)) in matrix.iter_mut().zip(layout.iter()) {
+            let coefs: [f32; MAX_AMBISONIC_CHANNELS] = sh_coefficients(*azi, *ele);
+            for (acn, gain) in gains.iter_mut().enumerate().take(channels) {
+                let n = channel_order(acn);
+                // the horizontal-only decoder ignores the non-sectoral components
+                if is_2d && acn != n * n && acn != n * n + 2 * n {
+                    continue;
+                }
+                *gain = coefs[acn] * order_gains[n] / num_speakers;
+            }
+        }
+
+        AmbisonicDecoder { matrix }
+    }
+
+    pub fn decode(&self, input: &[[f32; BUFSIZE]; ACH]) -> [[f32; BUFSIZE]; NCHAN] {
+        let mut out_buf = [[0.0; BUFSIZE]; NCHAN];
+
+        for (out, gains) in out_buf.iter_mut().zip(self.matrix.iter()) {
+            for (ach, gain) in gains.iter().enumerate() {
+                if *gain == 0.0 {
+                    continue;
+                }
+                for s in 0..BUFSIZE {
+                    out[s] += input[ach][s] * gain;
+                }
+            }
+        }
+
+        out_buf
+    }
+}
+
+// the squared SN3D normalization of the sectoral components on the horizontal plane
+fn sectoral_norm_squared(order: usize) -> f32 {
+    match order {
+        1 => 1.0,
+        2 => 0.75,
+        _ => 0.625,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ring() {
+        // eight speakers in a ring
+        let layout: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+
+        // source right at the third speaker
+        let coefs: [f32; 4] = sh_coefficients(std::f32::consts::PI / 2.0, 0.0);
+        let input = coefs.map(|c| [c; 128]);
+
+        let basic = DecoderO1::<128, 8>::new(&layout, DecoderWeighting::Basic).decode(&input);
+        let max_re = DecoderO1::<128, 8>::new(&layout, DecoderWeighting::MaxRe).decode(&input);
+
+        for out in [basic, max_re] {
+            // the speaker in the direction of the source is the loudest
+            for c in 0..8 {
+                assert!(out[c][0] <= out[2][0]);
+            }
+            // the pressure sums up to the source level
+            let sum: f32 = out.iter().map(|o| o[0]).sum();
+            assert!((sum - 1.0).abs() < 0.0001);
+        }
+
+        // less crosstalk to the opposite speaker with max-rE weighting
+        assert!(max_re[6][0].abs() < basic[6][0].abs());
+    }
+
+    #[test]
+    fn test_decode_higher_orders() {
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+
+        let coefs: [f32; 16] = sh_coefficients(std::f32::consts::PI / 2.0, 0.0);
+        let input = coefs.map(|c| [c; 128]);
+
+        let o1 = DecoderO3::<128, 8>::with_order(1, &ring, DecoderWeighting::Basic).decode(&input);
+        let o3 = DecoderO3::<128, 8>::new(&ring, DecoderWeighting::Basic).decode(&input);
+
+        // the higher order concentrates the source on fewer speakers
+        assert!(o3[2][0] > 2.0 * o1[2][0]);
+        assert!(o3[1][0] < 0.5 * o1[1][0]);
+        let sum: f32 = o3.iter().map(|o| o[0]).sum();
+        assert!((sum - 1.0).abs() < 0.0001);
+
+        // a 3D layout (octahedron plus a ring in between)
+        let mut dome: Vec<(f32, f32)> = (0..4)
+            .map(|i| (i as f32 * std::f32::consts::PI / 2.0, 0.0))
+            .collect();
+        dome.push((0.0, std::f32::consts::PI / 2.0));
+        dome.push((0.0, -std::f32::consts::PI / 2.0));
+        for i in 0..8 {
+            let azi = (i as f32 + 0.5) * std::f32::consts::PI / 4.0;
+            let ele = if i % 2 == 0 { 0.6 } else { -0.6 };
+            dome.push((azi, ele));
+        }
+
+        // source from above
+        let coefs: [f32; 9] = sh_coefficients(0.0, std::f32::consts::PI / 2.0);
+        let input = coefs.map(|c| [c; 128]);
+        let out = DecoderO2::<128, 14>::new(&dome, DecoderWeighting::MaxRe).decode(&input);
+        for c in 0..14 {
+            assert!(out[c][0] <= out[4][0]);
+        }
+        assert!(out[5][0].abs() < 0.2 * out[4][0]);
+    }
+}
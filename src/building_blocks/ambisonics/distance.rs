@@ -0,0 +1,219 @@
+use crate::building_blocks::{
+    Modulator, ModulatorResult, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+};
+
+use std::f32::consts::PI;
+
+const SPEED_OF_SOUND: f32 = 343.0;
+
+// up to this distance (meters), nothing changes
+const REFERENCE_DISTANCE: f32 = 1.0;
+
+// the cutoff of the air absorption lowpass is this (Hz * meters)
+// divided by the distance beyond the reference, i.e. 2kHz at 100 meters
+const AIR_ABSORPTION: f32 = 200000.0;
+
+// the longest travel time for the doppler effect (about 100 meters),
+// sources further away stay at that delay
+const MAX_DOPPLER_DELAY: f32 = 0.3;
+
+// time constant of the distance smoothing
+const SMOOTHING_TIME: f32 = 0.02;
+
+/**
+ * Distance cues for a mono source, with the distance in meters.
+ *
+ * The level falls with the inverse distance, and the air absorbs the highs.
+ * Optionally, the travel time of the sound is modeled with a delay line, so
+ * the pitch of moving sources changes (doppler effect). Note that this delays
+ * the source by the travel time. The delay line is allocated up front, so the
+ * effect can be switched on while the source is running.
+ *
+ * Sources closer than one meter are treated as if they were at one meter.
+ */
+pub struct DistanceModel<const BUFSIZE: usize> {
+    distance: f32,
+    distance_mod: Option<Modulator<BUFSIZE>>,
+    current: f32, // smoothed distance
+    smoothing_coef: f32,
+    started: bool, // before the first block, the distance doesn't need to be smoothed
+    lowpass_state: f32,
+    doppler: bool,
+    delay_line: Vec<f32>,
+    delay_idx: usize,
+    samplerate: f32,
+}
+
+impl<const BUFSIZE: usize> DistanceModel<BUFSIZE> {
+    pub fn new(samplerate: f32) -> Self {
+        DistanceModel {
+            distance: REFERENCE_DISTANCE,
+            distance_mod: None,
+            current: REFERENCE_DISTANCE,
+            smoothing_coef: (-1.0 / (SMOOTHING_TIME * samplerate)).exp(),
+            started: false,
+            lowpass_state: 0.0,
+            doppler: false,
+            delay_line: vec![0.0; (MAX_DOPPLER_DELAY * samplerate) as usize + 2],
+            delay_idx: 0,
+            samplerate,
+        }
+    }
+
+    pub fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        if par == SynthParameterLabel::AmbisonicDistance {
+            self.distance = init; // keep for later
+            Ok(self.distance_mod.replace(modulator))
+        } else {
+            Err(modulator)
+        }
+    }
+
+    pub fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::AmbisonicDistance => {
+                    self.distance = val.max(0.0);
+                    if !self.started {
+                        self.current = self.distance;
+                    }
+                }
+                SynthParameterLabel::AmbisonicDoppler => {
+                    // without a delay line (i.e. at a zero samplerate), there's nothing to switch on
+                    self.doppler = *val > 0.0 && self.delay_line.len() > 2;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Distant sources are wetter: the reverb send falls with the
+    /// square root of the distance only, relative to the dry level.
+    pub fn reverb_scale(&self) -> f32 {
+        (self.current.max(REFERENCE_DISTANCE) / REFERENCE_DISTANCE).sqrt()
+    }
+
+    pub fn process_block(
+        &mut self,
+        input: [f32; BUFSIZE],
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [f32; BUFSIZE] {
+        self.started = true;
+
+        // nothing to do at the reference distance
+        if self.distance_mod.is_none()
+            && !self.doppler
+            && self.distance <= REFERENCE_DISTANCE
+            && self.current <= REFERENCE_DISTANCE
+        {
+            self.current = self.distance;
+            return input;
+        }
+
+        let targets = if let Some(m) = self.distance_mod.as_mut() {
+            m.process(self.distance, start_sample, in_buffers)
+        } else {
+            [self.distance; BUFSIZE]
+        };
+
+        let mut out = [0.0; BUFSIZE];
+        for s in 0..BUFSIZE {
+            self.current = targets[s] + (self.current - targets[s]) * self.smoothing_coef;
+            let distance = self.current.max(REFERENCE_DISTANCE);
+
+            let mut sample = input[s] * REFERENCE_DISTANCE / distance;
+
+            // air absorption, a one-pole lowpass
+            let beyond = distance - REFERENCE_DISTANCE;
+            if beyond > 0.0 {
+                let cutoff = AIR_ABSORPTION / beyond;
+                let a = 1.0 - (-2.0 * PI * cutoff / self.samplerate).exp();
+                self.lowpass_state += (sample - self.lowpass_state) * a;
+                sample = self.lowpass_state;
+            } else {
+                self.lowpass_state = sample;
+            }
+
+            // the travel time, read with linear interpolation
+            if self.doppler {
+                let len = self.delay_line.len();
+                self.delay_line[self.delay_idx] = sample;
+
+                let delay = (distance / SPEED_OF_SOUND * self.samplerate).min((len - 2) as f32);
+                let delay_int = delay.floor() as usize;
+                let frac = delay - delay.floor();
+                let a = self.delay_line[(self.delay_idx + len - delay_int) % len];
+                let b = self.delay_line[(self.delay_idx + len - delay_int - 1) % len];
+                sample = a + (b - a) * frac;
+
+                self.delay_idx = (self.delay_idx + 1) % len;
+            }
+
+            out[s] = sample;
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_model() {
+        let mut near = DistanceModel::<128>::new(44100.0);
+        let mut far = DistanceModel::<128>::new(44100.0);
+        far.set_parameter(
+            SynthParameterLabel::AmbisonicDistance,
+            &SynthParameterValue::ScalarF32(4.0),
+        );
+
+        // at the reference distance, the signal passes unchanged
+        let input = [0.5; 128];
+        assert_eq!(near.process_block(input, 0, &[]), input);
+        assert_eq!(near.reverb_scale(), 1.0);
+
+        // a quarter of the level, no fade in as the distance was set before
+        let out = far.process_block(input, 0, &[]);
+        assert!((out[127] - 0.125).abs() < 0.001);
+        assert_eq!(far.reverb_scale(), 2.0);
+
+        // the highs are absorbed further away
+        far.set_parameter(
+            SynthParameterLabel::AmbisonicDistance,
+            &SynthParameterValue::ScalarF32(100.0),
+        );
+        let mut alternating = [0.0; 128];
+        for (s, a) in alternating.iter_mut().enumerate() {
+            *a = if s % 2 == 0 { 1.0 } else { -1.0 };
+        }
+        let mut out = [0.0; 128];
+        for _ in 0..100 {
+            out = far.process_block(alternating, 0, &[]);
+        }
+        assert!(out[127].abs() < 0.01 * 0.2);
+
+        // with the doppler effect, the sound arrives later
+        let mut doppler = DistanceModel::<512>::new(44100.0);
+        doppler.set_parameter(
+            SynthParameterLabel::AmbisonicDoppler,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        doppler.set_parameter(
+            SynthParameterLabel::AmbisonicDistance,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        let mut click = [0.0; 512];
+        click[0] = 1.0;
+        let out = doppler.process_block(click, 0, &[]);
+        let arrival = out.iter().position(|s| s.abs() > 0.1).unwrap();
+        assert_eq!(arrival, (44100.0 / SPEED_OF_SOUND) as usize);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::building_blocks::ambisonics::distance::DistanceModel;
+use crate::building_blocks::ambisonics::sh_coefficients;
+use crate::building_blocks::{
+    Modulator, ModulatorResult, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+};
+
+/**
+ * An ambisonics encoder, the order is determined by the number
+ * of channels (4, 9 or 16 for first, second or third order).
+ * The source can be moved away with the `AmbisonicDistance` parameter.
+ */
+pub struct AmbisonicEncoder<const BUFSIZE: usize, const ACH: usize> {
+    azimuth: f32,
+    elevation: f32,
+    azimuth_mod: Option<Modulator<BUFSIZE>>,
+    elevation_mod: Option<Modulator<BUFSIZE>>,
+    coefs: [f32; ACH],
+    distance: DistanceModel<BUFSIZE>,
+}
+
+pub type EncoderO1<const BUFSIZE: usize> = AmbisonicEncoder<BUFSIZE, 4>;
+pub type EncoderO2<const BUFSIZE: usize> = AmbisonicEncoder<BUFSIZE, 9>;
+pub type EncoderO3<const BUFSIZE: usize> = AmbisonicEncoder<BUFSIZE, 16>;
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicEncoder<BUFSIZE, ACH> {
+    pub fn new(samplerate: f32) -> Self {
+        AmbisonicEncoder {
+            azimuth: 0.0,
+            elevation: 0.0,
+            azimuth_mod: None,
+            elevation_mod: None,
+            coefs: sh_coefficients(0.0, 0.0),
+            distance: DistanceModel::new(samplerate),
+        }
+    }
+
+    pub fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        match par {
+            SynthParameterLabel::AmbisonicAzimuth => {
+                self.azimuth = init; // keep for later
+                Ok(self.azimuth_mod.replace(modulator))
+            }
+            SynthParameterLabel::AmbisonicElevation => {
+                self.elevation = init; // keep for later
+                Ok(self.elevation_mod.replace(modulator))
+            }
+            _ => self.distance.set_modulator(par, init, modulator),
+        }
+    }
+
+    // some parameter limits might be nice ...
+    pub fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::AmbisonicAzimuth => self.azimuth = *val,
+                SynthParameterLabel::AmbisonicElevation => self.elevation = *val,
+                _ => {
+                    self.distance.set_parameter(par, value);
+                    return;
+                }
+            };
+
+            self.coefs = sh_coefficients(self.azimuth, self.elevation);
+        }
+    }
+
+    /// scales the reverb send of the source, so distant sources are wetter
+    pub fn reverb_scale(&self) -> f32 {
+        self.distance.reverb_scale()
+    }
+
+    pub fn process_block(
+        &mut self,
+        input: [f32; BUFSIZE],
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; ACH] {
+        let input = self.distance.process_block(input, start_sample, in_buffers);
+        let mut enc_block = [[0.0; BUFSIZE]; ACH];
+
+        if self.azimuth_mod.is_some() || self.elevation_mod.is_some() {
+            let azi_buf = if let Some(azi_mod) = self.azimuth_mod.as_mut() {
+                azi_mod.process(self.azimuth, start_sample, in_buffers)
+            } else {
+                [self.azimuth; BUFSIZE]
+            };
+            let ele_buf = if let Some(ele_mod) = self.elevation_mod.as_mut() {
+                ele_mod.process(self.elevation, start_sample, in_buffers)
+            } else {
+                [self.elevation; BUFSIZE]
+            };
+
+            for s in 0..BUFSIZE {
+                let coefs: [f32; ACH] = sh_coefficients(azi_buf[s], ele_buf[s]);
+                for (ach, coef) in coefs.iter().enumerate() {
+                    enc_block[ach][s] = input[s] * coef;
+                }
+            }
+        } else {
+            for (ach, coef) in self.coefs.iter().enumerate() {
+                for s in 0..BUFSIZE {
+                    enc_block[ach][s] = input[s] * coef;
+                }
+            }
+        }
+
+        enc_block
+    }
+}
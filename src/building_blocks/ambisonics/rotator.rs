@@ -0,0 +1,377 @@
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, channel_order, direction_angles, sh_coefficients, MAX_AMBISONIC_CHANNELS,
+    MAX_AMBISONIC_ORDER,
+};
+use crate::building_blocks::{
+    Modulator, SampleBuffer, SynthParameterLabel, SynthParameterValue, ValueOrModulator,
+};
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+// time constant of the angle smoothing
+const SMOOTHING_TIME: f32 = 0.01;
+
+// 4-point gauss-legendre quadrature in z, together with eight azimuths
+// this integrates products of spherical harmonics up to the third order exactly
+const GAUSS_NODES: [f32; 4] = [-0.861_136_3, -0.339_981_04, 0.339_981_04, 0.861_136_3];
+const GAUSS_WEIGHTS: [f32; 4] = [0.347_854_85, 0.652_145_15, 0.652_145_15, 0.347_854_85];
+const QUADRATURE_AZIMUTHS: usize = 8;
+
+type Matrix3 = [[f32; 3]; 3];
+
+/// whether the parameter is one of the sound field rotation angles
+pub fn is_rotation_parameter(par: SynthParameterLabel) -> bool {
+    matches!(
+        par,
+        SynthParameterLabel::AmbisonicYaw
+            | SynthParameterLabel::AmbisonicPitch
+            | SynthParameterLabel::AmbisonicRoll
+    )
+}
+
+/**
+ * Rotates an ambisonic sound field, i.e. for head tracking.
+ *
+ * The rotation is applied as roll (around the front axis) first, then pitch
+ * (around the left axis), then yaw (around the vertical axis). A positive yaw
+ * increases the azimuth of all sources, so to compensate a head movement,
+ * the angles of the head need to be inverted.
+ *
+ * Yaw is a simple rotation of the components of each order, pitch and roll
+ * are turned into yaw by fixed 90 degree rotations. The angles are smoothed
+ * per sample, so fast movements don't cause zipper noise.
+ */
+pub struct AmbisonicRotator<const BUFSIZE: usize, const ACH: usize> {
+    angles: [f32; 3],  // yaw, pitch, roll
+    current: [f32; 3], // the smoothed angles
+    modulators: [Option<Modulator<BUFSIZE>>; 3],
+    smoothing_coef: f32,
+    order: usize,
+    channels: usize,
+    front_to_up: [[f32; ACH]; ACH],   // turns roll into yaw ...
+    roll_to_pitch: [[f32; ACH]; ACH], // ... and back, then pitch into yaw
+    left_to_up: [[f32; ACH]; ACH],    // turns pitch into yaw, if there is no roll
+    pitch_to_yaw: [[f32; ACH]; ACH],  // ... and back
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicRotator<BUFSIZE, ACH> {
+    /// the order follows from the number of channels
+    pub fn new(samplerate: f32) -> Self {
+        Self::with_order(super::ambisonic_order(ACH), samplerate)
+    }
+
+    /// Only rotate the channels up to the given order, the others are left alone.
+    pub fn with_order(order: usize, samplerate: f32) -> Self {
+        let order = order
+            .clamp(1, MAX_AMBISONIC_ORDER)
+            .min(super::ambisonic_order(ACH));
+        AmbisonicRotator {
+            angles: [0.0; 3],
+            current: [0.0; 3],
+            modulators: [None, None, None],
+            smoothing_coef: (-1.0 / (SMOOTHING_TIME * samplerate)).exp(),
+            order,
+            channels: ambisonic_channels(order),
+            front_to_up: rotation_matrix(rot_y(-FRAC_PI_2)),
+            roll_to_pitch: rotation_matrix(mat_mul(rot_x(FRAC_PI_2), rot_y(FRAC_PI_2))),
+            left_to_up: rotation_matrix(rot_x(FRAC_PI_2)),
+            pitch_to_yaw: rotation_matrix(rot_x(-FRAC_PI_2)),
+        }
+    }
+
+    /// Set an angle (in radians). Returns the modulator that has been
+    /// replaced by the value, if any, so it can be dropped elsewhere.
+    pub fn set_parameter(
+        &mut self,
+        par: SynthParameterLabel,
+        value: &SynthParameterValue,
+    ) -> Option<Modulator<BUFSIZE>> {
+        if let (Some(idx), SynthParameterValue::ScalarF32(val)) = (angle_index(par), value) {
+            self.angles[idx] = *val;
+            self.modulators[idx].take()
+        } else {
+            None
+        }
+    }
+
+    /// Modulate an angle. Returns the replaced modulator, if any, or the
+    /// given one if the parameter isn't an angle.
+    pub fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> Option<Modulator<BUFSIZE>> {
+        if let Some(idx) = angle_index(par) {
+            self.angles[idx] = init; // keep for later
+            self.modulators[idx].replace(modulator)
+        } else {
+            Some(modulator)
+        }
+    }
+
+    pub fn set_param_or_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        val_or_mod: ValueOrModulator<BUFSIZE>,
+    ) -> Option<Modulator<BUFSIZE>> {
+        match val_or_mod {
+            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
+            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+        }
+    }
+
+    /// rotate the sound field in place
+    pub fn process(&mut self, block: &mut [[f32; BUFSIZE]; ACH], in_buffers: &[SampleBuffer]) {
+        if self.modulators.iter().all(|m| m.is_none())
+            && self.current == self.angles
+            && self.angles == [0.0; 3]
+        {
+            return;
+        }
+
+        let mut targets = [[0.0; BUFSIZE]; 3];
+        for (i, target) in targets.iter_mut().enumerate() {
+            *target = if let Some(m) = self.modulators[i].as_mut() {
+                m.process(self.angles[i], 0, in_buffers)
+            } else {
+                [self.angles[i]; BUFSIZE]
+            };
+        }
+
+        let mut frame = [0.0; ACH];
+        for s in 0..BUFSIZE {
+            for (i, current) in self.current.iter_mut().enumerate() {
+                // take the shorter way around
+                let diff = wrap_angle(targets[i][s] - *current);
+                *current = if diff.abs() < 0.00001 {
+                    targets[i][s]
+                } else {
+                    wrap_angle(targets[i][s] - diff * self.smoothing_coef)
+                };
+            }
+            let [yaw, pitch, roll] = self.current;
+
+            for (f, ch) in frame.iter_mut().zip(block.iter()).take(self.channels) {
+                *f = ch[s];
+            }
+
+            if roll != 0.0 {
+                apply_matrix(&self.front_to_up, &mut frame, self.channels);
+                rotate_z(&mut frame, roll, self.order);
+                apply_matrix(&self.roll_to_pitch, &mut frame, self.channels);
+                rotate_z(&mut frame, pitch, self.order);
+                apply_matrix(&self.pitch_to_yaw, &mut frame, self.channels);
+            } else if pitch != 0.0 {
+                apply_matrix(&self.left_to_up, &mut frame, self.channels);
+                rotate_z(&mut frame, pitch, self.order);
+                apply_matrix(&self.pitch_to_yaw, &mut frame, self.channels);
+            }
+            rotate_z(&mut frame, yaw, self.order);
+
+            for (f, ch) in frame.iter().zip(block.iter_mut()).take(self.channels) {
+                ch[s] = *f;
+            }
+        }
+    }
+}
+
+fn angle_index(par: SynthParameterLabel) -> Option<usize> {
+    match par {
+        SynthParameterLabel::AmbisonicYaw => Some(0),
+        SynthParameterLabel::AmbisonicPitch => Some(1),
+        SynthParameterLabel::AmbisonicRoll => Some(2),
+        _ => None,
+    }
+}
+
+// wrap into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    angle - 2.0 * PI * (angle / (2.0 * PI)).round()
+}
+
+// rotation around the vertical axis, which only mixes the
+// components of the same order and the same |m| pairwise
+#[inline(always)]
+fn rotate_z<const ACH: usize>(frame: &mut [f32; ACH], angle: f32, order: usize) {
+    if angle == 0.0 {
+        return;
+    }
+    let (s1, c1) = angle.sin_cos();
+    let c2 = c1 * c1 - s1 * s1;
+    let s2 = 2.0 * s1 * c1;
+    let cos_m = [1.0, c1, c2, c2 * c1 - s2 * s1];
+    let sin_m = [0.0, s1, s2, s2 * c1 + c2 * s1];
+
+    for n in 1..=order {
+        let center = n * n + n;
+        for m in 1..=n {
+            let c = frame[center + m];
+            let s = frame[center - m];
+            frame[center + m] = c * cos_m[m] - s * sin_m[m];
+            frame[center - m] = s * cos_m[m] + c * sin_m[m];
+        }
+    }
+}
+
+// the matrices are block-diagonal, as rotations never mix orders
+#[inline(always)]
+fn apply_matrix<const ACH: usize>(
+    matrix: &[[f32; ACH]; ACH],
+    frame: &mut [f32; ACH],
+    channels: usize,
+) {
+    let mut out = [0.0; ACH];
+    for (a, o) in out.iter_mut().enumerate().take(channels) {
+        let n = channel_order(a);
+        for b in n * n..(n + 1) * (n + 1) {
+            *o += matrix[a][b] * frame[b];
+        }
+    }
+    frame[..channels].copy_from_slice(&out[..channels]);
+}
+
+fn rot_x(angle: f32) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[1.0, 0.0, 0.0], [0.0, c, -s], [0.0, s, c]]
+}
+
+fn rot_y(angle: f32) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, 0.0, s], [0.0, 1.0, 0.0], [-s, 0.0, c]]
+}
+
+#[cfg(test)]
+fn rot_z(angle: f32) -> Matrix3 {
+    let (s, c) = angle.sin_cos();
+    [[c, -s, 0.0], [s, c, 0.0], [0.0, 0.0, 1.0]]
+}
+
+fn mat_mul(a: Matrix3, b: Matrix3) -> Matrix3 {
+    let mut out = [[0.0; 3]; 3];
+    for i in 0..3 {
+        for j in 0..3 {
+            out[i][j] = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn mat_vec(a: &Matrix3, v: [f32; 3]) -> [f32; 3] {
+    [
+        a[0][0] * v[0] + a[0][1] * v[1] + a[0][2] * v[2],
+        a[1][0] * v[0] + a[1][1] * v[1] + a[1][2] * v[2],
+        a[2][0] * v[0] + a[2][1] * v[1] + a[2][2] * v[2],
+    ]
+}
+
+// The matrix that rotates a sound field like the given rotation rotates
+// a direction, found by projecting the rotated spherical harmonics onto
+// the original ones: M[a][b] = (2n + 1) / 4PI * integral(Y_a(R d) * Y_b(d))
+fn rotation_matrix<const ACH: usize>(rotation: Matrix3) -> [[f32; ACH]; ACH] {
+    let mut matrix = [[0.0; MAX_AMBISONIC_CHANNELS]; MAX_AMBISONIC_CHANNELS];
+    for (z, weight) in GAUSS_NODES.iter().zip(GAUSS_WEIGHTS.iter()) {
+        for i in 0..QUADRATURE_AZIMUTHS {
+            let phi = 2.0 * PI * i as f32 / QUADRATURE_AZIMUTHS as f32;
+            let r = (1.0 - z * z).sqrt();
+            let d = [r * phi.cos(), r * phi.sin(), *z];
+
+            let (azi, ele) = direction_angles(d);
+            let orig: [f32; MAX_AMBISONIC_CHANNELS] = sh_coefficients(azi, ele);
+            let (azi, ele) = direction_angles(mat_vec(&rotation, d));
+            let rotated: [f32; MAX_AMBISONIC_CHANNELS] = sh_coefficients(azi, ele);
+
+            let w = weight * 2.0 * PI / QUADRATURE_AZIMUTHS as f32;
+            for a in 0..MAX_AMBISONIC_CHANNELS {
+                let n = channel_order(a);
+                for b in n * n..(n + 1) * (n + 1) {
+                    matrix[a][b] += (2 * n + 1) as f32 / (4.0 * PI) * w * rotated[a] * orig[b];
+                }
+            }
+        }
+    }
+
+    let mut out = [[0.0; ACH]; ACH];
+    for (o, m) in out.iter_mut().zip(matrix.iter()) {
+        for (o, m) in o.iter_mut().zip(m.iter()) {
+            *o = *m;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building_blocks::ambisonics::direction_vector;
+
+    // run constant blocks through the rotator until the smoothing has settled
+    fn settle(rot: &mut AmbisonicRotator<128, 16>, coefs: &[f32; 16]) -> [f32; 16] {
+        let mut out = [0.0; 16];
+        for _ in 0..100 {
+            let mut block = coefs.map(|c| [c; 128]);
+            rot.process(&mut block, &[]);
+            out = block.map(|ch| ch[127]);
+        }
+        out
+    }
+
+    #[test]
+    fn test_rotate_yaw() {
+        let mut rot = AmbisonicRotator::<128, 16>::new(44100.0);
+        let coefs: [f32; 16] = sh_coefficients(0.3, 0.2);
+
+        // no rotation, no change
+        assert_eq!(settle(&mut rot, &coefs), coefs);
+
+        rot.set_parameter(
+            SynthParameterLabel::AmbisonicYaw,
+            &SynthParameterValue::ScalarF32(PI / 2.0),
+        );
+
+        // no jumps, the rotation is smoothed
+        let mut block = coefs.map(|c| [c; 128]);
+        rot.process(&mut block, &[]);
+        for ch in block.iter() {
+            for s in 1..128 {
+                assert!((ch[s] - ch[s - 1]).abs() < 0.05);
+            }
+        }
+
+        let out = settle(&mut rot, &coefs);
+        let expected: [f32; 16] = sh_coefficients(0.3 + PI / 2.0, 0.2);
+        for (o, e) in out.iter().zip(expected.iter()) {
+            assert!((o - e).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_rotate_pitch_roll() {
+        for (yaw, pitch, roll) in [(0.0, 0.4, 0.0), (0.0, 0.0, -1.1), (2.5, -0.7, 0.9)] {
+            let mut rot = AmbisonicRotator::<128, 16>::new(44100.0);
+            rot.set_parameter(
+                SynthParameterLabel::AmbisonicYaw,
+                &SynthParameterValue::ScalarF32(yaw),
+            );
+            rot.set_parameter(
+                SynthParameterLabel::AmbisonicPitch,
+                &SynthParameterValue::ScalarF32(pitch),
+            );
+            rot.set_parameter(
+                SynthParameterLabel::AmbisonicRoll,
+                &SynthParameterValue::ScalarF32(roll),
+            );
+
+            let coefs: [f32; 16] = sh_coefficients(1.0, -0.3);
+            let out = settle(&mut rot, &coefs);
+
+            // the same as encoding from the rotated direction
+            let rotation = mat_mul(mat_mul(rot_z(yaw), rot_y(pitch)), rot_x(roll));
+            let (azi, ele) = direction_angles(mat_vec(&rotation, direction_vector(1.0, -0.3)));
+            let expected: [f32; 16] = sh_coefficients(azi, ele);
+            for (o, e) in out.iter().zip(expected.iter()) {
+                assert!((o - e).abs() < 0.001);
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 #[repr(C)]
@@ -47,10 +47,12 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for Bitcrusher<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         if let SynthParameterLabel::BitcrusherBits = par {
             self.bits = init as u32;
-            self.bits_mod = Some(modulator);
+            Ok(self.bits_mod.replace(modulator))
+        } else {
+            Err(modulator)
         }
     }
 
@@ -86,6 +86,14 @@ impl<const BUFSIZE: usize> UniformPartitionedConvolution<BUFSIZE> {
         }
     }
 
+    /// clear the internal state, that is, the tail of the convolution
+    pub fn clear(&mut self) {
+        for bin in self.frequency_delay_line.iter_mut() {
+            bin.fill(Complex::new(0.0, 0.0));
+        }
+        self.remainder.fill(0.0);
+    }
+
     /// perform the convolution
     pub fn convolve(&mut self, input: [f32; BUFSIZE]) -> [f32; BUFSIZE] {
         // assemble input block from remainder part from previous block
@@ -1,7 +1,8 @@
 use crate::building_blocks::filters::*;
 use crate::building_blocks::interpolation::*;
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue, ValueOrModulator,
+    pass_leftover, Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
 };
 
 use super::FilterType;
@@ -13,7 +14,8 @@ pub struct MonoDelay<const BUFSIZE: usize> {
     feedback: f32,
 
     // internal parameters
-    buffer: Vec<f32>, // max 2 sec for now
+    buffer: Vec<f32>, // max 2 sec by default
+    max_time: f32,
     buffer_ptr: f32,
     max_buffer_ptr: f32,
     dampening_filter: Box<dyn MonoEffect<BUFSIZE> + Sync + Send>,
@@ -23,6 +25,11 @@ pub struct MonoDelay<const BUFSIZE: usize> {
     rate_mod: Option<Modulator<BUFSIZE>>,
     time_mod: Option<Modulator<BUFSIZE>>,
     fb_mod: Option<Modulator<BUFSIZE>>,
+
+    // per-sample parameter values, so a block can be processed in segments
+    fb_buf: [f32; BUFSIZE],
+    rate_buf: [f32; BUFSIZE],
+    time_buf: [f32; BUFSIZE],
 }
 
 impl<const BUFSIZE: usize> MonoDelay<BUFSIZE> {
@@ -31,6 +38,7 @@ impl<const BUFSIZE: usize> MonoDelay<BUFSIZE> {
             rate: 1.0,
             time: 0.256,
             buffer: vec![0.0; sr as usize * 2 + 3],
+            max_time: 2.0,
             buffer_ptr: 1.0,
             max_buffer_ptr: (sr * 0.256) + 1.0, // 256 ms default time
             feedback: 0.5,
@@ -53,24 +61,127 @@ impl<const BUFSIZE: usize> MonoDelay<BUFSIZE> {
             rate_mod: None,
             time_mod: None,
             fb_mod: None,
+            fb_buf: [0.5; BUFSIZE],
+            rate_buf: [1.0; BUFSIZE],
+            time_buf: [(sr * 0.256) + 1.0; BUFSIZE],
         }
     }
 
     pub fn new(sr: f32) -> Self {
+        MonoDelay::with_max_time(sr, 2.0)
+    }
+
+    /// a delay that can be set to at most `max_time` seconds
+    pub fn with_max_time(sr: f32, max_time: f32) -> Self {
+        let time = max_time.min(0.256);
         MonoDelay {
             rate: 1.0,
-            time: 0.256,
-            buffer: vec![0.0; sr as usize * 2 + 3],
+            time,
+            buffer: vec![0.0; (sr * max_time) as usize + 3],
+            max_time,
             buffer_ptr: 1.0,
-            max_buffer_ptr: (sr * 0.256) + 1.0, // 256 ms default time
+            max_buffer_ptr: (sr * time) + 1.0, // 256 ms default time
             feedback: 0.5,
             dampening_filter: Box::new(Lpf18::new(3000.0, 0.4, 0.3, 44100.0)),
             samplerate: sr,
             rate_mod: None,
             time_mod: None,
             fb_mod: None,
+            fb_buf: [0.5; BUFSIZE],
+            rate_buf: [1.0; BUFSIZE],
+            time_buf: [(sr * time) + 1.0; BUFSIZE],
+        }
+    }
+
+    /// clear the delay line
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.dampening_filter.reset();
+    }
+
+    /// Fill the per-sample parameter values, starting at sample `from`.
+    /// Modulators are only processed if a start sample is given, as
+    /// they should only advance once per block.
+    fn update_parameter_buffers(
+        &mut self,
+        from: usize,
+        modulator_start: Option<usize>,
+        in_buffers: &[SampleBuffer],
+    ) {
+        match (self.fb_mod.as_mut(), modulator_start) {
+            (Some(m), Some(start)) => self.fb_buf = m.process(self.feedback, start, in_buffers),
+            (Some(_), None) => {} // keep modulated values until the next block
+            (None, _) => self.fb_buf[from..].fill(self.feedback),
+        }
+
+        match (self.rate_mod.as_mut(), modulator_start) {
+            (Some(m), Some(start)) => self.rate_buf = m.process(self.rate, start, in_buffers),
+            (Some(_), None) => {}
+            (None, _) => self.rate_buf[from..].fill(self.rate),
+        }
+
+        match (self.time_mod.as_mut(), modulator_start) {
+            (Some(m), Some(start)) => {
+                self.time_buf = m
+                    .process(self.time, start, in_buffers)
+                    .map(|x| (self.samplerate * x.min(self.max_time)) + 1.0)
+            }
+            (Some(_), None) => {}
+            (None, _) => self.time_buf[from..].fill(self.max_buffer_ptr),
+        }
+    }
+
+    fn process_samples(
+        &mut self,
+        block: &[f32; BUFSIZE],
+        out_buf: &mut [f32; BUFSIZE],
+        start_sample: usize,
+        end_sample: usize,
+    ) {
+        for i in start_sample..end_sample {
+            // get sample:
+            let idx = self.buffer_ptr.floor();
+            let frac = self.buffer_ptr - idx;
+            let idx_u = idx as usize;
+
+            // 4-point, 3rd-order Hermite
+            let buf_out = interpolate(
+                frac,
+                self.buffer[idx_u - 1],
+                self.buffer[idx_u],
+                self.buffer[idx_u + 1],
+                self.buffer[idx_u + 2],
+                1.0,
+            );
+
+            self.buffer[idx_u] =
+                (self.dampening_filter.maybe_process_sample(buf_out) * self.fb_buf[i]) + block[i];
+
+            out_buf[i] = self.buffer[idx_u];
+
+            // increment delay idx
+            self.buffer_ptr += self.rate_buf[i];
+            if self.buffer_ptr >= self.time_buf[i] {
+                self.buffer_ptr = 1.0 + (self.buffer_ptr - self.time_buf[i]);
+            }
         }
     }
+
+    /// Process only the samples from `start_sample` (inclusive) to `end_sample` (exclusive),
+    /// so parameters can be changed at precise positions inside the block.
+    /// The segments of a block need to be processed in order, starting at zero.
+    pub fn process_segment(
+        &mut self,
+        block: &[f32; BUFSIZE],
+        out_buf: &mut [f32; BUFSIZE],
+        start_sample: usize,
+        end_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) {
+        let modulator_start = if start_sample == 0 { Some(0) } else { None };
+        self.update_parameter_buffers(start_sample, modulator_start, in_buffers);
+        self.process_samples(block, out_buf, start_sample, end_sample);
+    }
 }
 
 impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MonoDelay<BUFSIZE> {
@@ -79,28 +190,26 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MonoDelay<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
-            SynthParameterLabel::DelayDampeningFrequency => {
-                self.dampening_filter.set_modulator(
-                    SynthParameterLabel::LowpassCutoffFrequency,
-                    init,
-                    modulator,
-                );
-            }
+            SynthParameterLabel::DelayDampeningFrequency => self.dampening_filter.set_modulator(
+                SynthParameterLabel::LowpassCutoffFrequency,
+                init,
+                modulator,
+            ),
             SynthParameterLabel::DelayFeedback => {
                 self.feedback = init;
-                self.fb_mod = Some(modulator);
+                Ok(self.fb_mod.replace(modulator))
             }
             SynthParameterLabel::DelayRate => {
                 self.rate = init;
-                self.rate_mod = Some(modulator);
+                Ok(self.rate_mod.replace(modulator))
             }
             SynthParameterLabel::DelayTime => {
                 self.time = init;
-                self.time_mod = Some(modulator);
+                Ok(self.time_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
     // some parameter limits might be nice ...
@@ -113,7 +222,7 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MonoDelay<BUFSIZE> {
                 SynthParameterLabel::DelayFeedback => self.feedback = *val,
                 SynthParameterLabel::DelayRate => self.rate = *val,
                 SynthParameterLabel::DelayTime => {
-                    self.time = *val;
+                    self.time = val.min(self.max_time);
                     self.max_buffer_ptr = self.samplerate * self.time + 1.0;
                 }
                 _ => (),
@@ -135,83 +244,8 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MonoDelay<BUFSIZE> {
     ) -> [f32; BUFSIZE] {
         let mut out_buf: [f32; BUFSIZE] = [0.0; BUFSIZE];
 
-        if self.fb_mod.is_some() || self.rate_mod.is_some() || self.time_mod.is_some() {
-            let fb_buf = if let Some(m) = self.fb_mod.as_mut() {
-                m.process(self.feedback, start_sample, in_buffers)
-            } else {
-                [self.feedback; BUFSIZE]
-            };
-
-            let rate_buf = if let Some(m) = self.rate_mod.as_mut() {
-                m.process(self.rate, start_sample, in_buffers)
-            } else {
-                [self.rate; BUFSIZE]
-            };
-
-            let time_buf = if let Some(m) = self.time_mod.as_mut() {
-                m.process(self.time, start_sample, in_buffers)
-                    .map(|x| (self.samplerate * x) + 1.0)
-            } else {
-                [self.samplerate * self.time; BUFSIZE]
-            };
-
-            for i in 0..BUFSIZE {
-                // get sample:
-                let idx = self.buffer_ptr.floor();
-                let frac = self.buffer_ptr - idx;
-                let idx_u = idx as usize;
-
-                // 4-point, 3rd-order Hermite
-                let buf_out = interpolate(
-                    frac,
-                    self.buffer[idx_u - 1],
-                    self.buffer[idx_u],
-                    self.buffer[idx_u + 1],
-                    self.buffer[idx_u + 2],
-                    1.0,
-                );
-
-                self.buffer[idx_u] =
-                    (self.dampening_filter.maybe_process_sample(buf_out) * fb_buf[i]) + block[i];
-
-                out_buf[i] = self.buffer[idx_u];
-
-                // increment delay idx
-                self.buffer_ptr += rate_buf[i];
-                if self.buffer_ptr >= time_buf[i] {
-                    self.buffer_ptr = 1.0 + (self.buffer_ptr - time_buf[i]);
-                }
-            }
-        } else {
-            for i in 0..BUFSIZE {
-                // get sample:
-                let idx = self.buffer_ptr.floor();
-                let frac = self.buffer_ptr - idx;
-                let idx_u = idx as usize;
-
-                // 4-point, 3rd-order Hermite
-                let buf_out = interpolate(
-                    frac,
-                    self.buffer[idx_u - 1],
-                    self.buffer[idx_u],
-                    self.buffer[idx_u + 1],
-                    self.buffer[idx_u + 2],
-                    1.0,
-                );
-
-                self.buffer[idx_u] = (self.dampening_filter.maybe_process_sample(buf_out)
-                    * self.feedback)
-                    + block[i];
-
-                out_buf[i] = self.buffer[idx_u];
-
-                // increment delay idx
-                self.buffer_ptr += self.rate;
-                if self.buffer_ptr >= self.max_buffer_ptr {
-                    self.buffer_ptr = 1.0 + (self.buffer_ptr - self.max_buffer_ptr);
-                }
-            }
-        }
+        self.update_parameter_buffers(0, Some(start_sample), in_buffers);
+        self.process_samples(&block, &mut out_buf, 0, BUFSIZE);
 
         out_buf
     }
@@ -223,10 +257,15 @@ pub struct MultichannelDelay<const BUFSIZE: usize, const NCHAN: usize> {
 
 impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelDelay<BUFSIZE, NCHAN> {
     pub fn new(sr: f32) -> Self {
+        MultichannelDelay::with_max_time(sr, 2.0)
+    }
+
+    /// a delay that can be set to at most `max_time` seconds
+    pub fn with_max_time(sr: f32, max_time: f32) -> Self {
         let mut delays = Vec::new();
 
         for _ in 0..NCHAN {
-            delays.push(MonoDelay::<BUFSIZE>::new(sr));
+            delays.push(MonoDelay::<BUFSIZE>::with_max_time(sr, max_time));
         }
 
         MultichannelDelay { delays }
@@ -238,28 +277,50 @@ impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelDelay<BUFSIZE, NCHAN>
         }
     }
 
+    /// the value is handed to `leftover` once it's set, as well as
+    /// the modulators that were replaced or not taken
     pub fn set_param_or_modulator(
         &mut self,
         par: SynthParameterLabel,
         val_or_mod: ValueOrModulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                leftover(ValueOrModulator::Val(val));
+            }
+            ValueOrModulator::Mod(init, modulator) => {
+                self.set_modulator(par, init, modulator, copies, leftover)
+            }
         }
     }
 
+    /// Each channel needs its own modulator, all but the first one take
+    /// theirs from `copies` (that is, `NCHAN - 1` copies are needed),
+    /// so nothing needs to be cloned in the audio thread.
     pub fn set_modulator(
         &mut self,
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        for c in 0..NCHAN {
-            // i might want to think of a better method because
-            // this clone will be called in the audio thread, even though
-            // this won't be super common, but still ...
-            self.delays[c].set_modulator(par, init, modulator.clone());
+        let (first, rest) = self.delays.split_first_mut().unwrap();
+        pass_leftover(first.set_modulator(par, init, modulator), leftover);
+        for delay in rest.iter_mut() {
+            if let Some(copy) = copies.pop() {
+                pass_leftover(delay.set_modulator(par, init, copy), leftover);
+            }
+        }
+    }
+
+    /// clear the delay lines, so the delay tail stops immediately
+    pub fn clear(&mut self) {
+        for delay in self.delays.iter_mut() {
+            delay.clear();
         }
     }
 
@@ -276,4 +337,65 @@ impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelDelay<BUFSIZE, NCHAN>
 
         out_buf
     }
+
+    /// Process a part of the block, see [`MonoDelay::process_segment`].
+    pub fn process_segment(
+        &mut self,
+        block: &[[f32; BUFSIZE]; NCHAN],
+        out_buf: &mut [[f32; BUFSIZE]; NCHAN],
+        start_sample: usize,
+        end_sample: usize,
+        sample_buffers: &[SampleBuffer],
+    ) {
+        for c in 0..NCHAN {
+            self.delays[c].process_segment(
+                &block[c],
+                &mut out_buf[c],
+                start_sample,
+                end_sample,
+                sample_buffers,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_process_segments() {
+        let mut delay_block = MultichannelDelay::<128, 2>::new(44100.0);
+        let mut delay_segments = MultichannelDelay::<128, 2>::new(44100.0);
+
+        delay_block.set_parameter(
+            SynthParameterLabel::DelayTime,
+            &SynthParameterValue::ScalarF32(0.001),
+        );
+        delay_segments.set_parameter(
+            SynthParameterLabel::DelayTime,
+            &SynthParameterValue::ScalarF32(0.001),
+        );
+
+        let mut block = [[0.0; 128]; 2];
+        block[0][0] = 1.0;
+        block[1][10] = 1.0;
+
+        for _ in 0..4 {
+            let out_block = delay_block.process(block, &[]);
+
+            // splitting the block shouldn't change anything ...
+            let mut out_segments = [[0.0; 128]; 2];
+            delay_segments.process_segment(&block, &mut out_segments, 0, 33, &[]);
+            delay_segments.process_segment(&block, &mut out_segments, 33, 100, &[]);
+            delay_segments.process_segment(&block, &mut out_segments, 100, 128, &[]);
+
+            for c in 0..2 {
+                for s in 0..128 {
+                    assert_eq!(out_block[c][s], out_segments[c][s]);
+                }
+            }
+            block = [[0.0; 128]; 2];
+        }
+    }
 }
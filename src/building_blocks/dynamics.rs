@@ -0,0 +1,372 @@
+use crate::building_blocks::{SynthParameterLabel, SynthParameterValue};
+
+use std::collections::VecDeque;
+
+fn db_to_lin(db: f32) -> f32 {
+    f32::powf(10.0, db / 20.0)
+}
+
+fn lin_to_db(lin: f32) -> f32 {
+    20.0 * lin.max(0.000001).log10()
+}
+
+fn time_to_coef(time: f32, samplerate: f32) -> f32 {
+    if time > 0.0 {
+        (-1.0 / (time * samplerate)).exp()
+    } else {
+        0.0
+    }
+}
+
+/**
+ * A simple feed-forward compressor with hard knee.
+ *
+ * The gain reduction is linked, that is, it's calculated from
+ * the loudest channel and applied to all channels alike, so the
+ * image doesn't shift.
+ */
+pub struct MultichannelCompressor<const BUFSIZE: usize, const NCHAN: usize> {
+    active: bool,
+    threshold: f32, // dB
+    ratio: f32,
+    attack: f32,  // seconds
+    release: f32, // seconds
+    makeup: f32,  // linear
+    attack_coef: f32,
+    release_coef: f32,
+    envelope: f32,       // current gain reduction in dB
+    gain_reduction: f32, // max gain reduction of the last block in dB
+    samplerate: f32,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelCompressor<BUFSIZE, NCHAN> {
+    pub fn new(samplerate: f32) -> Self {
+        MultichannelCompressor {
+            active: false,
+            threshold: -12.0,
+            ratio: 4.0,
+            attack: 0.01,
+            release: 0.1,
+            makeup: 1.0,
+            attack_coef: time_to_coef(0.01, samplerate),
+            release_coef: time_to_coef(0.1, samplerate),
+            envelope: 0.0,
+            gain_reduction: 0.0,
+            samplerate,
+        }
+    }
+
+    pub fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::CompressorActive => self.active = *val > 0.0,
+                SynthParameterLabel::CompressorThreshold => self.threshold = *val,
+                SynthParameterLabel::CompressorRatio => self.ratio = val.max(1.0),
+                SynthParameterLabel::CompressorAttack => {
+                    self.attack = *val;
+                    self.attack_coef = time_to_coef(self.attack, self.samplerate);
+                }
+                SynthParameterLabel::CompressorRelease => {
+                    self.release = *val;
+                    self.release_coef = time_to_coef(self.release, self.samplerate);
+                }
+                SynthParameterLabel::CompressorMakeupGain => self.makeup = db_to_lin(*val),
+                _ => (),
+            };
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// max gain reduction of the last block, in dB
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction
+    }
+
+    pub fn process(&mut self, block: [[f32; BUFSIZE]; NCHAN]) -> [[f32; BUFSIZE]; NCHAN] {
+        self.gain_reduction = 0.0;
+
+        if !self.active {
+            return block;
+        }
+
+        let mut out_buf = block;
+        let slope = 1.0 - (1.0 / self.ratio);
+
+        for s in 0..BUFSIZE {
+            let mut peak: f32 = 0.0;
+            for c in 0..NCHAN {
+                peak = peak.max(block[c][s].abs());
+            }
+
+            let over = lin_to_db(peak) - self.threshold;
+            let target = if over > 0.0 { over * slope } else { 0.0 };
+
+            let coef = if target > self.envelope {
+                self.attack_coef
+            } else {
+                self.release_coef
+            };
+            self.envelope = target + (self.envelope - target) * coef;
+            self.gain_reduction = self.gain_reduction.max(self.envelope);
+
+            let gain = db_to_lin(-self.envelope) * self.makeup;
+            for c in 0..NCHAN {
+                out_buf[c][s] *= gain;
+            }
+        }
+
+        out_buf
+    }
+}
+
+/**
+ * A look-ahead brickwall limiter.
+ *
+ * The signal is delayed by the look-ahead time, so the gain can be
+ * ramped down before a peak arrives. The gain reduction is linked
+ * across all channels. The gain is held at the lowest gain required
+ * by the samples inside the look-ahead window, so it's only released
+ * once the last peak has passed. Whatever still exceeds the ceiling
+ * (only due to rounding errors) is clipped.
+ */
+pub struct MultichannelLimiter<const BUFSIZE: usize, const NCHAN: usize> {
+    active: bool,
+    ceiling: f32, // linear
+    release_coef: f32,
+    lookahead_buffers: Vec<Vec<f32>>, // allocated once, at creation
+    lookahead: usize,
+    buffer_idx: usize,
+    // sliding minimum of the required gain over the look-ahead window,
+    // (sample count, required gain), allocated once, at creation
+    required_window: VecDeque<(usize, f32)>,
+    sample_count: usize,
+    gain: f32,
+    slope: f32,          // current attack slope
+    gain_reduction: f32, // max gain reduction of the last block in dB
+    samplerate: f32,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelLimiter<BUFSIZE, NCHAN> {
+    pub fn new(samplerate: f32) -> Self {
+        let lookahead = ((samplerate * 0.0015) as usize).max(1); // 1.5ms
+        MultichannelLimiter {
+            active: false,
+            ceiling: db_to_lin(-0.3),
+            release_coef: time_to_coef(0.05, samplerate),
+            lookahead_buffers: vec![vec![0.0; lookahead]; NCHAN],
+            lookahead,
+            buffer_idx: 0,
+            required_window: VecDeque::with_capacity(lookahead + 1),
+            sample_count: 0,
+            gain: 1.0,
+            slope: 0.0,
+            gain_reduction: 0.0,
+            samplerate,
+        }
+    }
+
+    pub fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::LimiterActive => self.active = *val > 0.0,
+                SynthParameterLabel::LimiterCeiling => self.ceiling = db_to_lin(*val),
+                SynthParameterLabel::LimiterRelease => {
+                    self.release_coef = time_to_coef(*val, self.samplerate)
+                }
+                _ => (),
+            };
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// max gain reduction of the last block, in dB
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction
+    }
+
+    pub fn process(&mut self, block: [[f32; BUFSIZE]; NCHAN]) -> [[f32; BUFSIZE]; NCHAN] {
+        self.gain_reduction = 0.0;
+
+        if !self.active {
+            return block;
+        }
+
+        let mut out_buf = [[0.0; BUFSIZE]; NCHAN];
+
+        for s in 0..BUFSIZE {
+            let mut peak: f32 = 0.0;
+            for c in 0..NCHAN {
+                peak = peak.max(block[c][s].abs());
+            }
+
+            // the gain needed once this sample leaves the look-ahead buffer
+            let required = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+
+            // the window covers the sample leaving the look-ahead buffer
+            // now up to the one that just came in, the front is its minimum
+            while self
+                .required_window
+                .back()
+                .is_some_and(|(_, r)| *r >= required)
+            {
+                self.required_window.pop_back();
+            }
+            while self
+                .required_window
+                .front()
+                .is_some_and(|(n, _)| self.sample_count.wrapping_sub(*n) > self.lookahead)
+            {
+                self.required_window.pop_front();
+            }
+            self.required_window
+                .push_back((self.sample_count, required));
+            self.sample_count = self.sample_count.wrapping_add(1);
+            let window_min = self.required_window.front().unwrap().1;
+
+            if required < self.gain + self.slope * self.lookahead as f32 {
+                // ramp down fast enough to reach the required gain in time
+                self.slope = (required - self.gain) / self.lookahead as f32;
+            }
+
+            if self.slope < 0.0 {
+                // no need to go below what the window requires
+                self.gain += self.slope;
+                if self.gain <= window_min {
+                    self.gain = window_min;
+                    self.slope = 0.0;
+                }
+            } else {
+                // release, as far as the window allows
+                self.gain = (1.0 + (self.gain - 1.0) * self.release_coef).min(window_min);
+            }
+
+            self.gain_reduction = self.gain_reduction.max(-lin_to_db(self.gain));
+
+            for c in 0..NCHAN {
+                let delayed = self.lookahead_buffers[c][self.buffer_idx];
+                self.lookahead_buffers[c][self.buffer_idx] = block[c][s];
+                out_buf[c][s] = (delayed * self.gain).clamp(-self.ceiling, self.ceiling);
+            }
+
+            self.buffer_idx += 1;
+            if self.buffer_idx >= self.lookahead {
+                self.buffer_idx = 0;
+            }
+        }
+
+        out_buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_ceiling() {
+        let mut limiter = MultichannelLimiter::<128, 2>::new(44100.0);
+        limiter.set_parameter(
+            SynthParameterLabel::LimiterActive,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        limiter.set_parameter(
+            SynthParameterLabel::LimiterCeiling,
+            &SynthParameterValue::ScalarF32(-6.0),
+        );
+
+        let ceiling = db_to_lin(-6.0);
+        let mut reduced = false;
+
+        for b in 0..10 {
+            let mut block = [[0.0; 128]; 2];
+            for s in 0..128 {
+                let t = (b * 128 + s) as f32 / 44100.0;
+                block[0][s] = (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 2.0;
+                block[1][s] = (2.0 * std::f32::consts::PI * 220.0 * t).sin() * 0.1;
+            }
+
+            let out = limiter.process(block);
+            for c in 0..2 {
+                for s in 0..128 {
+                    assert!(out[c][s].abs() <= ceiling);
+                }
+            }
+            reduced |= limiter.gain_reduction() > 6.0;
+        }
+        assert!(reduced);
+    }
+
+    #[test]
+    fn test_limiter_steady_signal_not_clipped() {
+        let mut limiter = MultichannelLimiter::<128, 1>::new(44100.0);
+        limiter.set_parameter(
+            SynthParameterLabel::LimiterActive,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        limiter.set_parameter(
+            SynthParameterLabel::LimiterCeiling,
+            &SynthParameterValue::ScalarF32(-6.0),
+        );
+
+        let ceiling = db_to_lin(-6.0);
+        let lookahead = limiter.lookahead;
+        let input =
+            |n: usize| (2.0 * std::f32::consts::PI * 440.0 * n as f32 / 44100.0).sin() * 2.0;
+
+        for b in 0..20 {
+            let mut block = [[0.0; 128]; 1];
+            for s in 0..128 {
+                block[0][s] = input(b * 128 + s);
+            }
+
+            let out = limiter.process(block);
+
+            // once settled, the gain is held, so the output is just the
+            // delayed input, scaled down, instead of a clipped version of it
+            if b >= 2 {
+                for s in 0..128 {
+                    let expected = input(b * 128 + s - lookahead) * ceiling / 2.0;
+                    assert!((out[0][s] - expected).abs() < 0.001);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compressor_gain_reduction() {
+        let mut comp = MultichannelCompressor::<128, 1>::new(44100.0);
+        comp.set_parameter(
+            SynthParameterLabel::CompressorActive,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        comp.set_parameter(
+            SynthParameterLabel::CompressorThreshold,
+            &SynthParameterValue::ScalarF32(-20.0),
+        );
+        comp.set_parameter(
+            SynthParameterLabel::CompressorRatio,
+            &SynthParameterValue::ScalarF32(4.0),
+        );
+        comp.set_parameter(
+            SynthParameterLabel::CompressorAttack,
+            &SynthParameterValue::ScalarF32(0.0),
+        );
+
+        // constant level of 0 dB, 20 dB over the threshold
+        let out = comp.process([[1.0; 128]; 1]);
+
+        // 20 dB over with ratio 4:1 leaves 5 dB, so 15 dB of reduction
+        assert!((comp.gain_reduction() - 15.0).abs() < 0.01);
+        assert!((lin_to_db(out[0][127]) + 15.0).abs() < 0.01);
+    }
+}
@@ -13,7 +13,10 @@ pub use crate::building_blocks::envelopes::multi_point_envelope::MultiPointEffec
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
-    use crate::building_blocks::{MonoEffect, SynthParameterLabel, SynthParameterValue};
+    use crate::building_blocks::{
+        EnvelopeSegmentInfo, EnvelopeSegmentType, MonoEffect, SynthParameterLabel,
+        SynthParameterValue, ValOp,
+    };
 
     /// test the general workings of the ASREnvelope
     #[test]
@@ -218,6 +221,184 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gated_multi_point_envelope() {
+        let test_block: [f32; 128] = [1.0; 128];
+
+        // 64 samples attack, 64 samples sustain, 64 samples release
+        let seg_time = 64.0 / 44100.0;
+        let mut env = MultiPointEffectEnvelope::<128>::new(
+            vec![
+                EnvelopeSegmentInfo {
+                    from: 0.0,
+                    to: 1.0,
+                    time: seg_time,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+                EnvelopeSegmentInfo {
+                    from: 1.0,
+                    to: 1.0,
+                    time: seg_time,
+                    segment_type: EnvelopeSegmentType::Constant,
+                },
+                EnvelopeSegmentInfo {
+                    from: 1.0,
+                    to: 0.0,
+                    time: seg_time,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+            ],
+            false,
+            44100.0,
+        );
+
+        env.set_parameter(
+            SynthParameterLabel::EnvelopeGate,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+
+        let out_1 = env.process_block(test_block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(out_1[0], 0.0, 0.00001);
+        assert_approx_eq::assert_approx_eq!(out_1[127], 1.0, 0.00001);
+
+        // way past the nominal sustain time, the envelope should still hold
+        for _ in 0..10 {
+            let out = env.process_block(test_block, 0, &Vec::new());
+            for s in out.iter() {
+                assert_approx_eq::assert_approx_eq!(*s, 1.0, 0.00001);
+            }
+            assert!(!env.is_finished());
+        }
+
+        env.release();
+
+        let out_2 = env.process_block(test_block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(out_2[0], 1.0, 0.00001);
+        assert!(out_2[32] < 1.0);
+        assert_approx_eq::assert_approx_eq!(out_2[127], 0.0, 0.00001);
+
+        env.process_block(test_block, 0, &Vec::new());
+        assert!(env.is_finished());
+    }
+
+    #[test]
+    fn test_multi_point_envelope_release_during_attack() {
+        let test_block: [f32; 128] = [1.0; 128];
+
+        // 256 samples attack, 64 samples sustain, 64 samples release
+        let mut env = MultiPointEffectEnvelope::<128>::new(
+            vec![
+                EnvelopeSegmentInfo {
+                    from: 0.0,
+                    to: 1.0,
+                    time: 256.0 / 44100.0,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+                EnvelopeSegmentInfo {
+                    from: 1.0,
+                    to: 1.0,
+                    time: 64.0 / 44100.0,
+                    segment_type: EnvelopeSegmentType::Constant,
+                },
+                EnvelopeSegmentInfo {
+                    from: 1.0,
+                    to: 0.0,
+                    time: 64.0 / 44100.0,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+            ],
+            false,
+            44100.0,
+        );
+
+        env.set_parameter(
+            SynthParameterLabel::EnvelopeGate,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+
+        let out_1 = env.process_block(test_block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(out_1[127], 0.5, 0.01);
+
+        env.release();
+
+        // no jump to the full level, the release starts where the attack was
+        let out_2 = env.process_block(test_block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(out_2[0], out_1[127], 0.01);
+        for pair in out_2.windows(2) {
+            assert!(pair[1] <= pair[0]);
+        }
+        assert_approx_eq::assert_approx_eq!(out_2[127], 0.0, 0.00001);
+        assert!(env.is_finished());
+    }
+
+    #[test]
+    fn test_multi_point_envelope_segment_types() {
+        let test_block: [f32; 128] = [1.0; 128];
+
+        let segments = vec![
+            EnvelopeSegmentInfo {
+                from: 0.0,
+                to: 1.0,
+                time: 0.01,
+                segment_type: EnvelopeSegmentType::Sin,
+            },
+            EnvelopeSegmentInfo {
+                from: 1.0,
+                to: 0.0,
+                time: 0.01,
+                segment_type: EnvelopeSegmentType::Cos,
+            },
+        ];
+
+        // setting the envelope later on should give the same result
+        let mut env_1 = MultiPointEffectEnvelope::<128>::new(segments.clone(), false, 44100.0);
+        let mut env_2 = MultiPointEffectEnvelope::<128>::empty(44100.0);
+        env_2.set_parameter(
+            SynthParameterLabel::Envelope,
+            &SynthParameterValue::MultiPointEnvelope(segments, false, ValOp::Replace),
+        );
+
+        for _ in 0..8 {
+            assert_eq!(
+                env_1.process_block(test_block, 0, &Vec::new()),
+                env_2.process_block(test_block, 0, &Vec::new())
+            );
+        }
+    }
+
+    #[test]
+    fn test_gated_linear_asr_envelope() {
+        let test_block: [f32; 128] = [1.0; 128];
+
+        // 64 samples attack, 64 samples sustain, 64 samples release
+        let seg_time = 64.0 / 44100.0;
+        let mut env = LinearASREnvelope::<128>::new(1.0, seg_time, seg_time, seg_time, 44100.0);
+
+        env.set_parameter(
+            SynthParameterLabel::EnvelopeGate,
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+
+        env.process_block(test_block, 0, &Vec::new());
+
+        // way past the nominal sustain time, the envelope should still hold
+        for _ in 0..10 {
+            let out = env.process_block(test_block, 0, &Vec::new());
+            for s in out.iter() {
+                assert_approx_eq::assert_approx_eq!(*s, 1.0, 0.00001);
+            }
+            assert!(!env.is_finished());
+        }
+
+        env.release();
+
+        let out = env.process_block(test_block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(out[0], 1.0, 0.00001);
+        assert!(out[32] < 1.0);
+        assert_approx_eq::assert_approx_eq!(out[127], 0.0, 0.00001);
+        assert!(env.is_finished());
+    }
+
     /*
         #[test]
         fn test_multi_point_effect_env() {
@@ -1,5 +1,6 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue, SynthState,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    SynthState,
 };
 
 /// Exponential/Linear Percussion Envelope (currently with fixed curve value)
@@ -60,7 +61,14 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for ExpPercEnvelope<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
         let mut update_internals = false;
@@ -1,8 +1,11 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue, SynthState,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    SynthState,
 };
 
-/// simple linear attack-sustain-release envelope
+/// Simple linear attack-sustain-release envelope. If gated
+/// (see `SynthParameterLabel::EnvelopeGate`), the sustain level is held
+/// until the envelope is released.
 #[derive(Clone, Copy)]
 pub struct LinearASREnvelope<const BUFSIZE: usize> {
     samplerate: f32,
@@ -17,6 +20,8 @@ pub struct LinearASREnvelope<const BUFSIZE: usize> {
     max_lvl: f32,
     atk_lvl_increment: f32,
     rel_lvl_decrement: f32,
+    gated: bool,
+    released: bool,
     state: SynthState,
 }
 
@@ -39,9 +44,29 @@ impl<const BUFSIZE: usize> LinearASREnvelope<BUFSIZE> {
             max_lvl: lvl,
             atk_lvl_increment: lvl / atk_samples,
             rel_lvl_decrement: lvl / (rel_samples - sus_samples),
+            gated: false,
+            released: false,
             state: SynthState::Fresh,
         }
     }
+
+    /// Release a gated envelope, that is, start the release phase
+    /// from the current level. Non-gated envelopes ignore the release.
+    pub fn release(&mut self) {
+        if !self.gated || self.released {
+            return;
+        }
+        self.released = true;
+        if self.sample_count < self.sus_samples {
+            self.sample_count = self.sus_samples;
+            let rel_samples = (self.rel_samples - self.sus_samples) as f32;
+            self.rel_lvl_decrement = if rel_samples > 0.0 {
+                self.lvl / rel_samples
+            } else {
+                0.0
+            };
+        }
+    }
 }
 
 impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for LinearASREnvelope<BUFSIZE> {
@@ -53,7 +78,14 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for LinearASREnvelope<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
         let mut update_internals = false;
@@ -82,6 +114,7 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for LinearASREnvelope<BUFSIZE> {
                         self.samplerate = *val;
                         update_internals = true;
                     }
+                    SynthParameterLabel::EnvelopeGate => self.gated = *val > 0.0,
 
                     _ => (),
                 }
@@ -139,6 +172,12 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for LinearASREnvelope<BUFSIZE> {
         for i in start_sample..BUFSIZE {
             out[i] = block[i] * self.lvl;
 
+            // hold the sustain level until released
+            if self.gated && !self.released && self.sample_count + 1 >= self.sus_samples {
+                self.lvl = self.max_lvl;
+                continue;
+            }
+
             self.sample_count += 1;
             if self.sample_count < self.atk_samples {
                 self.lvl += self.atk_lvl_increment;
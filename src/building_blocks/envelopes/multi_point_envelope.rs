@@ -1,6 +1,6 @@
 use crate::building_blocks::{
-    envelopes::source_env::MultiPointEnvelope, EnvelopeSegmentInfo, Modulator, MonoEffect,
-    MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    envelopes::source_env::MultiPointEnvelope, EnvelopeSegmentInfo, Modulator, ModulatorResult,
+    MonoEffect, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /// more complex, configurable envelope
@@ -20,6 +20,11 @@ impl<const BUFSIZE: usize> MultiPointEffectEnvelope<BUFSIZE> {
             inner_env: MultiPointEnvelope::empty(samplerate),
         }
     }
+
+    /// release a gated envelope (note-off)
+    pub fn release(&mut self) {
+        self.inner_env.release();
+    }
 }
 
 impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MultiPointEffectEnvelope<BUFSIZE> {
@@ -36,8 +41,8 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for MultiPointEffectEnvelope<BUFS
         label: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        self.inner_env.set_modulator(label, init, modulator);
+    ) -> ModulatorResult<BUFSIZE> {
+        self.inner_env.set_modulator(label, init, modulator)
     }
 
     /// multi-point envelopes can only be set as a whole,
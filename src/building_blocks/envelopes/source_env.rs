@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    EnvelopeSegmentInfo, EnvelopeSegmentType, Modulator, MonoSource, SampleBuffer,
+    EnvelopeSegmentInfo, EnvelopeSegmentType, Modulator, ModulatorResult, MonoSource, SampleBuffer,
     SynthParameterLabel, SynthParameterValue, SynthState,
 };
 
@@ -47,7 +47,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for LinearRamp<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -120,7 +127,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for LogRamp<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -194,7 +208,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for ExpRamp<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -266,7 +287,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for SineRamp<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -338,7 +366,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for CosRamp<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -395,7 +430,14 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for ConstantMod<BUFSIZE> {
         matches!(self.state, SynthState::Finished)
     }
 
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
 
@@ -415,47 +457,104 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for ConstantMod<BUFSIZE> {
     }
 }
 
+/// the number of segments a multi-point envelope has room for
+/// without allocating, so envelopes can be set on running synths
+const RESERVED_ENVELOPE_SEGMENTS: usize = 8;
+
+/// the segments are stored inline rather than boxed,
+/// so they can be replaced without allocating
+#[derive(Clone, Copy)]
+enum EnvelopeSegment<const BUFSIZE: usize> {
+    Lin(LinearRamp<BUFSIZE>),
+    Log(LogRamp<BUFSIZE>),
+    Exp(ExpRamp<BUFSIZE>),
+    Sin(SineRamp<BUFSIZE>),
+    Cos(CosRamp<BUFSIZE>),
+    Constant(ConstantMod<BUFSIZE>),
+}
+
+impl<const BUFSIZE: usize> EnvelopeSegment<BUFSIZE> {
+    fn new(info: &EnvelopeSegmentInfo, samplerate: f32) -> Self {
+        match info.segment_type {
+            EnvelopeSegmentType::Lin => {
+                EnvelopeSegment::Lin(LinearRamp::new(info.from, info.to, info.time, samplerate))
+            }
+            EnvelopeSegmentType::Log => {
+                EnvelopeSegment::Log(LogRamp::new(info.from, info.to, info.time, samplerate))
+            }
+            EnvelopeSegmentType::Exp => {
+                EnvelopeSegment::Exp(ExpRamp::new(info.from, info.to, info.time, samplerate))
+            }
+            EnvelopeSegmentType::Sin => {
+                EnvelopeSegment::Sin(SineRamp::new(info.from, info.to, info.time, samplerate))
+            }
+            EnvelopeSegmentType::Cos => {
+                EnvelopeSegment::Cos(CosRamp::new(info.from, info.to, info.time, samplerate))
+            }
+            EnvelopeSegmentType::Constant => {
+                EnvelopeSegment::Constant(ConstantMod::new(info.time, info.to, samplerate))
+            }
+        }
+    }
+
+    fn source(&mut self) -> &mut dyn MonoSource<BUFSIZE> {
+        match self {
+            EnvelopeSegment::Lin(s) => s,
+            EnvelopeSegment::Log(s) => s,
+            EnvelopeSegment::Exp(s) => s,
+            EnvelopeSegment::Sin(s) => s,
+            EnvelopeSegment::Cos(s) => s,
+            EnvelopeSegment::Constant(s) => s,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.source().reset();
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            EnvelopeSegment::Lin(s) => s.is_finished(),
+            EnvelopeSegment::Log(s) => s.is_finished(),
+            EnvelopeSegment::Exp(s) => s.is_finished(),
+            EnvelopeSegment::Sin(s) => s.is_finished(),
+            EnvelopeSegment::Cos(s) => s.is_finished(),
+            EnvelopeSegment::Constant(s) => s.is_finished(),
+        }
+    }
+
+    fn get_next_block(&mut self, start_sample: usize, bufs: &[SampleBuffer]) -> [f32; BUFSIZE] {
+        self.source().get_next_block(start_sample, bufs)
+    }
+}
+
 /**
  * Multi-Point Modulator Envelope
  */
 #[derive(Clone)]
 pub struct MultiPointEnvelope<const BUFSIZE: usize> {
-    segments: Vec<Box<dyn MonoSource<BUFSIZE> + Sync + Send>>,
+    segments: Vec<EnvelopeSegment<BUFSIZE>>,
     segment_samples: Vec<usize>,
     segment_idx: usize,
     sample_count: usize, // re-set on every segment switch
     loop_env: bool,
+    gated: bool,    // if gated, hold the sustain segment until released
+    released: bool, // only relevant for gated envelopes
+    level: f32,     // the last value, so the release can start from there
+    release_segment: Option<EnvelopeSegmentInfo>, // as defined, before it's been released
     state: SynthState,
     samplerate: f32,
 }
 
 impl<const BUFSIZE: usize> MultiPointEnvelope<BUFSIZE> {
     pub fn new(segment_infos: Vec<EnvelopeSegmentInfo>, loop_env: bool, samplerate: f32) -> Self {
-        let mut segments: Vec<Box<dyn MonoSource<BUFSIZE> + Sync + Send>> = Vec::new();
-        let mut segment_samples = Vec::new();
+        let capacity = segment_infos.len().max(RESERVED_ENVELOPE_SEGMENTS);
+        let mut segments = Vec::with_capacity(capacity);
+        let mut segment_samples = Vec::with_capacity(capacity);
 
         for info in segment_infos.iter() {
             segment_samples.push((info.time * samplerate).round() as usize);
-            segments.push(match info.segment_type {
-                EnvelopeSegmentType::Lin => {
-                    Box::new(LinearRamp::new(info.from, info.to, info.time, samplerate))
-                }
-                EnvelopeSegmentType::Log => {
-                    Box::new(LogRamp::new(info.from, info.to, info.time, samplerate))
-                }
-                EnvelopeSegmentType::Exp => {
-                    Box::new(ExpRamp::new(info.from, info.to, info.time, samplerate))
-                }
-                EnvelopeSegmentType::Sin => {
-                    Box::new(ExpRamp::new(info.from, info.to, info.time, samplerate))
-                }
-                EnvelopeSegmentType::Cos => {
-                    Box::new(ExpRamp::new(info.from, info.to, info.time, samplerate))
-                }
-                EnvelopeSegmentType::Constant => {
-                    Box::new(ConstantMod::new(info.time, info.to, samplerate))
-                }
-            });
+            segments.push(EnvelopeSegment::new(info, samplerate));
         }
 
         MultiPointEnvelope {
@@ -464,6 +563,10 @@ impl<const BUFSIZE: usize> MultiPointEnvelope<BUFSIZE> {
             segment_idx: 0,
             sample_count: 0,
             loop_env,
+            gated: false,
+            released: false,
+            level: segment_infos.first().map_or(0.0, |info| info.from),
+            release_segment: segment_infos.last().copied(),
             state: SynthState::Fresh,
             samplerate,
         }
@@ -471,86 +574,54 @@ impl<const BUFSIZE: usize> MultiPointEnvelope<BUFSIZE> {
 
     pub fn empty(samplerate: f32) -> Self {
         MultiPointEnvelope {
-            segments: Vec::new(),
-            segment_samples: Vec::new(),
+            segments: Vec::with_capacity(RESERVED_ENVELOPE_SEGMENTS),
+            segment_samples: Vec::with_capacity(RESERVED_ENVELOPE_SEGMENTS),
             segment_idx: 0,
             sample_count: 0,
             loop_env: false,
+            gated: false,
+            released: false,
+            level: 0.0,
+            release_segment: None,
             state: SynthState::Fresh,
             samplerate,
         }
     }
-}
-
-impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MultiPointEnvelope<BUFSIZE> {
-    fn reset(&mut self) {
-        self.sample_count = 0;
-        self.segment_idx = 0;
-        for s in self.segments.iter_mut() {
-            s.reset();
-        }
-    }
 
-    fn finish(&mut self) {
-        self.state = SynthState::Finished;
+    /// The second-to-last segment is the sustain segment,
+    /// so a gated envelope needs at least two segments.
+    fn holding(&self) -> bool {
+        self.gated
+            && !self.released
+            && self.segments.len() > 1
+            && self.segment_idx == self.segments.len() - 2
     }
 
-    fn is_finished(&self) -> bool {
-        if let Some(last) = self.segments.last() {
-            // check if last element has finished or whether this is a looping envelope
-            !self.loop_env && last.is_finished()
-        } else {
-            true // an empty envelope doesn't do anything and is always finished
+    /// Release a gated envelope, that is, move on to the
+    /// last (release) segment. If the envelope hasn't reached
+    /// the sustain segment yet, it jumps directly to the release segment.
+    /// Either way, the release starts from the current level.
+    /// Non-gated envelopes ignore the release.
+    pub fn release(&mut self) {
+        if !self.gated || self.released || self.segments.len() < 2 {
+            return;
         }
-    }
-
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
-
-    fn set_parameter(&mut self, par: SynthParameterLabel, val: &SynthParameterValue) {
-        // TODO: recalc envelope segments from attack, decay, sustain, release etc ...
-        if let SynthParameterLabel::Envelope = par {
-            if let SynthParameterValue::MultiPointEnvelope(segment_infos, loop_env, _) = val {
-                let mut segments: Vec<Box<dyn MonoSource<BUFSIZE> + Sync + Send>> = Vec::new();
-                let mut segment_samples = Vec::new();
-
-                for info in segment_infos.iter() {
-                    segment_samples.push((info.time * self.samplerate).round() as usize);
-                    segments.push(match info.segment_type {
-                        EnvelopeSegmentType::Lin => Box::new(LinearRamp::new(
-                            info.from,
-                            info.to,
-                            info.time,
-                            self.samplerate,
-                        )),
-                        EnvelopeSegmentType::Log => {
-                            Box::new(LogRamp::new(info.from, info.to, info.time, self.samplerate))
-                        }
-                        EnvelopeSegmentType::Exp => {
-                            Box::new(ExpRamp::new(info.from, info.to, info.time, self.samplerate))
-                        }
-                        EnvelopeSegmentType::Sin => Box::new(SineRamp::new(
-                            info.from,
-                            info.to,
-                            info.time,
-                            self.samplerate,
-                        )),
-                        EnvelopeSegmentType::Cos => {
-                            Box::new(CosRamp::new(info.from, info.to, info.time, self.samplerate))
-                        }
-                        EnvelopeSegmentType::Constant => {
-                            Box::new(ConstantMod::new(info.time, info.to, self.samplerate))
-                        }
-                    });
-                }
-
-                self.segments = segments;
-                self.segment_samples = segment_samples;
-                self.loop_env = *loop_env;
+        self.released = true;
+        let release_idx = self.segments.len() - 1;
+        if self.segment_idx < release_idx {
+            if let Some(info) = self.release_segment {
+                let from_here = EnvelopeSegmentInfo {
+                    from: self.level,
+                    ..info
+                };
+                self.segments[release_idx] = EnvelopeSegment::new(&from_here, self.samplerate);
             }
+            self.segment_idx = release_idx;
+            self.sample_count = 0;
         }
     }
 
-    fn get_next_block(&mut self, start_sample: usize, bufs: &[SampleBuffer]) -> [f32; BUFSIZE] {
+    fn next_block(&mut self, start_sample: usize, bufs: &[SampleBuffer]) -> [f32; BUFSIZE] {
         // this should also avoid problems with "empty" multi-point envelopes ...
         if self.segment_idx >= self.segments.len() {
             if let Some(last_seg) = self.segments.last_mut() {
@@ -561,6 +632,11 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MultiPointEnvelope<BUFSIZE> {
             }
         }
 
+        // gated envelopes stay in the sustain segment until released ...
+        if self.holding() {
+            return self.segments[self.segment_idx].get_next_block(start_sample, bufs);
+        }
+
         // first, let's see how many samples we have to fill
         let block_samples_to_fill_total = BUFSIZE - start_sample;
 
@@ -586,13 +662,22 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MultiPointEnvelope<BUFSIZE> {
             // we need some handling in case multiple segments fall into one block,
             // so we count down on the samples that are left to fill ...
             while block_samples_to_fill_rest > 0 {
+                let holding = self.holding();
                 // if there is a next segment ...
                 if let Some(current_segment) = self.segments.get_mut(self.segment_idx) {
+                    let out_current = current_segment.get_next_block(start_index, bufs);
+
+                    // reached the sustain segment of a gated envelope,
+                    // so fill the rest of the block
+                    if holding {
+                        out[start_index..BUFSIZE]
+                            .copy_from_slice(&out_current[start_index..BUFSIZE]);
+                        break; // jump out
+                    }
+
                     let samples_left_in_segment =
                         self.segment_samples[self.segment_idx] - self.sample_count;
 
-                    let out_current = current_segment.get_next_block(start_index, bufs);
-
                     // again, more than we need ?
                     if samples_left_in_segment >= block_samples_to_fill_rest {
                         // copy samples
@@ -628,6 +713,77 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MultiPointEnvelope<BUFSIZE> {
         }
     }
 }
+
+impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MultiPointEnvelope<BUFSIZE> {
+    fn reset(&mut self) {
+        self.sample_count = 0;
+        self.segment_idx = 0;
+        for s in self.segments.iter_mut() {
+            s.reset();
+        }
+        // the release segment might have been moved to the level it was released at
+        if let (Some(info), Some(last)) = (self.release_segment, self.segments.last_mut()) {
+            *last = EnvelopeSegment::new(&info, self.samplerate);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.state = SynthState::Finished;
+    }
+
+    fn is_finished(&self) -> bool {
+        if let Some(last) = self.segments.last() {
+            // check if last element has finished or whether this is a looping envelope
+            !self.loop_env && last.is_finished()
+        } else {
+            true // an empty envelope doesn't do anything and is always finished
+        }
+    }
+
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
+
+    fn set_parameter(&mut self, par: SynthParameterLabel, val: &SynthParameterValue) {
+        if let SynthParameterLabel::EnvelopeGate = par {
+            if let SynthParameterValue::ScalarF32(g) = val {
+                self.gated = *g > 0.0;
+            }
+        }
+
+        // TODO: recalc envelope segments from attack, decay, sustain, release etc ...
+        if let SynthParameterLabel::Envelope = par {
+            if let SynthParameterValue::MultiPointEnvelope(segment_infos, loop_env, _) = val {
+                // re-use the segment storage, so no memory is allocated or freed
+                // as long as the new envelope doesn't have more segments than reserved
+                self.segments.clear();
+                self.segment_samples.clear();
+
+                for info in segment_infos.iter() {
+                    self.segment_samples
+                        .push((info.time * self.samplerate).round() as usize);
+                    self.segments
+                        .push(EnvelopeSegment::new(info, self.samplerate));
+                }
+                self.level = segment_infos.first().map_or(0.0, |info| info.from);
+                self.release_segment = segment_infos.last().copied();
+
+                self.loop_env = *loop_env;
+            }
+        }
+    }
+
+    fn get_next_block(&mut self, start_sample: usize, bufs: &[SampleBuffer]) -> [f32; BUFSIZE] {
+        let out = self.next_block(start_sample, bufs);
+        self.level = out[BUFSIZE - 1];
+        out
+    }
+}
 /*
 #[cfg(test)]
 mod tests {
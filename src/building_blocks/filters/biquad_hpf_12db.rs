@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 use crate::building_blocks::filters::sos::*;
@@ -57,30 +57,26 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for BiquadHpf12dB<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        let mut update = false;
-        match par {
+    ) -> ModulatorResult<BUFSIZE> {
+        let replaced = match par {
             SynthParameterLabel::HighpassCutoffFrequency => {
                 self.cutoff = init;
-                self.cutoff_mod = Some(modulator);
-                update = true;
+                self.cutoff_mod.replace(modulator)
             }
             SynthParameterLabel::HighpassQFactor => {
                 self.q = init;
-                self.q_mod = Some(modulator);
-                update = true;
+                self.q_mod.replace(modulator)
             }
-            _ => {}
-        }
-
-        if update {
-            BiquadHpf12dB::<BUFSIZE>::generate_coefs(
-                &mut self.coefs,
-                self.cutoff,
-                self.q,
-                self.samplerate,
-            );
-        }
+            _ => return Err(modulator),
+        };
+
+        BiquadHpf12dB::<BUFSIZE>::generate_coefs(
+            &mut self.coefs,
+            self.cutoff,
+            self.q,
+            self.samplerate,
+        );
+        Ok(replaced)
     }
     // some parameter limits might be nice ...
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
@@ -156,4 +152,8 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for BiquadHpf12dB<BUFSIZE> {
     fn maybe_process_sample(&mut self, sample: f32) -> f32 {
         process_sos_sample(&self.coefs, &mut self.delay, sample)
     }
+
+    fn reset(&mut self) {
+        self.delay = SOSDelay::default();
+    }
 }
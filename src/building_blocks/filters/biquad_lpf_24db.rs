@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 use crate::building_blocks::filters::sos::*;
@@ -48,29 +48,25 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for BiquadLpf24dB<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        let mut update = false;
-        match par {
+    ) -> ModulatorResult<BUFSIZE> {
+        let replaced = match par {
             SynthParameterLabel::LowpassCutoffFrequency => {
                 self.cutoff = init;
-                self.cutoff_mod = Some(modulator);
-                update = true;
+                self.cutoff_mod.replace(modulator)
             }
             SynthParameterLabel::LowpassQFactor => {
                 self.q = init;
-                self.q_mod = Some(modulator);
-                update = true;
+                self.q_mod.replace(modulator)
             }
-            _ => {}
-        }
-        if update {
-            BiquadLpf12dB::<BUFSIZE>::generate_coefs(
-                &mut self.coefs,
-                self.cutoff,
-                self.q,
-                self.samplerate,
-            );
-        }
+            _ => return Err(modulator),
+        };
+        BiquadLpf12dB::<BUFSIZE>::generate_coefs(
+            &mut self.coefs,
+            self.cutoff,
+            self.q,
+            self.samplerate,
+        );
+        Ok(replaced)
     }
     // some parameter limits might be nice ...
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
@@ -157,4 +153,9 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for BiquadLpf24dB<BUFSIZE> {
             process_sos_sample(&self.coefs, &mut self.delay1, sample),
         )
     }
+
+    fn reset(&mut self) {
+        self.delay1 = SOSDelay::default();
+        self.delay2 = SOSDelay::default();
+    }
 }
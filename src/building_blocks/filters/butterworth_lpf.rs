@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 use crate::building_blocks::filters::sos::*;
@@ -239,16 +239,15 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for ButterworthLpf<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::LowpassCutoffFrequency => {
                 self.cutoff = init;
-                self.cutoff_mod = Some(modulator);
                 self.regenerate_coefs(self.cutoff);
+                Ok(self.cutoff_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
-        self.regenerate_coefs(self.cutoff);
     }
     // some parameter limits might be nice ...
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
@@ -312,4 +311,10 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for ButterworthLpf<BUFSIZE> {
         }
         s
     }
+
+    fn reset(&mut self) {
+        for delay in self.delays.iter_mut() {
+            *delay = SOSDelay::default();
+        }
+    }
 }
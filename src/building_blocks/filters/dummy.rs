@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -20,7 +20,14 @@ impl<const BUFSIZE: usize> Default for DummyFilter<BUFSIZE> {
 }
 
 impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for DummyFilter<BUFSIZE> {
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, _: SynthParameterLabel, _: &SynthParameterValue) {}
     fn finish(&mut self) {} // this effect is stateless
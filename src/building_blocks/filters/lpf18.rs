@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -86,29 +86,24 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for Lpf18<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        let mut update = false;
-        match par {
+    ) -> ModulatorResult<BUFSIZE> {
+        let replaced = match par {
             SynthParameterLabel::LowpassCutoffFrequency => {
                 self.cutoff = init;
-                self.cutoff_mod = Some(modulator);
-                update = true;
+                self.cutoff_mod.replace(modulator)
             }
             SynthParameterLabel::LowpassQFactor => {
                 self.res = init;
-                self.res_mod = Some(modulator);
-                update = true;
+                self.res_mod.replace(modulator)
             }
             SynthParameterLabel::LowpassFilterDistortion => {
                 self.dist = init;
-                self.dist_mod = Some(modulator);
-                update = true;
+                self.dist_mod.replace(modulator)
             }
-            _ => {}
-        }
-        if update {
-            self.update_internals(self.cutoff, self.res, self.dist);
-        }
+            _ => return Err(modulator),
+        };
+        self.update_internals(self.cutoff, self.res, self.dist);
+        Ok(replaced)
     }
     // some parameter limits might be nice ...
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
@@ -215,4 +210,14 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for Lpf18<BUFSIZE> {
 
         (self.aout * self.value).tanh()
     }
+
+    fn reset(&mut self) {
+        self.ay1 = 0.0;
+        self.ay2 = 0.0;
+        self.ay11 = 0.0;
+        self.ay31 = 0.0;
+        self.ax1 = 0.0;
+        self.aout = 0.0;
+        self.lastin = 0.0;
+    }
 }
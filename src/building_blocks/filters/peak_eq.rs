@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -80,29 +80,24 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for PeakEq<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        let mut update = false;
-        match par {
+    ) -> ModulatorResult<BUFSIZE> {
+        let replaced = match par {
             SynthParameterLabel::PeakFrequency => {
                 self.center = init;
-                self.center_mod = Some(modulator);
-                update = true;
+                self.center_mod.replace(modulator)
             }
             SynthParameterLabel::PeakGain => {
                 self.gain = init;
-                self.gain_mod = Some(modulator);
-                update = true;
+                self.gain_mod.replace(modulator)
             }
             SynthParameterLabel::PeakBandwidth => {
                 self.bw = init;
-                self.bw_mod = Some(modulator);
-                update = true;
+                self.bw_mod.replace(modulator)
             }
-            _ => {}
-        }
-        if update {
-            self.update_internals(self.center, self.bw, self.gain);
-        }
+            _ => return Err(modulator),
+        };
+        self.update_internals(self.center, self.bw, self.gain);
+        Ok(replaced)
     }
 
     // some parameter limits might be nice ...
@@ -195,4 +190,9 @@ impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for PeakEq<BUFSIZE> {
         self.del1 = x_h;
         out
     }
+
+    fn reset(&mut self) {
+        self.del1 = 0.0;
+        self.del2 = 0.0;
+    }
 }
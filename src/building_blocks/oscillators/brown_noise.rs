@@ -1,9 +1,10 @@
+use crate::building_blocks::random::{seed_from_parameter, WyRand};
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
- * A brown noise generator based on wyrand (through fastrand)
+ * A brown noise generator based on wyrand, seedable via the RandomSeed parameter
  * Based on https://github.com/porres/pd-else/blob/master/Classes/Source/brown%7E.c
  */
 #[derive(Clone)]
@@ -12,6 +13,7 @@ pub struct BrownNoise<const BUFSIZE: usize> {
     amp: f32,
     cur: f32,
     amp_mod: Option<Modulator<BUFSIZE>>, // and level
+    rng: WyRand,
 }
 
 impl<const BUFSIZE: usize> BrownNoise<BUFSIZE> {
@@ -21,6 +23,7 @@ impl<const BUFSIZE: usize> BrownNoise<BUFSIZE> {
             amp,
             cur: 0.0,
             amp_mod: None,
+            rng: WyRand::new(),
         }
     }
 }
@@ -33,18 +36,28 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for BrownNoise<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         if par == SynthParameterLabel::OscillatorAmplitude {
             self.amp = init;
-            self.amp_mod = Some(modulator);
+            Ok(self.amp_mod.replace(modulator))
+        } else {
+            Err(modulator)
         }
     }
 
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
-        if par == SynthParameterLabel::OscillatorAmplitude {
-            if let SynthParameterValue::ScalarF32(l) = value {
-                self.amp = *l;
+        match par {
+            SynthParameterLabel::OscillatorAmplitude => {
+                if let SynthParameterValue::ScalarF32(l) = value {
+                    self.amp = *l;
+                }
+            }
+            SynthParameterLabel::RandomSeed => {
+                if let Some(seed) = seed_from_parameter(value) {
+                    self.rng.seed(seed);
+                }
             }
+            _ => {}
         }
     }
 
@@ -70,7 +83,7 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for BrownNoise<BUFSIZE> {
                 .take(BUFSIZE)
                 .skip(start_sample)
             {
-                let noise = fastrand::i32(-100..100) as f32 / 100.0;
+                let noise = self.rng.i32(-100, 100) as f32 / 100.0;
                 self.cur += noise * self.step;
 
                 if self.cur > 1.0 {
@@ -83,7 +96,7 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for BrownNoise<BUFSIZE> {
             }
         } else {
             for current_sample in out_buf.iter_mut().take(BUFSIZE).skip(start_sample) {
-                let noise = fastrand::i32(-100..100) as f32 / 100.0;
+                let noise = self.rng.i32(-100, 100) as f32 / 100.0;
                 self.cur += noise * self.step;
 
                 if self.cur > 1.0 {
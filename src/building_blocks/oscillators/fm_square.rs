@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 use std::f32::consts::PI;
@@ -80,21 +80,21 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for FMSquare<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::PitchFrequency => {
                 self.freq = init;
-                self.freq_mod = Some(modulator);
+                Ok(self.freq_mod.replace(modulator))
             }
             SynthParameterLabel::OscillatorAmplitude => {
                 self.amp = init;
-                self.amp_mod = Some(modulator);
+                Ok(self.amp_mod.replace(modulator))
             }
             SynthParameterLabel::Pulsewidth => {
                 self.pulsewidth = init;
-                self.pw_mod = Some(modulator);
+                Ok(self.pw_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
 
@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 use std::f32::consts::PI;
@@ -76,17 +76,17 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for FMTri<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::PitchFrequency => {
                 self.freq = init;
-                self.freq_mod = Some(modulator);
+                Ok(self.freq_mod.replace(modulator))
             }
             SynthParameterLabel::OscillatorAmplitude => {
                 self.amp = init;
-                self.amp_mod = Some(modulator);
+                Ok(self.amp_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
 
@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -43,18 +43,18 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for LFCub<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::PitchFrequency => {
                 self.freq = init;
                 self.internal_freq = self.freq * self.sample_period;
-                self.freq_mod = Some(modulator);
+                Ok(self.freq_mod.replace(modulator))
             }
             SynthParameterLabel::OscillatorAmplitude => {
                 self.amp = init;
-                self.amp_mod = Some(modulator);
+                Ok(self.amp_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
     // some parameter limits might be nice ...
@@ -1,5 +1,6 @@
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue, SynthState,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    SynthState,
 };
 
 /**
@@ -59,22 +60,22 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for Wavematrix<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::PitchFrequency => {
                 self.freq = init;
                 self.phase_inc_smp = self.tablesize as f32 * self.freq * self.sample_period;
-                self.freq_mod = Some(modulator);
+                Ok(self.freq_mod.replace(modulator))
             }
             SynthParameterLabel::OscillatorAmplitude => {
                 self.amp = init;
-                self.amp_mod = Some(modulator);
+                Ok(self.amp_mod.replace(modulator))
             }
             SynthParameterLabel::WavematrixTableIndex => {
                 self.table_idx = init;
-                self.table_idx_mod = Some(modulator);
+                Ok(self.table_idx_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
 
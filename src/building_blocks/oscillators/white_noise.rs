@@ -1,19 +1,25 @@
+use crate::building_blocks::random::{seed_from_parameter, WyRand};
 use crate::building_blocks::{
-    Modulator, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
- * a white noise generator based on wyrand (through fastrand)
+ * a white noise generator based on wyrand, seedable via the RandomSeed parameter
  */
 #[derive(Clone)]
 pub struct WhiteNoise<const BUFSIZE: usize> {
     amp: f32,
     amp_mod: Option<Modulator<BUFSIZE>>, // and level
+    rng: WyRand,
 }
 
 impl<const BUFSIZE: usize> WhiteNoise<BUFSIZE> {
     pub fn new(amp: f32) -> Self {
-        WhiteNoise { amp, amp_mod: None }
+        WhiteNoise {
+            amp,
+            amp_mod: None,
+            rng: WyRand::new(),
+        }
     }
 }
 
@@ -25,18 +31,28 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for WhiteNoise<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         if par == SynthParameterLabel::OscillatorAmplitude {
             self.amp = init;
-            self.amp_mod = Some(modulator);
+            Ok(self.amp_mod.replace(modulator))
+        } else {
+            Err(modulator)
         }
     }
 
     fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
-        if par == SynthParameterLabel::OscillatorAmplitude {
-            if let SynthParameterValue::ScalarF32(l) = value {
-                self.amp = *l;
+        match par {
+            SynthParameterLabel::OscillatorAmplitude => {
+                if let SynthParameterValue::ScalarF32(l) = value {
+                    self.amp = *l;
+                }
+            }
+            SynthParameterLabel::RandomSeed => {
+                if let Some(seed) = seed_from_parameter(value) {
+                    self.rng.seed(seed);
+                }
             }
+            _ => {}
         }
     }
 
@@ -62,12 +78,12 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for WhiteNoise<BUFSIZE> {
                 .take(BUFSIZE)
                 .skip(start_sample)
             {
-                let raw = fastrand::i32(-100..100);
+                let raw = self.rng.i32(-100, 100);
                 *current_sample = (raw as f32 / 100.0) * amp_buf[idx];
             }
         } else {
             for current_sample in out_buf.iter_mut().take(BUFSIZE).skip(start_sample) {
-                let raw = fastrand::i32(-100..100);
+                let raw = self.rng.i32(-100, 100);
                 *current_sample = (raw as f32 / 100.0) * self.amp;
             }
         }
@@ -1,6 +1,6 @@
 use crate::building_blocks::{
-    oscillators::Wavetable, Modulator, MonoSource, SampleBuffer, SynthParameterLabel,
-    SynthParameterValue,
+    oscillators::Wavetable, Modulator, ModulatorResult, MonoSource, SampleBuffer,
+    SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -48,8 +48,8 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for WTSaw<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
-        self.wt.set_modulator(par, init, modulator);
+    ) -> ModulatorResult<BUFSIZE> {
+        self.wt.set_modulator(par, init, modulator)
     }
 
     fn set_parameter(&mut self, par: SynthParameterLabel, val: &SynthParameterValue) {
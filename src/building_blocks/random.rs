@@ -0,0 +1,55 @@
+use crate::building_blocks::SynthParameterValue;
+
+/**
+ * A small, seedable wyrand generator (the same algorithm fastrand uses).
+ *
+ * The state is kept as a plain integer, so the sources using it stay `Sync`,
+ * and it can be re-seeded to get reproducible output.
+ */
+#[derive(Clone, Copy)]
+pub struct WyRand {
+    state: u64,
+}
+
+impl Default for WyRand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WyRand {
+    /// random initial state, so the output is different every time ...
+    pub fn new() -> Self {
+        WyRand {
+            state: fastrand::u64(..),
+        }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        WyRand { state: seed }
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0xa076_1d64_78bd_642f);
+        let t = u128::from(self.state) * u128::from(self.state ^ 0xe703_7ed1_a0b4_28db);
+        (t as u64) ^ (t >> 64) as u64
+    }
+
+    /// random integer in the range from `low` (inclusive) to `high` (exclusive)
+    pub fn i32(&mut self, low: i32, high: i32) -> i32 {
+        low + (self.u64() % (high - low) as u64) as i32
+    }
+}
+
+/// Get a seed from a parameter value, if it's an integer.
+pub fn seed_from_parameter(value: &SynthParameterValue) -> Option<u64> {
+    match value {
+        SynthParameterValue::ScalarUsize(s) => Some(*s as u64),
+        SynthParameterValue::ScalarU32(s) => Some(*s as u64),
+        _ => None,
+    }
+}
@@ -1,5 +1,7 @@
+pub mod ambisonic_fdn;
 pub mod convolution;
 pub mod freeverb;
 
+pub use crate::building_blocks::reverb::ambisonic_fdn::AmbisonicReverb;
 pub use crate::building_blocks::reverb::convolution::MultichannelConvolutionReverb;
 pub use crate::building_blocks::reverb::freeverb::MultichannelFreeverb;
@@ -0,0 +1,292 @@
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, channel_order, sh_coefficients, MAX_AMBISONIC_CHANNELS, MAX_AMBISONIC_ORDER,
+};
+use crate::building_blocks::{MultichannelReverb, SynthParameterLabel, SynthParameterValue};
+
+// number of delay lines, one per direction
+const NUM_LINES: usize = 16;
+
+// mutually prime delay lengths @ 44.1kHz, between 25 and 55 ms
+const LINE_TUNING: [usize; NUM_LINES] = [
+    1117, 1201, 1297, 1381, 1453, 1543, 1621, 1709, 1787, 1873, 1951, 2039, 2113, 2207, 2293, 2377,
+];
+
+// same ranges as the freeverb parameters, so both reverbs
+// sound roughly alike with the same settings
+const SCALE_ROOM: f32 = 0.28;
+const OFFSET_ROOM: f32 = 0.7;
+const SCALE_DAMP: f32 = 0.4;
+const INITIAL_ROOM: f32 = 0.5;
+const INITIAL_DAMP: f32 = 0.5;
+const INPUT_GAIN: f32 = 0.5;
+
+/**
+ * A delay line with a dampening lowpass in the feedback path.
+ */
+struct DampedDelayLine {
+    buffer: Vec<f32>,
+    idx: usize,
+    feedback: f32,
+    filterstore: f32,
+}
+
+impl DampedDelayLine {
+    fn with_buffer_size(buf_size: usize) -> Self {
+        DampedDelayLine {
+            buffer: vec![0.0; buf_size.max(1)],
+            idx: 0,
+            feedback: 0.5,
+            filterstore: 0.0,
+        }
+    }
+
+    #[inline(always)]
+    fn read(&self) -> f32 {
+        self.buffer[self.idx]
+    }
+
+    // the damped and attenuated output, to be fed back
+    #[inline(always)]
+    fn feedback(&mut self, out: f32, damp: f32) -> f32 {
+        self.filterstore = out * (1.0 - damp) + self.filterstore * damp;
+        if !self.filterstore.is_normal() {
+            self.filterstore = 0.0;
+        }
+        self.filterstore * self.feedback
+    }
+
+    #[inline(always)]
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.idx] = sample;
+        self.idx += 1;
+        if self.idx >= self.buffer.len() {
+            self.idx = 0;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.filterstore = 0.0;
+    }
+}
+
+// in-place fast walsh-hadamard transform, normalized so it's orthogonal
+#[inline(always)]
+fn hadamard(lines: &mut [f32; NUM_LINES]) {
+    let mut h = 1;
+    while h < NUM_LINES {
+        for i in (0..NUM_LINES).step_by(h * 2) {
+            for j in i..i + h {
+                let a = lines[j];
+                let b = lines[j + h];
+                lines[j] = a + b;
+                lines[j + h] = a - b;
+            }
+        }
+        h *= 2;
+    }
+    for l in lines.iter_mut() {
+        *l *= 0.25; // 1 / sqrt(NUM_LINES)
+    }
+}
+
+/**
+ * A feedback delay network reverb that works on an ambisonic sound field.
+ *
+ * Each delay line is assigned a direction. The input sound field is decoded
+ * to these directions, and the output of the delay lines is encoded from
+ * them again, so the reverb keeps the spatial distribution of its input,
+ * while the (orthogonal) feedback matrix makes the tail diffuse over time.
+ *
+ * Like the freeverb, it doesn't have a dry/wet parameter, as it's
+ * meant to be used on a bus.
+ */
+pub struct AmbisonicReverb<const BUFSIZE: usize, const ACH: usize> {
+    lines: Vec<DampedDelayLine>,
+    decoder: [[f32; ACH]; NUM_LINES], // input, from the sound field to the lines
+    encoder: [[f32; NUM_LINES]; ACH], // output, from the lines to the sound field
+    channels: usize,
+    line_lengths: [f32; NUM_LINES],
+    damp: f32,
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicReverb<BUFSIZE, ACH> {
+    /// the order follows from the number of channels
+    pub fn new(sr: f32) -> Self {
+        Self::with_order(crate::building_blocks::ambisonics::ambisonic_order(ACH), sr)
+    }
+
+    /// Only work on the channels up to the given order, the others are ignored.
+    pub fn with_order(order: usize, sr: f32) -> Self {
+        let channels = ambisonic_channels(order.clamp(1, MAX_AMBISONIC_ORDER)).min(ACH);
+        let scale_factor = sr / 44100.0;
+
+        let mut lines = Vec::new();
+        let mut line_lengths = [0.0; NUM_LINES];
+        for (len, tuning) in line_lengths.iter_mut().zip(LINE_TUNING.iter()) {
+            *len = (*tuning as f32 * scale_factor).round().max(1.0);
+            lines.push(DampedDelayLine::with_buffer_size(*len as usize));
+        }
+
+        // the directions of the lines, evenly spread on a sphere
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+        let mut decoder = [[0.0; ACH]; NUM_LINES];
+        let mut encoder = [[0.0; NUM_LINES]; ACH];
+        for l in 0..NUM_LINES {
+            let elevation = (1.0 - 2.0 * (l as f32 + 0.5) / NUM_LINES as f32).asin();
+            let azimuth = l as f32 * golden_angle;
+            let coefs: [f32; MAX_AMBISONIC_CHANNELS] = sh_coefficients(azimuth, elevation);
+
+            for acn in 0..channels {
+                let n = channel_order(acn);
+                decoder[l][acn] = coefs[acn] * (2 * n + 1) as f32 / NUM_LINES as f32 * INPUT_GAIN;
+                encoder[acn][l] = coefs[acn];
+            }
+        }
+
+        let mut rev = AmbisonicReverb {
+            lines,
+            decoder,
+            encoder,
+            channels,
+            line_lengths,
+            damp: 0.0,
+        };
+
+        rev.set_roomsize(INITIAL_ROOM);
+        rev.set_damp(INITIAL_DAMP);
+        rev
+    }
+
+    pub fn set_roomsize(&mut self, value: f32) {
+        // the feedback applies to the average line length, so
+        // all lines decay at the same rate
+        let feedback = (value * SCALE_ROOM + OFFSET_ROOM).clamp(0.0, 0.999);
+        let mean_len = self.line_lengths.iter().sum::<f32>() / NUM_LINES as f32;
+        for (line, len) in self.lines.iter_mut().zip(self.line_lengths.iter()) {
+            line.feedback = feedback.powf(len / mean_len);
+        }
+    }
+
+    pub fn set_damp(&mut self, value: f32) {
+        self.damp = (value * SCALE_DAMP).clamp(0.0, 0.999);
+    }
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> MultichannelReverb<BUFSIZE, ACH>
+    for AmbisonicReverb<BUFSIZE, ACH>
+{
+    fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::ReverbRoomsize => self.set_roomsize(*val),
+                SynthParameterLabel::ReverbDampening => self.set_damp(*val),
+                _ => (),
+            };
+        }
+    }
+
+    fn clear(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.clear();
+        }
+    }
+
+    fn process(&mut self, block: [[f32; BUFSIZE]; ACH]) -> [[f32; BUFSIZE]; ACH] {
+        let mut out_buf = [[0.0; BUFSIZE]; ACH];
+        self.process_segment(&block, &mut out_buf, 0, BUFSIZE);
+        out_buf
+    }
+
+    fn process_segment(
+        &mut self,
+        block: &[[f32; BUFSIZE]; ACH],
+        out_buf: &mut [[f32; BUFSIZE]; ACH],
+        start_sample: usize,
+        end_sample: usize,
+    ) {
+        for s in start_sample..end_sample {
+            let mut outs = [0.0; NUM_LINES];
+            let mut fb = [0.0; NUM_LINES];
+
+            for (l, line) in self.lines.iter_mut().enumerate() {
+                outs[l] = line.read();
+                fb[l] = line.feedback(outs[l], self.damp);
+            }
+
+            hadamard(&mut fb);
+
+            for (l, line) in self.lines.iter_mut().enumerate() {
+                let mut input = 0.0;
+                for acn in 0..self.channels {
+                    input += block[acn][s] * self.decoder[l][acn];
+                }
+                line.write(input + fb[l]);
+            }
+
+            for acn in 0..self.channels {
+                let mut out = 0.0;
+                for l in 0..NUM_LINES {
+                    out += outs[l] * self.encoder[acn][l];
+                }
+                out_buf[acn][s] = out;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ambisonic_reverb() {
+        let mut rev = AmbisonicReverb::<128, 16>::with_order(1, 44100.0);
+
+        // a click from the right
+        let coefs: [f32; 16] = sh_coefficients(std::f32::consts::PI / 2.0, 0.0);
+        let mut block = [[0.0; 128]; 16];
+        for (ch, c) in block.iter_mut().zip(coefs.iter()) {
+            ch[0] = *c;
+        }
+
+        let mut energy = [0.0; 16];
+        let mut tail_energy = 0.0;
+        let mut early_w_y = 0.0;
+        for i in 0..400 {
+            let out = rev.process(block);
+            block = [[0.0; 128]; 16];
+            for c in 0..16 {
+                energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+            }
+            if i < 20 {
+                early_w_y += out[0]
+                    .iter()
+                    .zip(out[1].iter())
+                    .map(|(w, y)| w * y)
+                    .sum::<f32>();
+            }
+            if i >= 350 {
+                tail_energy += out[0].iter().map(|x| x * x).sum::<f32>();
+            }
+        }
+
+        // there is a reverb, and it decays
+        assert!(energy[0] > 0.0);
+        assert!(tail_energy < energy[0] * 0.001);
+
+        // the higher orders are left alone
+        for e in energy.iter().skip(4) {
+            assert_eq!(*e, 0.0);
+        }
+
+        // the early part comes from the source direction (negative y is right),
+        // the tail is more diffuse than the source
+        assert!(early_w_y < 0.0);
+        assert!(energy[1] < energy[0]);
+
+        rev.clear();
+        let out = rev.process([[0.0; 128]; 16]);
+        assert!(out.iter().flatten().all(|x| *x == 0.0));
+    }
+}
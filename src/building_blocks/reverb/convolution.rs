@@ -22,6 +22,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelReverb<BUFSIZE, NCHAN
         // nothing to do here ...
     }
 
+    fn clear(&mut self) {
+        for convolver in self.channel_convolvers.iter_mut() {
+            convolver.clear();
+        }
+    }
+
     /**
      * Main processing routine.
      * Takes a mono block, as this would be downmixed anyway.
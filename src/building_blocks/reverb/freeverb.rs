@@ -154,6 +154,10 @@ impl Allpass {
         }
     }
 
+    pub fn clear(&mut self) {
+        self.delay_buffer.fill(0.0);
+    }
+
     #[inline(always)]
     pub fn process_sample(&mut self, sample: f32) -> f32 {
         let mut buf_out: f32 = self.delay_buffer[self.delay_idx];
@@ -202,6 +206,11 @@ impl Comb {
         }
     }
 
+    pub fn clear(&mut self) {
+        self.delay_buffer.fill(0.0);
+        self.filterstore = 0.0;
+    }
+
     #[inline(always)]
     pub fn process_sample(&mut self, sample: f32) -> f32 {
         let mut out = self.delay_buffer[self.delay_idx];
@@ -378,17 +387,39 @@ impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelReverb<BUFSIZE, NCHAN
         }
     }
 
+    fn clear(&mut self) {
+        for (comb_l, comb_r) in self.combs.iter_mut().flatten() {
+            comb_l.clear();
+            comb_r.clear();
+        }
+        for (allpass_l, allpass_r) in self.allpasses.iter_mut().flatten() {
+            allpass_l.clear();
+            allpass_r.clear();
+        }
+    }
+
     /**
      * Main processing routine.
      * Takes a mono block, as this would be downmixed anyway.
      */
     fn process(&mut self, block: [[f32; BUFSIZE]; NCHAN]) -> [[f32; BUFSIZE]; NCHAN] {
         let mut out_buf = [[0.0; BUFSIZE]; NCHAN];
+        self.process_segment(&block, &mut out_buf, 0, BUFSIZE);
+        out_buf
+    }
+
+    fn process_segment(
+        &mut self,
+        block: &[[f32; BUFSIZE]; NCHAN],
+        out_buf: &mut [[f32; BUFSIZE]; NCHAN],
+        start_sample: usize,
+        end_sample: usize,
+    ) {
         let cur_gain = self.gain * 0.5;
 
         for c in 0..NCHAN {
             let upper = (c + 1) % NCHAN;
-            for i in 0..BUFSIZE {
+            for i in start_sample..end_sample {
                 let mut out_l = 0.0;
                 let mut out_r = 0.0;
 
@@ -410,7 +441,5 @@ impl<const BUFSIZE: usize, const NCHAN: usize> MultichannelReverb<BUFSIZE, NCHAN
                 out_buf[upper][i] = (out_r * self.wet1) + (out_l * self.wet2);
             }
         }
-
-        out_buf
     }
 }
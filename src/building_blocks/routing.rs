@@ -1,8 +1,10 @@
 mod bal_chan;
 mod pan_chan; // pan mono // balance stereo
+mod vbap; // pan mono on arbitrary speaker layouts
 
 pub use bal_chan::BalChan;
 pub use pan_chan::PanChan;
+pub use vbap::{VbapLayout, VbapPanner};
 
 // TEST TEST TEST
 #[cfg(test)]
@@ -10,6 +12,7 @@ mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
     use crate::building_blocks::{SynthParameterLabel, SynthParameterValue};
+    use std::f32::consts::PI;
     #[test]
     fn panchan_test_basic_pan() {
         let mut pchan = PanChan::<128, 2>::new();
@@ -131,4 +134,90 @@ mod tests {
         assert_approx_eq::assert_approx_eq!(block_out[6][0], 0.0, 0.0001);
         assert_approx_eq::assert_approx_eq!(block_out[7][0], 0.0, 0.0001);
     }
+
+    #[test]
+    fn vbap_test_ring() {
+        // a square, front left, front right, back right, back left
+        let layout = std::sync::Arc::new(VbapLayout::new(&[
+            (-PI / 4.0, 0.0),
+            (PI / 4.0, 0.0),
+            (3.0 * PI / 4.0, 0.0),
+            (-3.0 * PI / 4.0, 0.0),
+        ]));
+        let mut vbap = VbapPanner::<128, 4>::new(layout.clone());
+
+        let mut block = [0.0; 128];
+        block[0] = 1.0;
+
+        // front center, between the front speakers
+        let block_out = vbap.process_block(block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(block_out[0][0], 0.707, 0.001);
+        assert_approx_eq::assert_approx_eq!(block_out[1][0], 0.707, 0.001);
+        assert_approx_eq::assert_approx_eq!(block_out[2][0], 0.0, 0.0001);
+        assert_approx_eq::assert_approx_eq!(block_out[3][0], 0.0, 0.0001);
+
+        // on the back right speaker, the gains are interpolated
+        vbap.set_parameter(
+            SynthParameterLabel::AmbisonicAzimuth,
+            &SynthParameterValue::ScalarF32(3.0 * PI / 4.0),
+        );
+        vbap.process_block(block, 0, &Vec::new());
+        let block_out = vbap.process_block(block, 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(block_out[0][0], 0.0, 0.0001);
+        assert_approx_eq::assert_approx_eq!(block_out[1][0], 0.0, 0.0001);
+        assert_approx_eq::assert_approx_eq!(block_out[2][0], 1.0, 0.0001);
+        assert_approx_eq::assert_approx_eq!(block_out[3][0], 0.0, 0.0001);
+
+        // the spread reaches the neighbours, at the same power
+        let mut gains = [0.0; 4];
+        layout.gains(3.0 * PI / 4.0, 0.0, PI / 4.0, &mut gains);
+        assert!(gains[1] > 0.1 && gains[3] > 0.1);
+        assert!(gains[2] > gains[1]);
+        assert_approx_eq::assert_approx_eq!(gains.iter().map(|g| g * g).sum::<f32>(), 1.0, 0.0001);
+    }
+
+    #[test]
+    fn vbap_test_3d() {
+        // an octahedron, front, right, back, left, top, bottom
+        let layout = std::sync::Arc::new(VbapLayout::new(&[
+            (0.0, 0.0),
+            (PI / 2.0, 0.0),
+            (PI, 0.0),
+            (-PI / 2.0, 0.0),
+            (0.0, PI / 2.0),
+            (0.0, -PI / 2.0),
+        ]));
+
+        let mut gains = [0.0; 6];
+        layout.gains(PI / 2.0, PI / 4.0, 0.0, &mut gains);
+        for (g, expected) in gains.iter().zip([0.0, 0.707, 0.0, 0.0, 0.707, 0.0]) {
+            assert_approx_eq::assert_approx_eq!(g, expected, 0.001);
+        }
+
+        // in the middle of a triangle
+        layout.gains(3.0 * PI / 4.0, -0.6155, 0.0, &mut gains);
+        for (g, expected) in gains.iter().zip([0.0, 0.577, 0.577, 0.0, 0.0, 0.577]) {
+            assert_approx_eq::assert_approx_eq!(g, expected, 0.001);
+        }
+
+        // a modulated direction, from the front to the right
+        let mut vbap = VbapPanner::<128, 6>::new(layout);
+        assert!(vbap
+            .set_modulator(
+                SynthParameterLabel::AmbisonicAzimuth,
+                0.0,
+                crate::building_blocks::Modulator::lin_ramp(
+                    crate::building_blocks::ValOp::Replace,
+                    0.0,
+                    PI / 2.0,
+                    0.001,
+                    44100.0,
+                ),
+            )
+            .is_ok());
+        let block_out = vbap.process_block([1.0; 128], 0, &Vec::new());
+        assert_approx_eq::assert_approx_eq!(block_out[0][127], 0.0, 0.001);
+        assert_approx_eq::assert_approx_eq!(block_out[1][127], 1.0, 0.001);
+        assert!(block_out[0][20] > 0.0 && block_out[1][20] > 0.0);
+    }
 }
@@ -1,4 +1,6 @@
-use crate::building_blocks::{Modulator, SampleBuffer, SynthParameterLabel, SynthParameterValue};
+use crate::building_blocks::{
+    Modulator, ModulatorResult, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+};
 
 use std::f32::consts::PI;
 
@@ -34,10 +36,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> BalChan<BUFSIZE, NCHAN> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         if par == SynthParameterLabel::ChannelPosition {
             self.pos = init; // keep for later
-            self.pos_mod = Some(modulator);
+            Ok(self.pos_mod.replace(modulator))
+        } else {
+            Err(modulator)
         }
     }
 
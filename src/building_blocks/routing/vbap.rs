@@ -0,0 +1,418 @@
+use crate::building_blocks::ambisonics::direction_vector;
+use crate::building_blocks::{
+    Modulator, ModulatorResult, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// with a modulated direction, the gains are calculated every
+// this many samples and interpolated in between
+const CONTROL_INTERVAL: usize = 16;
+
+// number of virtual sources around the direction of a spread source
+const SPREAD_SOURCES: usize = 6;
+
+const EPSILON: f32 = 0.0001;
+
+// a speaker pair (2D) or triangle (3D) and the inverse of the matrix of their
+// direction vectors, to get the gains from a direction
+struct VbapBase {
+    speakers: [usize; 3],
+    inverse: [[f32; 3]; 3],
+}
+
+/**
+ * A loudspeaker layout for vector-base amplitude panning (VBAP), with one
+ * (azimuth, elevation) pair (in radians) per output channel, using the same
+ * directions as the ambisonic module.
+ *
+ * If all speakers are on the horizontal plane, sources are panned between
+ * adjacent speakers, otherwise between the corners of the triangles that
+ * make up the hull of the layout. Sources outside of the area covered by
+ * the speakers (i.e. below a dome) go to the closest pair or triangle.
+ *
+ * The layout is analyzed once, and shared between the panners.
+ */
+pub struct VbapLayout {
+    speakers: Vec<[f32; 3]>,
+    bases: Vec<VbapBase>,
+    is_2d: bool,
+}
+
+impl VbapLayout {
+    pub fn new(layout: &[(f32, f32)]) -> Self {
+        let is_2d = layout.iter().all(|(_, ele)| ele.abs() < 0.001);
+        let speakers: Vec<[f32; 3]> = layout
+            .iter()
+            .map(|(azi, ele)| direction_vector(*azi, *ele))
+            .collect();
+
+        let bases = if is_2d {
+            Self::pairs(&speakers)
+        } else {
+            Self::triangles(&speakers)
+        };
+
+        VbapLayout {
+            speakers,
+            bases,
+            is_2d,
+        }
+    }
+
+    pub fn num_speakers(&self) -> usize {
+        self.speakers.len()
+    }
+
+    // adjacent speakers on the ring, less than half a circle apart
+    fn pairs(speakers: &[[f32; 3]]) -> Vec<VbapBase> {
+        let mut sorted: Vec<(f32, usize)> = speakers
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v[1].atan2(v[0]), i))
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut bases = Vec::new();
+        if sorted.len() < 2 {
+            return bases;
+        }
+
+        for i in 0..sorted.len() {
+            let (angle_a, a) = sorted[i];
+            let (angle_b, b) = sorted[(i + 1) % sorted.len()];
+            let gap = (angle_b - angle_a).rem_euclid(2.0 * PI);
+
+            let (va, vb) = (speakers[a], speakers[b]);
+            let det = va[0] * vb[1] - va[1] * vb[0];
+            if gap >= PI - EPSILON || det.abs() < EPSILON {
+                continue;
+            }
+
+            bases.push(VbapBase {
+                speakers: [a, b, a],
+                inverse: [
+                    [vb[1] / det, -vb[0] / det, 0.0],
+                    [-va[1] / det, va[0] / det, 0.0],
+                    [0.0; 3],
+                ],
+            });
+        }
+        bases
+    }
+
+    // the faces of the convex hull, that is, all speaker triangles
+    // with all other speakers on one side
+    fn triangles(speakers: &[[f32; 3]]) -> Vec<VbapBase> {
+        let mut bases = Vec::new();
+        let n = speakers.len();
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    let (va, vb, vc) = (speakers[a], speakers[b], speakers[c]);
+
+                    // triangles in a plane through the center can't be inverted
+                    let bc = cross(vb, vc);
+                    let det = dot(va, bc);
+                    if det.abs() < EPSILON {
+                        continue;
+                    }
+
+                    let normal = cross(sub(vb, va), sub(vc, va));
+                    let (mut above, mut below) = (false, false);
+                    for (i, vd) in speakers.iter().enumerate() {
+                        if i == a || i == b || i == c {
+                            continue;
+                        }
+                        let side = dot(normal, sub(*vd, va));
+                        above |= side > EPSILON;
+                        below |= side < -EPSILON;
+                    }
+                    if above && below {
+                        continue;
+                    }
+
+                    let ca = cross(vc, va);
+                    let ab = cross(va, vb);
+                    bases.push(VbapBase {
+                        speakers: [a, b, c],
+                        inverse: [
+                            bc.map(|x| x / det),
+                            ca.map(|x| x / det),
+                            ab.map(|x| x / det),
+                        ],
+                    });
+                }
+            }
+        }
+        bases
+    }
+
+    // the gains of a point source, added to the gains
+    fn add_point_gains(&self, direction: [f32; 3], gains: &mut [f32]) {
+        let mut direction = direction;
+        if self.is_2d {
+            direction[2] = 0.0;
+            let norm = dot(direction, direction).sqrt();
+            if norm < EPSILON {
+                // straight above or below the ring, all speakers alike
+                let gain = 1.0 / (self.speakers.len() as f32).sqrt();
+                for g in gains.iter_mut().take(self.speakers.len()) {
+                    *g += gain;
+                }
+                return;
+            }
+            direction = direction.map(|x| x / norm);
+        }
+
+        // the base with all positive gains, or the one that's closest
+        let num = if self.is_2d { 2 } else { 3 };
+        let mut point_gains = [0.0; 3];
+        let mut best_min = f32::NEG_INFINITY;
+        let mut best_idx = 0;
+        for (idx, base) in self.bases.iter().enumerate() {
+            let base_gains = base.inverse.map(|row| dot(row, direction));
+            let min = base_gains[..num]
+                .iter()
+                .fold(f32::INFINITY, |m, g| m.min(*g));
+            if min > best_min {
+                best_min = min;
+                best_idx = idx;
+                point_gains = base_gains.map(|g| g.max(0.0));
+            }
+            if min >= -EPSILON {
+                break;
+            }
+        }
+        let norm = dot(point_gains, point_gains).sqrt();
+
+        if norm > EPSILON {
+            let base = &self.bases[best_idx];
+            for (speaker, gain) in base.speakers.iter().zip(point_gains.iter()).take(num) {
+                if let Some(g) = gains.get_mut(*speaker) {
+                    *g += gain / norm;
+                }
+            }
+        } else if let Some(nearest) = (0..self.speakers.len()).max_by(|a, b| {
+            dot(self.speakers[*a], direction).total_cmp(&dot(self.speakers[*b], direction))
+        }) {
+            // no pair or triangle to pan between, take the nearest speaker
+            if let Some(g) = gains.get_mut(nearest) {
+                *g += 1.0;
+            }
+        }
+    }
+
+    /// The speaker gains for a direction (in radians) and a spread, one per
+    /// output channel. The spread is the half-angle of the cone the sound is
+    /// spread on, i.e. the angle between the direction and the cone's edge,
+    /// so 0 is a point source and PI covers all directions. Speakers beyond
+    /// the number of gains are ignored.
+    pub fn gains(&self, azimuth: f32, elevation: f32, spread: f32, gains: &mut [f32]) {
+        gains.fill(0.0);
+        if self.speakers.is_empty() {
+            return;
+        }
+
+        let direction = direction_vector(azimuth, elevation);
+        self.add_point_gains(direction, gains);
+
+        // virtual sources on a cone around the direction, at the spread angle
+        let spread = spread.clamp(0.0, PI);
+        if spread > EPSILON {
+            let helper = if direction[2].abs() < 0.9 {
+                [0.0, 0.0, 1.0]
+            } else {
+                [1.0, 0.0, 0.0]
+            };
+            let u = cross(helper, direction);
+            let u_norm = dot(u, u).sqrt();
+            let u = u.map(|x| x / u_norm);
+            let v = cross(direction, u);
+
+            for k in 0..SPREAD_SOURCES {
+                let phi = k as f32 * 2.0 * PI / SPREAD_SOURCES as f32;
+                let mut virtual_direction = [0.0; 3];
+                for i in 0..3 {
+                    virtual_direction[i] = spread.cos() * direction[i]
+                        + spread.sin() * (phi.cos() * u[i] + phi.sin() * v[i]);
+                }
+                self.add_point_gains(virtual_direction, gains);
+            }
+        }
+
+        // keep the power constant
+        let power: f32 = gains.iter().map(|g| g * g).sum();
+        if power > 0.0 {
+            let norm = power.sqrt();
+            for g in gains.iter_mut() {
+                *g /= norm;
+            }
+        }
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/**
+ * Pans a mono signal on an arbitrary speaker layout with VBAP, as an
+ * alternative to `PanChan`. The direction is set by the `AmbisonicAzimuth`
+ * and `AmbisonicElevation` parameters, the width by `VbapSpread`
+ * (the half-angle of the cone, in radians).
+ */
+pub struct VbapPanner<const BUFSIZE: usize, const NCHAN: usize> {
+    layout: Arc<VbapLayout>,
+    azimuth: f32,
+    elevation: f32,
+    spread: f32,
+    azimuth_mod: Option<Modulator<BUFSIZE>>,
+    elevation_mod: Option<Modulator<BUFSIZE>>,
+    spread_mod: Option<Modulator<BUFSIZE>>,
+    gains: [f32; NCHAN],  // current gains
+    target: [f32; NCHAN], // gains of the unmodulated direction
+    started: bool,        // before the first block, the gains don't need to be interpolated
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> VbapPanner<BUFSIZE, NCHAN> {
+    pub fn new(layout: Arc<VbapLayout>) -> Self {
+        let mut gains = [0.0; NCHAN];
+        layout.gains(0.0, 0.0, 0.0, &mut gains);
+        VbapPanner {
+            layout,
+            azimuth: 0.0,
+            elevation: 0.0,
+            spread: 0.0,
+            azimuth_mod: None,
+            elevation_mod: None,
+            spread_mod: None,
+            gains,
+            target: gains,
+            started: false,
+        }
+    }
+
+    pub fn set_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        match par {
+            SynthParameterLabel::AmbisonicAzimuth => {
+                self.azimuth = init; // keep for later
+                Ok(self.azimuth_mod.replace(modulator))
+            }
+            SynthParameterLabel::AmbisonicElevation => {
+                self.elevation = init; // keep for later
+                Ok(self.elevation_mod.replace(modulator))
+            }
+            SynthParameterLabel::VbapSpread => {
+                self.spread = init; // keep for later
+                Ok(self.spread_mod.replace(modulator))
+            }
+            _ => Err(modulator),
+        }
+    }
+
+    pub fn set_parameter(&mut self, par: SynthParameterLabel, value: &SynthParameterValue) {
+        if let SynthParameterValue::ScalarF32(val) = value {
+            match par {
+                SynthParameterLabel::AmbisonicAzimuth => self.azimuth = *val,
+                SynthParameterLabel::AmbisonicElevation => self.elevation = *val,
+                SynthParameterLabel::VbapSpread => self.spread = *val,
+                _ => return,
+            };
+
+            self.layout
+                .gains(self.azimuth, self.elevation, self.spread, &mut self.target);
+            if !self.started {
+                self.gains = self.target;
+            }
+        }
+    }
+
+    pub fn process_block(
+        &mut self,
+        block: [f32; BUFSIZE],
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; NCHAN] {
+        self.started = true;
+
+        let modulated =
+            self.azimuth_mod.is_some() || self.elevation_mod.is_some() || self.spread_mod.is_some();
+
+        let mut out_buf = [[0.0; BUFSIZE]; NCHAN];
+
+        if !modulated && self.gains == self.target {
+            for (out, gain) in out_buf.iter_mut().zip(self.gains.iter()) {
+                for s in 0..BUFSIZE {
+                    out[s] = block[s] * gain;
+                }
+            }
+            return out_buf;
+        }
+
+        let mut buffers = [[0.0; BUFSIZE]; 3];
+        if modulated {
+            for ((buf, modulator), val) in buffers
+                .iter_mut()
+                .zip([
+                    &mut self.azimuth_mod,
+                    &mut self.elevation_mod,
+                    &mut self.spread_mod,
+                ])
+                .zip([self.azimuth, self.elevation, self.spread])
+            {
+                *buf = if let Some(m) = modulator.as_mut() {
+                    m.process(val, start_sample, in_buffers)
+                } else {
+                    [val; BUFSIZE]
+                };
+            }
+        }
+
+        // interpolate towards the gains at the end of each interval
+        let mut start = 0;
+        while start < BUFSIZE {
+            let end = (start + CONTROL_INTERVAL).min(BUFSIZE);
+            let mut target = self.target;
+            if modulated {
+                self.layout.gains(
+                    buffers[0][end - 1],
+                    buffers[1][end - 1],
+                    buffers[2][end - 1],
+                    &mut target,
+                );
+            }
+
+            let len = (end - start) as f32;
+            for (c, out) in out_buf.iter_mut().enumerate() {
+                let step = (target[c] - self.gains[c]) / len;
+                for (i, s) in (start..end).enumerate() {
+                    out[s] = block[s] * (self.gains[c] + step * (i + 1) as f32);
+                }
+            }
+
+            self.gains = target;
+            start = end;
+        }
+
+        out_buf
+    }
+}
@@ -1,6 +1,6 @@
 // parent imports
 use crate::building_blocks::{
-    interpolation::*, Modulator, MonoSource, SampleBuffer, SynthParameterLabel,
+    interpolation::*, Modulator, ModulatorResult, MonoSource, SampleBuffer, SynthParameterLabel,
     SynthParameterValue, SynthState,
 };
 
@@ -280,17 +280,17 @@ impl<const BUFSIZE: usize> MonoSource<BUFSIZE> for MonoSampler<BUFSIZE> {
         par: SynthParameterLabel,
         init: f32,
         modulator: Modulator<BUFSIZE>,
-    ) {
+    ) -> ModulatorResult<BUFSIZE> {
         match par {
             SynthParameterLabel::PlaybackRate => {
                 self.playback_rate = init;
-                self.rate_mod = Some(modulator);
+                Ok(self.rate_mod.replace(modulator))
             }
             SynthParameterLabel::OscillatorAmplitude => {
                 self.amp = init;
-                self.amp_mod = Some(modulator);
+                Ok(self.amp_mod.replace(modulator))
             }
-            _ => {}
+            _ => Err(modulator),
         }
     }
     fn set_parameter(&mut self, par: SynthParameterLabel, val: &SynthParameterValue) {
@@ -1,5 +1,5 @@
 use crate::building_blocks::{
-    Modulator, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
+    Modulator, ModulatorResult, MonoEffect, SampleBuffer, SynthParameterLabel, SynthParameterValue,
 };
 
 /**
@@ -22,7 +22,14 @@ impl<const BUFSIZE: usize> Default for Waveshaper<BUFSIZE> {
 }
 
 impl<const BUFSIZE: usize> MonoEffect<BUFSIZE> for Waveshaper<BUFSIZE> {
-    fn set_modulator(&mut self, _: SynthParameterLabel, _: f32, _: Modulator<BUFSIZE>) {}
+    fn set_modulator(
+        &mut self,
+        _: SynthParameterLabel,
+        _: f32,
+        modulator: Modulator<BUFSIZE>,
+    ) -> ModulatorResult<BUFSIZE> {
+        Err(modulator)
+    }
 
     fn set_parameter(&mut self, label: SynthParameterLabel, value: &SynthParameterValue) {
         if let SynthParameterLabel::WaveshaperMix = label {
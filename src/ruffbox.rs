@@ -1,4 +1,10 @@
+pub mod ruffbox_binaural_filter;
+pub mod ruffbox_clock;
+pub mod ruffbox_config;
 pub mod ruffbox_controls;
+pub mod ruffbox_error;
+pub mod ruffbox_meters;
+pub mod ruffbox_offline;
 pub mod ruffbox_playhead;
 
 // crossbeam for the event queue
@@ -7,18 +13,25 @@ use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 
 use std::cmp::Ordering;
+use std::mem::Discriminant;
 use std::sync::Arc;
 
+use crate::building_blocks::ambisonics::binauralizer::{resample_filter, Binauralizer};
+use crate::building_blocks::ambisonics::MAX_AMBISONIC_CHANNELS;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
     Modulator, SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue, ValueOrModulator,
 };
 
-pub use crate::ruffbox::{ruffbox_controls::*, ruffbox_playhead::*};
+pub use crate::ruffbox::{
+    ruffbox_binaural_filter::*, ruffbox_clock::*, ruffbox_config::*, ruffbox_controls::*,
+    ruffbox_error::*, ruffbox_meters::*, ruffbox_offline::*, ruffbox_playhead::*,
+};
+use crate::synths::SynthType;
 
 pub enum ScheduledSource<const BUFSIZE: usize, const NCHAN: usize> {
     Channel(Box<dyn Synth<BUFSIZE, NCHAN> + Send + Sync>),
-    Ambi(Box<dyn Synth<BUFSIZE, 4> + Send + Sync>),
+    Ambi(Box<dyn Synth<BUFSIZE, MAX_AMBISONIC_CHANNELS> + Send + Sync>), // see AmbisonicBusSynth
 }
 
 /// timed event, to be created in the trigger method, then
@@ -26,6 +39,12 @@ pub enum ScheduledSource<const BUFSIZE: usize, const NCHAN: usize> {
 /// or pushed to the pending queue ...
 pub(crate) struct ScheduledEvent<const BUFSIZE: usize, const NCHAN: usize> {
     timestamp: f64,
+    id: usize, // instance id, to address the instance once it's been triggered
+    synth_type: Discriminant<SynthType>, // needed for voice stealing
+    aux_sends: Vec<f32>, // send levels for the additional aux buses
+    group: usize, // instance group, to control several instances at once
+    tag: Option<usize>, // to flush or shift groups of pending events
+    beat: Option<f64>, // the beat position, if scheduled on the tempo clock
     source: ScheduledSource<BUFSIZE, NCHAN>,
 }
 
@@ -33,7 +52,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Ord for ScheduledEvent<BUFSIZE, N
     /// ScheduledEvent implements Ord so the pending events queue
     /// can be ordered by the timestamps ...
     fn cmp(&self, other: &Self) -> Ordering {
-        self.timestamp.partial_cmp(&other.timestamp).unwrap()
+        self.timestamp.total_cmp(&other.timestamp)
     }
 }
 
@@ -49,7 +68,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> PartialEq for ScheduledEvent<BUFS
     /// ScheduledEvent implements PartialEq so the pending events queue
     /// can be ordered by the timestamps ...
     fn eq(&self, other: &Self) -> bool {
-        self.timestamp == other.timestamp
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -57,9 +76,20 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Eq for ScheduledEvent<BUFSIZE, NC
 
 // constructor implementation
 impl<const BUFSIZE: usize, const NCHAN: usize> ScheduledEvent<BUFSIZE, NCHAN> {
-    pub fn new(ts: f64, src: ScheduledSource<BUFSIZE, NCHAN>) -> Self {
+    pub fn new(
+        ts: f64,
+        id: usize,
+        synth_type: Discriminant<SynthType>,
+        src: ScheduledSource<BUFSIZE, NCHAN>,
+    ) -> Self {
         ScheduledEvent {
             timestamp: ts,
+            id,
+            synth_type,
+            aux_sends: Vec::new(),
+            group: 0,
+            tag: None,
+            beat: None,
             source: src,
         }
     }
@@ -67,10 +97,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> ScheduledEvent<BUFSIZE, NCHAN> {
     pub fn set_parameter(&mut self, par: SynthParameterAddress, value: &SynthParameterValue) {
         match self.source {
             ScheduledSource::Channel(ref mut src) => {
-                src.set_parameter(par, value);
+                set_parameter_with_aux_sends(src.as_mut(), &mut self.aux_sends, par, value);
             }
             ScheduledSource::Ambi(ref mut src) => {
-                src.set_parameter(par, value);
+                set_parameter_with_aux_sends(src.as_mut(), &mut self.aux_sends, par, value);
             }
         }
     }
@@ -80,39 +110,227 @@ impl<const BUFSIZE: usize, const NCHAN: usize> ScheduledEvent<BUFSIZE, NCHAN> {
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
         match self.source {
             ScheduledSource::Channel(ref mut src) => {
-                src.set_modulator(par, init, modulator);
+                set_modulator_with_aux_sends(src.as_mut(), par, init, modulator, copies, leftover);
             }
             ScheduledSource::Ambi(ref mut src) => {
-                src.set_modulator(par, init, modulator);
+                set_modulator_with_aux_sends(src.as_mut(), par, init, modulator, copies, leftover);
             }
         }
     }
 
+    /// the value is handed to `leftover` once it's set, as well as
+    /// the modulators that were replaced or not taken
     fn set_param_or_modulator(
         &mut self,
         par: SynthParameterAddress,
         val_or_mod: ValueOrModulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
         match val_or_mod {
-            ValueOrModulator::Val(val) => self.set_parameter(par, &val),
-            ValueOrModulator::Mod(init, modulator) => self.set_modulator(par, init, modulator),
+            ValueOrModulator::Val(val) => {
+                self.set_parameter(par, &val);
+                leftover(ValueOrModulator::Val(val));
+            }
+            ValueOrModulator::Mod(init, modulator) => {
+                self.set_modulator(par, init, modulator, copies, leftover)
+            }
+        }
+    }
+
+    /// see `Synth::modulator_copies`
+    fn modulator_copies(&self) -> usize {
+        match self.source {
+            ScheduledSource::Channel(ref src) => src.modulator_copies(),
+            ScheduledSource::Ambi(ref src) => src.modulator_copies(),
+        }
+    }
+
+    fn release(&mut self) {
+        match self.source {
+            ScheduledSource::Channel(ref mut src) => {
+                src.release();
+            }
+            ScheduledSource::Ambi(ref mut src) => {
+                src.release();
+            }
         }
     }
 }
 
+/// The aux send levels for the additional buses are kept outside of the synths,
+/// sends to the master reverb and delay are just the synth's reverb and delay mix.
+pub(crate) fn set_parameter_with_aux_sends<const BUFSIZE: usize, const NCHAN: usize>(
+    synth: &mut (dyn Synth<BUFSIZE, NCHAN> + Send + Sync),
+    aux_sends: &mut [f32],
+    par: SynthParameterAddress,
+    value: &SynthParameterValue,
+) {
+    if par.label != SynthParameterLabel::AuxSend {
+        synth.set_parameter(par, value);
+        return;
+    }
+
+    match par.idx {
+        Some(0) | None => synth.set_parameter(SynthParameterLabel::ReverbMix.into(), value),
+        Some(1) => synth.set_parameter(SynthParameterLabel::DelayMix.into(), value),
+        Some(bus) => {
+            // sends to buses that didn't exist when the instance was prepared are ignored
+            if let (Some(send), SynthParameterValue::ScalarF32(level)) =
+                (aux_sends.get_mut(bus - 2), value)
+            {
+                *send = *level;
+            }
+        }
+    }
+}
+
+/// Only the master reverb and delay sends can be modulated so far,
+/// so the controls reject modulators for sends to the other buses.
+pub(crate) fn is_aux_bus_send_modulator<const BUFSIZE: usize>(
+    par: SynthParameterAddress,
+    val_or_mod: &ValueOrModulator<BUFSIZE>,
+) -> bool {
+    par.label == SynthParameterLabel::AuxSend
+        && par.idx.is_some_and(|bus| bus >= 2)
+        && matches!(val_or_mod, ValueOrModulator::Mod(..))
+}
+
+/// Modulators for the other aux buses are handed back, see `is_aux_bus_send_modulator`.
+pub(crate) fn set_modulator_with_aux_sends<const BUFSIZE: usize, const NCHAN: usize>(
+    synth: &mut (dyn Synth<BUFSIZE, NCHAN> + Send + Sync),
+    par: SynthParameterAddress,
+    init: f32,
+    modulator: Modulator<BUFSIZE>,
+    copies: &mut Vec<Modulator<BUFSIZE>>,
+    leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+) {
+    if par.label != SynthParameterLabel::AuxSend {
+        synth.set_modulator(par, init, modulator, copies, leftover);
+        return;
+    }
+
+    match par.idx {
+        Some(0) | None => synth.set_modulator(
+            SynthParameterLabel::ReverbMix.into(),
+            init,
+            modulator,
+            copies,
+            leftover,
+        ),
+        Some(1) => synth.set_modulator(
+            SynthParameterLabel::DelayMix.into(),
+            init,
+            modulator,
+            copies,
+            leftover,
+        ),
+        Some(_) => leftover(ValueOrModulator::Mod(init, modulator)),
+    }
+}
+
 /// Make your choice, freeverb or convolution ??
+#[derive(Clone)]
 pub enum ReverbMode {
     FreeVerb,
     Convolution(Vec<f32>, f32),
 }
 
+/// Maximum number of additional aux buses (the master reverb and delay not included),
+/// so that no memory has to be allocated in the audio thread when adding buses.
+pub const MAX_AUX_BUSES: usize = 16;
+
+/// Number of instance groups. Instances are in group 0 unless
+/// assigned to a different group.
+pub const NUM_GROUPS: usize = 16;
+
+/// Default capacity of the control queue (see `init_ruffbox_with_queue_capacity`).
+pub const DEFAULT_QUEUE_CAPACITY: usize = 2000;
+
+/// The effects that can be used on additional aux buses.
+pub enum AuxBusType {
+    Reverb(ReverbMode),
+    Delay,
+}
+
+/// Once the voice limit is reached, which voice should make room
+/// for the new one ? The stolen voice is faded out quickly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceStealingPolicy {
+    Oldest,
+    Quietest,
+    SameSynthTypeFirst, // oldest voice of the same synth type, or the oldest voice at all
+}
+
+/// Notifications sent back from the playhead (that is, the audio thread)
+/// to the controls, as the audio thread shouldn't print anything.
+/// The channel is lossy, if nobody fetches the notifications they're
+/// simply dropped once the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayheadNotification {
+    EventLate(usize, usize),               // instance id, samples late
+    InstanceFinished(usize),               // instance id
+    FreezeAfterRecCompleted(usize, usize), // freeze buffer, live buffer
+    SampleLoaded(usize),                   // buffer number
+    AmbiEventDropped(usize),               // instance id (ambisonic module not enabled)
+    EventDropped(usize),                   // instance id (no room left for more instances)
+}
+
+/// Everything the playhead doesn't need anymore is sent back to the controls,
+/// so the memory is freed there and not in the audio thread.
+#[allow(dead_code)] // apart from the instance ids, the contents are never read, just dropped
+pub(crate) enum Garbage<const BUFSIZE: usize, const NCHAN: usize> {
+    Instance(RunningInstance<BUFSIZE, NCHAN>),
+    AmbiInstance(RunningInstance<BUFSIZE, MAX_AMBISONIC_CHANNELS>),
+    Event(ScheduledEvent<BUFSIZE, NCHAN>),
+    SampleBuffer(SampleBuffer),
+    Value(ValueOrModulator<BUFSIZE>),
+    ModulatorCopies(Vec<Modulator<BUFSIZE>>),
+    AuxBus(AuxBus<BUFSIZE, NCHAN>),
+    Binauralizer(Box<Binauralizer<BUFSIZE, MAX_AMBISONIC_CHANNELS>>),
+    TempoClock(TempoClock),
+}
+
 pub(crate) enum ControlMessage<const BUFSIZE: usize, const NCHAN: usize> {
     LoadSample(usize, usize, SampleBuffer), // num, len, samples
-    SetGlobalParamOrModulator(SynthParameterLabel, ValueOrModulator<BUFSIZE>),
+    // timestamp, param, value, copies of the modulator
+    SetGlobalParamOrModulator(
+        f64,
+        SynthParameterLabel,
+        ValueOrModulator<BUFSIZE>,
+        Vec<Modulator<BUFSIZE>>,
+    ),
     ScheduleEvent(ScheduledEvent<BUFSIZE, NCHAN>),
+    // instance id, param, value, copies of the modulator
+    SetInstanceParamOrModulator(
+        usize,
+        SynthParameterAddress,
+        ValueOrModulator<BUFSIZE>,
+        Vec<Modulator<BUFSIZE>>,
+    ),
+    ReleaseInstance(usize), // instance id
+    SetVoiceLimit(Option<usize>, VoiceStealingPolicy),
+    Panic(f64, bool),                       // fade time, flush effects
+    FlushPendingEvents(f64),                // drop pending events at or after timestamp
+    FlushTaggedEvents(usize),               // drop pending events with tag
+    ShiftPendingEvents(Option<usize>, f64), // tag (all if none), offset in seconds
+    RetimePendingEvents(TempoClock),        // recalculate beat-scheduled events
+    AddAuxBus(AuxBus<BUFSIZE, NCHAN>),
+    SetGroupGain(usize, f32),  // group, gain
+    SetGroupMute(usize, bool), // group, mute
+    SetGroupSolo(usize, bool), // group, solo
+    // bus, param, value, copies of the modulator
+    SetAuxBusParamOrModulator(
+        usize,
+        SynthParameterLabel,
+        ValueOrModulator<BUFSIZE>,
+        Vec<Modulator<BUFSIZE>>,
+    ),
     FreezeBuffer(usize, usize),
     FreezeAddBuffer(usize, usize),
     FreezeAfterRec(usize, usize, usize, bool),
@@ -121,10 +339,12 @@ pub(crate) enum ControlMessage<const BUFSIZE: usize, const NCHAN: usize> {
     ClearAllFreezeBuffers,
     ClearAllLiveBuffers,
     ClearAllBuffers, // only live and freeze buffers, not sample buffers
+    SetBinauralFilter(Box<Binauralizer<BUFSIZE, MAX_AMBISONIC_CHANNELS>>),
 }
 
 /// before loading, analyze how many samples you want to load,
 /// and pre-allocate the buffer vector accordingly (later)
+/// For more settings, and validation, see `RuffboxConfig`.
 pub fn init_ruffbox<const BUFSIZE: usize, const NCHAN: usize>(
     live_buffers: usize,
     live_buffer_time: f64,
@@ -136,36 +356,122 @@ pub fn init_ruffbox<const BUFSIZE: usize, const NCHAN: usize>(
 ) -> (
     RuffboxControls<BUFSIZE, NCHAN>,
     RuffboxPlayhead<BUFSIZE, NCHAN>,
+) {
+    init_ruffbox_with_queue_capacity(
+        live_buffers,
+        live_buffer_time,
+        reverb_mode,
+        samplerate,
+        max_buffers,
+        freeze_buffers,
+        ambisonics_binaural,
+        DEFAULT_QUEUE_CAPACITY,
+    )
+}
+
+/// Same as `init_ruffbox`, but with a custom capacity for the control queue,
+/// that is, the number of control messages (events, parameter changes, etc.)
+/// that can be sent before the playhead has to catch up.
+#[allow(clippy::too_many_arguments)]
+pub fn init_ruffbox_with_queue_capacity<const BUFSIZE: usize, const NCHAN: usize>(
+    live_buffers: usize,
+    live_buffer_time: f64,
+    reverb_mode: &ReverbMode,
+    samplerate: f64,
+    max_buffers: usize,
+    freeze_buffers: usize,
+    ambisonics_binaural: bool,
+    queue_capacity: usize,
+) -> (
+    RuffboxControls<BUFSIZE, NCHAN>,
+    RuffboxPlayhead<BUFSIZE, NCHAN>,
+) {
+    let config = RuffboxConfig::new(samplerate)
+        .live_buffers(live_buffers, live_buffer_time)
+        .freeze_buffers(freeze_buffers)
+        .max_buffers(max_buffers)
+        .reverb(reverb_mode.clone())
+        .ambisonics_binaural(ambisonics_binaural)
+        .queue_capacity(queue_capacity);
+
+    // not validated, for backwards compatibility
+    init_ruffbox_from_config(&config)
+}
+
+// create the controls and the playhead, the config needs to be validated already
+pub(crate) fn init_ruffbox_from_config<const BUFSIZE: usize, const NCHAN: usize>(
+    config: &RuffboxConfig,
+) -> (
+    RuffboxControls<BUFSIZE, NCHAN>,
+    RuffboxPlayhead<BUFSIZE, NCHAN>,
 ) {
     let (tx, rx): (
         Sender<ControlMessage<BUFSIZE, NCHAN>>,
         Receiver<ControlMessage<BUFSIZE, NCHAN>>,
+    ) = crossbeam::channel::bounded(config.queue_capacity.max(1));
+
+    let (ntx, nrx): (Sender<PlayheadNotification>, Receiver<PlayheadNotification>) =
+        crossbeam::channel::bounded(2000);
+
+    let (gtx, grx): (
+        Sender<Garbage<BUFSIZE, NCHAN>>,
+        Receiver<Garbage<BUFSIZE, NCHAN>>,
     ) = crossbeam::channel::bounded(2000);
 
     let now = Arc::new(AtomicCell::<f64>::new(0.0));
-
-    let controls = RuffboxControls::<BUFSIZE, NCHAN>::new(
-        samplerate,
-        live_buffers,
-        live_buffer_time,
-        max_buffers,
-        freeze_buffers,
+    let ambisonic_order = Arc::new(AtomicCell::<usize>::new(0)); // 0 means disabled
+    let meters = Arc::new(SharedMeters::<NCHAN>::new());
+
+    let mut controls = RuffboxControls::<BUFSIZE, NCHAN>::new(
+        config.samplerate,
+        config.live_buffers,
+        config.live_buffer_time,
+        config.max_buffers,
+        config.freeze_buffers,
         &now,
+        &ambisonic_order,
+        &meters,
         tx,
+        nrx,
+        grx,
     );
+    controls.set_random_seed(config.random_seed);
+
     let mut playhead = RuffboxPlayhead::<BUFSIZE, NCHAN>::new(
-        live_buffers,
-        live_buffer_time,
-        reverb_mode,
-        samplerate,
-        max_buffers,
-        freeze_buffers,
+        config.live_buffers,
+        config.live_buffer_time,
+        &config.reverb_mode,
+        config.samplerate,
+        config.max_buffers,
+        config.freeze_buffers,
+        config.delay_max_time,
         &now,
+        &ambisonic_order,
+        &meters,
         rx,
+        ntx,
+        gtx,
     );
 
-    if ambisonics_binaural {
-        playhead.enable_ambisonics_binaural();
+    if config.ambisonics_binaural {
+        if let Some(ir) = config.binaural_filter.as_ref() {
+            let ir = resample_filter(
+                ir,
+                config.binaural_filter_samplerate,
+                config.samplerate as f32,
+            );
+            playhead.enable_ambisonics_binaural_with_filter(&ir);
+        } else {
+            playhead.enable_ambisonics_binaural_with_order(config.ambisonic_order);
+        }
+    } else if let Some((layout, weighting)) = config.ambisonics_decoder.as_ref() {
+        playhead.enable_ambisonics_decoder_with_order(config.ambisonic_order, layout, *weighting);
+    }
+
+    playhead.set_voice_limit(config.voice_limit, config.voice_stealing_policy);
+
+    if let Some(layout) = config.vbap_layout.as_ref() {
+        controls.set_vbap_layout(layout);
     }
 
     (controls, playhead)
@@ -244,6 +550,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_freeze_buffer() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<512, 2>(1, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        for _ in 0..1024 {
+            ruff.write_sample_to_live_buffer(0, 0.5);
+        }
+
+        // the first freeze buffer comes right after the live buffer
+        ctrl.freeze_buffer(0, 0);
+        ctrl.freeze_add_buffer(0, 0);
+        ruff.process(0.0, true);
+
+        let SampleBuffer::Mono(buf) = &ruff.buffers[1] else {
+            panic!()
+        };
+        // past the fade-in of the live buffer
+        assert_approx_eq::assert_approx_eq!(buf[300], 1.0, 0.0002);
+        assert_approx_eq::assert_approx_eq!(buf[800], 1.0, 0.0002);
+    }
+
     #[test]
     fn test_load_mono_sample() {
         let (ctrl, mut ruff) =
@@ -270,6 +598,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_load_stereo_sample_resampled() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<512, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        // the right channel is shorter, so it's padded
+        let bnum =
+            ctrl.load_stereo_sample(&mut vec![0.1; 3000], &mut vec![0.2; 2000], true, 48000.0);
+        ruff.process(0.0, true);
+
+        let SampleBuffer::Stereo(left, right) = &ruff.buffers[bnum] else {
+            panic!()
+        };
+        assert_eq!(left.len(), right.len());
+        assert_approx_eq::assert_approx_eq!(left[500], 0.1, 0.01);
+        assert_approx_eq::assert_approx_eq!(right[500], 0.2, 0.01);
+    }
+
+    #[test]
+    fn test_try_load_sample_queue_full() {
+        let (ctrl, mut ruff) = init_ruffbox_with_queue_capacity::<512, 2>(
+            0,
+            2.0,
+            &ReverbMode::FreeVerb,
+            44100.0,
+            3000,
+            10,
+            false,
+            1,
+        );
+
+        let samples = vec![1.0_f32; 500];
+        assert_eq!(ctrl.try_load_mono_sample(&samples, false, 44100.0), Ok(0));
+        assert_eq!(
+            ctrl.try_load_stereo_sample(&samples, &samples, false, 44100.0),
+            Err(RuffboxError::QueueFull)
+        );
+        // the samples are left alone
+        assert_eq!(samples.len(), 500);
+
+        // the buffer number wasn't used up
+        ruff.process(0.0, true);
+        assert_eq!(
+            ctrl.try_load_stereo_sample(&samples, &samples, false, 44100.0),
+            Ok(1)
+        );
+    }
+
     #[test]
     fn test_sine_synth_at_block_start() {
         let (ctrl, mut ruff) =
@@ -1030,12 +1406,1336 @@ mod tests {
             assert_approx_eq::assert_approx_eq!(out_buf[0][i], sample1[i + 2], 0.03);
         }
     }
+
+    #[test]
+    fn test_set_running_instance_parameter() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                0.0,
+                0,
+            )
+            .unwrap();
+
+        inst.set_instance_parameter(
+            SynthParameterLabel::ChannelPosition.into(),
+            &SynthParameterValue::ScalarF32(0.0),
+        );
+
+        let prepared_id = inst.id();
+        let id = ctrl.trigger(inst);
+        assert_eq!(prepared_id, id);
+
+        // everything on the left channel ...
+        let out_1 = ruff.process(0.0, true);
+        assert!(out_1[0].iter().any(|x| *x != 0.0));
+        assert!(out_1[1].iter().all(|x| *x == 0.0));
+
+        // ... pan the running instance to the right channel
+        ctrl.set_instance_parameter(
+            id,
+            SynthParameterLabel::ChannelPosition.into(),
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+
+        let out_2 = ruff.process(0.0, true);
+        for s in 0..128 {
+            assert_approx_eq::assert_approx_eq!(out_2[0][s], 0.0, 0.00001);
+        }
+        assert!(out_2[1].iter().any(|x| *x != 0.0));
+    }
+
+    #[test]
+    fn test_voice_limit_steal_oldest() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        ctrl.set_voice_limit(Some(1), VoiceStealingPolicy::Oldest);
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    0.0,
+                    0,
+                )
+                .unwrap();
+            // hold the voices so they don't end by themselves
+            inst.set_instance_parameter(
+                SynthParameterLabel::EnvelopeGate.into(),
+                &SynthParameterValue::ScalarF32(1.0),
+            );
+            ids.push(ctrl.trigger(inst));
+        }
+
+        // give the stolen voice some time to fade out
+        for _ in 0..5 {
+            ruff.process(0.0, true);
+        }
+
+        assert_eq!(ruff.running_instances.len(), 1);
+        assert_eq!(ruff.running_instances[0].id, ids[1]);
+    }
+
+    #[test]
+    fn test_playhead_notifications() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        let mut sample = vec![0.0, 0.1, 0.2, 0.3];
+        let bnum = ctrl.load_mono_sample(&mut sample, false, 44100.0);
+
+        ruff.process(0.0, true);
+        ruff.process(0.0, true);
+
+        // now is at 256 samples, so this one is 212 samples late
+        let inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                0.001,
+                0,
+            )
+            .unwrap();
+        let id = ctrl.trigger(inst);
+
+        // an ambisonic event, but the ambisonic module isn't enabled
+        let ambi_inst = ctrl
+            .prepare_instance(
+                SynthType::AmbisonicSampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![],
+                }),
+                0.0,
+                bnum,
+            )
+            .unwrap();
+        let ambi_id = ctrl.trigger(ambi_inst);
+
+        ruff.process(0.0, true);
+
+        let notifications: Vec<PlayheadNotification> = ctrl.notifications().collect();
+        assert_eq!(
+            notifications,
+            vec![
+                PlayheadNotification::SampleLoaded(bnum),
+                PlayheadNotification::EventLate(id, 212),
+                PlayheadNotification::AmbiEventDropped(ambi_id),
+            ]
+        );
+
+        // the sine lasts a little more than a second
+        for _ in 0..500 {
+            ruff.process(0.0, true);
+        }
+
+        assert!(ctrl
+            .notifications()
+            .any(|n| n == PlayheadNotification::InstanceFinished(id)));
+    }
+
+    #[test]
+    fn test_render_offline() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        let events = vec![
+            ScoreEvent::new(
+                0.01,
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+            )
+            .with_parameter(
+                SynthParameterLabel::ReverbMix.into(),
+                SynthParameterValue::ScalarF32(0.5),
+            ),
+            // too late, won't be rendered
+            ScoreEvent::new(2.0, SynthType::RissetBell),
+        ];
+
+        let rendering = render_offline(&ctrl, &mut ruff, events, 0.5, 0.5);
+        assert_eq!(rendering.dropped_events, 0);
+
+        let out = rendering.channels;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].len(), 44100);
+        assert_eq!(out[1].len(), 44100);
+
+        // the event starts at the exact sample
+        assert!(out[0][..441].iter().all(|x| *x == 0.0));
+        assert!(out[0][441..500].iter().any(|x| *x != 0.0));
+    }
+
+    #[test]
+    fn test_render_offline_full_queue() {
+        let (ctrl, mut ruff) = init_ruffbox_with_queue_capacity::<128, 2>(
+            0,
+            2.0,
+            &ReverbMode::FreeVerb,
+            44100.0,
+            3000,
+            10,
+            false,
+            2,
+        );
+
+        let sine = || {
+            SynthType::SingleOscillator(SynthDescription {
+                pre_filter_effects: vec![],
+                filters: vec![FilterType::Dummy, FilterType::Dummy],
+                oscillator_types: vec![OscillatorType::Sine],
+            })
+        };
+
+        // more events in the first block than fit into the queue
+        let mut events: Vec<ScoreEvent> = (0..5).map(|_| ScoreEvent::new(0.001, sine())).collect();
+        events.push(ScoreEvent::new(f64::NAN, sine()));
+        // there's no such buffer
+        events.push(
+            ScoreEvent::new(
+                0.0,
+                SynthType::Sampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![],
+                }),
+            )
+            .with_sample_buffer(42),
+        );
+
+        let rendering = render_offline(&ctrl, &mut ruff, events, 0.1, 0.0);
+        assert_eq!(rendering.dropped_events, 2);
+        assert_eq!(rendering.channels[0].len(), 4410);
+
+        // the ones that didn't fit are played in the following blocks
+        let late = ctrl
+            .notifications()
+            .filter(|n| matches!(n, PlayheadNotification::EventLate(..)))
+            .count();
+        assert_eq!(late, 3);
+    }
+
+    #[test]
+    fn test_seeded_noise_is_reproducible() {
+        let render = |seed: u64| {
+            let (ctrl, mut ruff) =
+                init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+            ctrl.set_random_seed(Some(seed));
+
+            let events = vec![ScoreEvent::new(
+                0.0,
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::WhiteNoise],
+                }),
+            )];
+
+            render_offline(&ctrl, &mut ruff, events, 0.1, 0.0).channels
+        };
+
+        let out_1 = render(42);
+        let out_2 = render(42);
+        let out_3 = render(23);
+
+        assert!(out_1[0].iter().any(|x| *x != 0.0));
+        assert_eq!(out_1, out_2);
+        assert_ne!(out_1, out_3);
+    }
+
+    #[test]
+    fn test_aux_bus_send() {
+        let render = |send: f32| {
+            let (ctrl, mut ruff) =
+                init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+            assert_eq!(ctrl.add_aux_bus(&AuxBusType::Delay), Some(2));
+            assert_eq!(
+                ctrl.add_aux_bus(&AuxBusType::Reverb(ReverbMode::FreeVerb)),
+                Some(3)
+            );
+
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    0.0,
+                    0,
+                )
+                .unwrap();
+
+            // no master sends, so only the aux bus makes a difference
+            for bus in 0..2 {
+                inst.set_instance_parameter(
+                    SynthParameterAddress {
+                        label: SynthParameterLabel::AuxSend,
+                        idx: Some(bus),
+                    },
+                    &SynthParameterValue::ScalarF32(0.0),
+                );
+            }
+            inst.set_instance_parameter(
+                SynthParameterAddress {
+                    label: SynthParameterLabel::AuxSend,
+                    idx: Some(2),
+                },
+                &SynthParameterValue::ScalarF32(send),
+            );
+            ctrl.trigger(inst);
+
+            ruff.process(0.0, true)
+        };
+
+        let dry = render(0.0);
+        let wet = render(1.0);
+
+        // the delay passes the signal through right away ...
+        for s in 1..128 {
+            if dry[0][s] != 0.0 {
+                assert_approx_eq::assert_approx_eq!(wet[0][s], dry[0][s] * 2.0, 0.00001);
+            }
+        }
+    }
+
+    #[test]
+    fn test_aux_bus_rejections() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+        assert_eq!(ctrl.add_aux_bus(&AuxBusType::Delay), Some(2));
+
+        // the master reverb and delay aren't aux buses here, and bus 3 doesn't exist
+        let delay_time = SynthParameterValue::ScalarF32(0.1);
+        for bus in [0, 1, 3] {
+            assert_eq!(
+                ctrl.try_set_aux_bus_parameter(
+                    bus,
+                    SynthParameterLabel::DelayTime,
+                    delay_time.clone()
+                ),
+                Err(RuffboxError::InvalidParameter("aux bus"))
+            );
+        }
+        assert!(ctrl
+            .try_set_aux_bus_parameter(2, SynthParameterLabel::DelayTime, delay_time)
+            .is_ok());
+
+        let send = |bus| SynthParameterAddress {
+            label: SynthParameterLabel::AuxSend,
+            idx: Some(bus),
+        };
+        let lfo = SynthParameterValue::Lfo(
+            0.5,
+            Box::new(SynthParameterValue::ScalarF32(1.0)),
+            0.0,
+            Box::new(SynthParameterValue::ScalarF32(0.5)),
+            0.5,
+            ValOp::Replace,
+        );
+
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                0.0,
+                0,
+            )
+            .unwrap();
+
+        // only the sends to the master reverb and delay can be modulated
+        assert!(inst.try_set_instance_parameter(send(0), &lfo).is_ok());
+        assert_eq!(
+            inst.try_set_instance_parameter(send(2), &lfo),
+            Err(RuffboxError::InvalidParameter("aux bus send modulator"))
+        );
+        let id = ctrl.trigger(inst);
+        ruff.process(0.0, true);
+
+        assert!(ctrl.try_set_instance_parameter(id, send(1), &lfo).is_ok());
+        assert_eq!(
+            ctrl.try_set_instance_parameter(id, send(2), &lfo),
+            Err(RuffboxError::InvalidParameter("aux bus send modulator"))
+        );
+        assert!(ctrl
+            .try_set_instance_parameter(id, send(2), &SynthParameterValue::ScalarF32(0.5))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_max_aux_buses() {
+        let (ctrl, _ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        for i in 0..MAX_AUX_BUSES {
+            assert_eq!(ctrl.add_aux_bus(&AuxBusType::Delay), Some(i + 2));
+        }
+        assert_eq!(ctrl.add_aux_bus(&AuxBusType::Delay), None);
+    }
+
+    #[test]
+    fn test_instance_groups() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        // one sine on the left in group 1, one on the right in group 2
+        for (group, pos) in [(1, 0.0), (2, 1.0)] {
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    0.0,
+                    0,
+                )
+                .unwrap();
+            inst.set_group(group);
+            inst.set_instance_parameter(
+                SynthParameterLabel::ChannelPosition.into(),
+                &SynthParameterValue::ScalarF32(pos),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::EnvelopeGate.into(),
+                &SynthParameterValue::ScalarF32(1.0),
+            );
+            for bus in 0..2 {
+                inst.set_instance_parameter(
+                    SynthParameterAddress {
+                        label: SynthParameterLabel::AuxSend,
+                        idx: Some(bus),
+                    },
+                    &SynthParameterValue::ScalarF32(0.0),
+                );
+            }
+            ctrl.trigger(inst);
+        }
+
+        let out = ruff.process(0.0, true);
+        assert!(out[0].iter().any(|x| *x != 0.0));
+        assert!(out[1].iter().any(|x| *x != 0.0));
+
+        // solo the left group, the right one fades out
+        ctrl.set_group_solo(1, true);
+        for _ in 0..20 {
+            ruff.process(0.0, true);
+        }
+        let out = ruff.process(0.0, true);
+        assert!(out[0].iter().any(|x| *x != 0.0));
+        assert!(out[1].iter().all(|x| *x == 0.0));
+
+        // mute the soloed group, now everything is silent
+        ctrl.set_group_mute(1, true);
+        for _ in 0..20 {
+            ruff.process(0.0, true);
+        }
+        let out = ruff.process(0.0, true);
+        assert!(out[0].iter().all(|x| *x == 0.0));
+
+        // unmute and halve the gain
+        ctrl.set_group_mute(1, false);
+        ctrl.set_group_gain(1, 0.5);
+        for _ in 0..20 {
+            ruff.process(0.0, true);
+        }
+        let out = ruff.process(0.0, true);
+        let peak = out[0].iter().fold(0.0_f32, |acc, x| acc.max(x.abs()));
+        assert!(peak > 0.0 && peak <= 0.5);
+    }
+
+    #[test]
+    fn test_master_limiter() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        ctrl.set_master_parameter(
+            SynthParameterLabel::LimiterActive,
+            SynthParameterValue::ScalarF32(1.0),
+        );
+        ctrl.set_master_parameter(
+            SynthParameterLabel::LimiterCeiling,
+            SynthParameterValue::ScalarF32(-6.0),
+        );
+
+        // a couple of sines stacked up, way too loud ...
+        for freq in [220.0, 330.0, 440.0, 550.0, 660.0, 770.0] {
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    0.0,
+                    0,
+                )
+                .unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::PitchFrequency.into(),
+                &SynthParameterValue::ScalarF32(freq),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::EnvelopeGate.into(),
+                &SynthParameterValue::ScalarF32(1.0),
+            );
+            ctrl.trigger(inst);
+        }
+
+        let ceiling = f32::powf(10.0, -6.0 / 20.0);
+        let mut peak: f32 = 0.0;
+        for _ in 0..50 {
+            let out = ruff.process(0.0, true);
+            for c in 0..2 {
+                peak = out[c].iter().fold(peak, |acc, x| acc.max(x.abs()));
+            }
+        }
+        assert!(peak > 0.0 && peak <= ceiling);
+    }
+
+    #[test]
+    fn test_meters() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                0.0,
+                0,
+            )
+            .unwrap();
+        inst.set_instance_parameter(
+            SynthParameterLabel::EnvelopeGate.into(),
+            &SynthParameterValue::ScalarF32(1.0),
+        );
+        inst.set_instance_parameter(
+            SynthParameterLabel::ReverbMix.into(),
+            &SynthParameterValue::ScalarF32(0.3),
+        );
+        ctrl.trigger(inst);
+
+        for _ in 0..10 {
+            let out = ruff.process(0.0, true);
+            let meter = ctrl.meter(MeterPoint::Master);
+            for c in 0..2 {
+                let peak = out[c].iter().fold(0.0_f32, |acc, x| acc.max(x.abs()));
+                assert_eq!(meter[c].peak, peak);
+                assert!(meter[c].true_peak >= meter[c].peak);
+                assert_eq!(meter[c].clips, 0);
+            }
+        }
+
+        assert!(ctrl.meter(MeterPoint::Master)[0].rms > 0.0);
+        assert!(ctrl.meter(MeterPoint::Reverb)[0].rms > 0.0);
+        assert_eq!(ctrl.meter(MeterPoint::Delay)[0].peak, 0.0);
+        assert_eq!(ctrl.gain_reduction(), 0.0);
+    }
+
+    #[test]
+    fn test_prepare_instance_at_beat() {
+        let (ctrl, _ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        ctrl.start_clock(1.0);
+        ctrl.set_tempo(0.0, 60.0, false);
+        ctrl.set_tempo(4.0, 120.0, false);
+
+        let inst = ctrl
+            .prepare_instance_at_beat(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                6.0,
+                0,
+            )
+            .unwrap();
+
+        // 4 beats at 60 bpm, 2 beats at 120 bpm
+        assert_approx_eq::assert_approx_eq!(inst.timestamp(), 6.0, 0.000001);
+        assert_approx_eq::assert_approx_eq!(ctrl.get_beat(), -1.0, 0.000001);
+
+        assert!(ctrl
+            .prepare_instance_at_beat(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                f64::NAN,
+                0,
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_tempo_change_moves_pending_events() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        ctrl.set_tempo(0.0, 120.0, false);
+
+        // at 120 bpm, beat 4 is at two seconds
+        let inst = ctrl
+            .prepare_instance_at_beat(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                4.0,
+                0,
+            )
+            .unwrap();
+        ctrl.trigger(inst);
+        ruff.process(0.0, true);
+
+        // twice as fast, so the event is at one second now
+        ctrl.set_tempo(0.0, 240.0, false);
+        // ignored
+        ctrl.set_tempo(2.0, f64::NAN, false);
+        ctrl.start_clock(f64::INFINITY);
+        assert!(ctrl.try_set_tempo(2.0, -10.0, false).is_err());
+        assert!(ctrl.try_start_clock(f64::NAN).is_err());
+
+        while ctrl.get_now() < 0.99 {
+            ruff.process(0.0, true);
+        }
+        assert!(ruff.running_instances.is_empty());
+
+        while ctrl.get_now() < 1.01 {
+            ruff.process(0.0, true);
+        }
+        assert_eq!(ruff.running_instances.len(), 1);
+    }
+
+    #[test]
+    fn test_flush_and_shift_pending_events() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        for (timestamp, tag) in [(0.1, Some(1)), (0.2, Some(2)), (0.3, None)] {
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    timestamp,
+                    0,
+                )
+                .unwrap();
+            if let Some(tag) = tag {
+                inst.set_tag(tag);
+            }
+            ctrl.trigger(inst);
+        }
+
+        ctrl.flush_tagged_events(1);
+        ctrl.flush_pending_events(0.25);
+        // moves the second event to 0.3
+        ctrl.shift_tagged_events(2, 0.1);
+
+        // non-finite values leave the pending events alone
+        ctrl.flush_pending_events(f64::NAN);
+        ctrl.shift_pending_events(f64::INFINITY);
+        assert!(ctrl.try_flush_pending_events(f64::NEG_INFINITY).is_err());
+        assert!(ctrl.try_shift_pending_events(f64::NAN).is_err());
+        assert!(ctrl.try_shift_tagged_events(2, f64::NAN).is_err());
+
+        while ctrl.get_now() < 0.29 {
+            ruff.process(0.0, true);
+        }
+        assert!(ruff.running_instances.is_empty());
+
+        while ctrl.get_now() < 0.32 {
+            ruff.process(0.0, true);
+        }
+        assert_eq!(ruff.running_instances.len(), 1);
+    }
+
+    #[test]
+    fn test_shift_pending_events_into_the_past() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        let inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                0.1,
+                0,
+            )
+            .unwrap();
+        let id = ctrl.trigger(inst);
+
+        ruff.process(0.0, true);
+        ruff.process(0.0, true);
+        assert!(ruff.running_instances.is_empty());
+
+        // now is at 256 samples, the event is moved to 0.0
+        ctrl.shift_pending_events(-0.1);
+        ruff.process(0.0, true);
+
+        assert_eq!(ruff.running_instances.len(), 1);
+        let notifications: Vec<PlayheadNotification> = ctrl.notifications().collect();
+        assert_eq!(
+            notifications,
+            vec![PlayheadNotification::EventLate(id, 256)]
+        );
+    }
+
+    #[test]
+    fn test_master_parameter_at_sample_offset() {
+        let mut outputs = Vec::new();
+
+        // no change, a change at sample 50, and a change that's
+        // undone by a second one with the same timestamp
+        for changes in [vec![], vec![0.9], vec![0.9, 0.0]] {
+            let (ctrl, mut ruff) =
+                init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+            ctrl.set_master_parameter(
+                SynthParameterLabel::DelayTime,
+                SynthParameterValue::ScalarF32(0.001),
+            );
+            ctrl.set_master_parameter(
+                SynthParameterLabel::DelayFeedback,
+                SynthParameterValue::ScalarF32(0.0),
+            );
+
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    0.0,
+                    0,
+                )
+                .unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::DelayMix.into(),
+                &SynthParameterValue::ScalarF32(1.0),
+            );
+            ctrl.trigger(inst);
+
+            // fill the delay line
+            for _ in 0..4 {
+                ruff.process(0.0, true);
+            }
+
+            let timestamp = ctrl.get_now() + 50.0 / 44100.0;
+            for feedback in changes {
+                ctrl.set_master_parameter_at(
+                    SynthParameterLabel::DelayFeedback,
+                    SynthParameterValue::ScalarF32(feedback),
+                    timestamp,
+                );
+            }
+            // ignored
+            ctrl.set_master_parameter_at(
+                SynthParameterLabel::DelayFeedback,
+                SynthParameterValue::ScalarF32(0.5),
+                f64::NAN,
+            );
+
+            outputs.push(ruff.process(0.0, true));
+        }
+
+        let first_difference = (0..128).find(|s| outputs[0][0][*s] != outputs[1][0][*s]);
+        assert_eq!(first_difference, Some(50));
+        assert_eq!(outputs[0], outputs[2]);
+    }
+
+    #[test]
+    fn test_panic() {
+        let (ctrl, mut ruff) =
+            init_ruffbox::<128, 2>(0, 2.0, &ReverbMode::FreeVerb, 44100.0, 3000, 10, false);
+
+        for timestamp in [0.0, 1.0] {
+            let mut inst = ctrl
+                .prepare_instance(
+                    SynthType::SingleOscillator(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![OscillatorType::Sine],
+                    }),
+                    timestamp,
+                    0,
+                )
+                .unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::EnvelopeGate.into(),
+                &SynthParameterValue::ScalarF32(1.0),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::ReverbMix.into(),
+                &SynthParameterValue::ScalarF32(0.5),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::DelayMix.into(),
+                &SynthParameterValue::ScalarF32(0.5),
+            );
+            ctrl.trigger(inst);
+        }
+
+        for _ in 0..100 {
+            ruff.process(0.0, true);
+        }
+        assert_eq!(ruff.running_instances.len(), 1);
+
+        // 10ms fade
+        ctrl.panic(0.01, true);
+        for _ in 0..4 {
+            ruff.process(0.0, true);
+        }
+
+        // the running instance is gone, and the pending one won't start
+        while ctrl.get_now() < 1.1 {
+            let out = ruff.process(0.0, true);
+            assert!(ruff.running_instances.is_empty());
+            assert!(out[0].iter().all(|x| *x == 0.0));
+            assert!(out[1].iter().all(|x| *x == 0.0));
+        }
+    }
+
+    #[test]
+    fn test_fallible_controls() {
+        // one live buffer, one freeze buffer, room for one more sample
+        let (ctrl, mut ruff) = init_ruffbox_with_queue_capacity::<128, 2>(
+            1,
+            2.0,
+            &ReverbMode::FreeVerb,
+            44100.0,
+            3,
+            1,
+            false,
+            4,
+        );
+
+        let bnum = ctrl
+            .try_load_mono_sample(&[0.0, 0.1, 0.2, 0.3], false, 44100.0)
+            .unwrap();
+        assert_eq!(bnum, 2);
+        assert_eq!(
+            ctrl.try_load_mono_sample(&[0.0; 4], false, 44100.0),
+            Err(RuffboxError::MaxBuffersExhausted)
+        );
+
+        let sampler = || {
+            SynthType::Sampler(SynthDescription {
+                pre_filter_effects: vec![],
+                filters: vec![FilterType::Dummy, FilterType::Dummy],
+                oscillator_types: vec![],
+            })
+        };
+        let ambi_sampler = SynthType::AmbisonicSampler(SynthDescription {
+            pre_filter_effects: vec![],
+            filters: vec![FilterType::Dummy, FilterType::Dummy],
+            oscillator_types: vec![],
+        });
+
+        assert_eq!(
+            ctrl.try_prepare_instance(sampler(), 0.0, 3).err(),
+            Some(RuffboxError::BufferIndexOutOfRange(3))
+        );
+        assert_eq!(
+            ctrl.try_prepare_instance(ambi_sampler, 0.0, bnum).err(),
+            Some(RuffboxError::AmbisonicsDisabled)
+        );
+        assert_eq!(
+            ctrl.try_freeze_buffer(1, 0),
+            Err(RuffboxError::BufferIndexOutOfRange(1))
+        );
+        assert_eq!(
+            ctrl.try_freeze_after_rec(0, 0, -1.0, false),
+            Err(RuffboxError::InvalidParameter("freeze time"))
+        );
+
+        // the sample is still in the queue, so there's room for three more messages
+        for _ in 0..3 {
+            let inst = ctrl.try_prepare_instance(sampler(), 0.0, bnum).unwrap();
+            assert!(ctrl.try_trigger(inst).is_ok());
+        }
+        let inst = ctrl.try_prepare_instance(sampler(), 0.0, bnum).unwrap();
+        assert_eq!(ctrl.try_trigger(inst), Err(RuffboxError::QueueFull));
+
+        // once the playhead has caught up, there's room again
+        ruff.process(0.0, true);
+        assert!(ctrl.try_freeze_buffer(0, 0).is_ok());
+
+        drop(ruff);
+        assert_eq!(ctrl.try_release(0), Err(RuffboxError::Disconnected));
+    }
+
+    #[test]
+    fn test_ambisonic_decoder() {
+        // eight speakers in a ring, the first one in front
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .ambisonics_decoder(
+                ring,
+                crate::building_blocks::ambisonics::DecoderWeighting::MaxRe,
+            )
+            .build::<128, 8>()
+            .unwrap();
+
+        let bnum = ctrl.load_mono_sample(&mut vec![0.5; 44100], false, 44100.0);
+
+        let mut inst = ctrl
+            .try_prepare_instance(
+                SynthType::AmbisonicSampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![],
+                }),
+                0.0,
+                bnum,
+            )
+            .unwrap();
+        // towards the third speaker
+        inst.set_instance_parameter(
+            SynthParameterLabel::AmbisonicAzimuth.into(),
+            &SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+        );
+        ctrl.trigger(inst);
+
+        let mut energy = [0.0; 8];
+        for _ in 0..20 {
+            let out = ruff.process(0.0, true);
+            for c in 0..8 {
+                energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+            }
+        }
+
+        for c in 0..8 {
+            assert!(energy[c] <= energy[2]);
+        }
+        assert!(energy[2] > 0.0);
+        assert!(energy[6] < energy[2] * 0.1);
+    }
+
+    #[test]
+    fn test_ambisonic_reverb() {
+        let mut tails = Vec::new();
+        for reverb in [0.0, 1.0] {
+            let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+                .ambisonics_binaural(true)
+                .ambisonic_order(2)
+                .build::<128, 2>()
+                .unwrap();
+
+            let bnum = ctrl.load_mono_sample(&mut vec![0.5; 256], false, 44100.0);
+            let mut inst = ctrl
+                .try_prepare_instance(
+                    SynthType::AmbisonicSampler(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![],
+                    }),
+                    0.0,
+                    bnum,
+                )
+                .unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::ReverbMix.into(),
+                &SynthParameterValue::ScalarF32(reverb),
+            );
+            ctrl.trigger(inst);
+
+            let mut tail = 0.0;
+            for i in 0..40 {
+                let out = ruff.process(0.0, true);
+                if i >= 10 {
+                    tail += out[0].iter().map(|x| x * x).sum::<f32>();
+                }
+            }
+            tails.push(tail);
+        }
+
+        // the sample is long over, only the ambisonic reverb is left
+        assert_eq!(tails[0], 0.0);
+        assert!(tails[1] > 0.0);
+    }
+
+    #[test]
+    fn test_higher_order_ambisonics() {
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+
+        let mut energies = Vec::new();
+        for order in [1, 3] {
+            let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+                .ambisonic_order(order)
+                .ambisonics_decoder(
+                    ring.clone(),
+                    crate::building_blocks::ambisonics::DecoderWeighting::Basic,
+                )
+                .build::<128, 8>()
+                .unwrap();
+            assert_eq!(ctrl.ambisonic_order(), order);
+
+            let bnum = ctrl.load_mono_sample(&mut vec![0.5; 44100], false, 44100.0);
+            let mut inst = ctrl
+                .try_prepare_instance(
+                    SynthType::AmbisonicSampler(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![],
+                    }),
+                    0.0,
+                    bnum,
+                )
+                .unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::AmbisonicAzimuth.into(),
+                &SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+            );
+            ctrl.trigger(inst);
+
+            let mut energy = [0.0; 8];
+            for _ in 0..20 {
+                let out = ruff.process(0.0, true);
+                for c in 0..8 {
+                    energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+                }
+            }
+            energies.push(energy);
+        }
+
+        // third order is more focused on the speaker in the source direction
+        let focus = |e: &[f32; 8]| e[2] / e.iter().sum::<f32>();
+        assert!(focus(&energies[1]) > 1.5 * focus(&energies[0]));
+    }
+
+    #[test]
+    fn test_ambisonic_rotation() {
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .ambisonic_order(3)
+            .ambisonics_decoder(
+                ring,
+                crate::building_blocks::ambisonics::DecoderWeighting::MaxRe,
+            )
+            .build::<128, 8>()
+            .unwrap();
+
+        // turn the sound field by a quarter
+        ctrl.set_master_parameter(
+            SynthParameterLabel::AmbisonicYaw,
+            SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+        );
+
+        let bnum = ctrl.load_mono_sample(&mut vec![0.5; 44100], false, 44100.0);
+        let mut inst = ctrl
+            .try_prepare_instance(
+                SynthType::AmbisonicSampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![],
+                }),
+                0.0,
+                bnum,
+            )
+            .unwrap();
+        inst.set_instance_parameter(
+            SynthParameterLabel::AmbisonicAzimuth.into(),
+            &SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+        );
+        ctrl.trigger(inst);
+
+        let mut energy = [0.0; 8];
+        for i in 0..40 {
+            let out = ruff.process(0.0, true);
+            // once the rotation has settled
+            if i >= 20 {
+                for c in 0..8 {
+                    energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+                }
+            }
+        }
+
+        // the source has moved from the third to the fifth speaker
+        for c in 0..8 {
+            assert!(energy[c] <= energy[4]);
+        }
+        assert!(energy[2] < 0.1 * energy[4]);
+    }
+
+    #[test]
+    fn test_ambisonic_oscillator_synths() {
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .ambisonic_order(2)
+            .ambisonics_decoder(
+                ring,
+                crate::building_blocks::ambisonics::DecoderWeighting::MaxRe,
+            )
+            .build::<128, 8>()
+            .unwrap();
+
+        let desc = || SynthDescription {
+            pre_filter_effects: vec![],
+            filters: vec![FilterType::Dummy, FilterType::Dummy],
+            oscillator_types: vec![OscillatorType::Sine],
+        };
+
+        for synth_type in [
+            SynthType::AmbisonicSingleOscillator(desc()),
+            SynthType::AmbisonicMultiOscillator(desc()),
+            SynthType::AmbisonicKarPlusPlus(desc()),
+            SynthType::AmbisonicRissetBell,
+        ] {
+            let mut inst = ctrl.try_prepare_instance(synth_type, 0.0, 0).unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::PitchFrequency.into(),
+                &SynthParameterValue::ScalarF32(220.0),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::AmbisonicAzimuth.into(),
+                &SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+            );
+            ctrl.trigger(inst);
+
+            let mut energy = [0.0; 8];
+            for _ in 0..10 {
+                let out = ruff.process(0.0, true);
+                for c in 0..8 {
+                    energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+                }
+            }
+
+            // the speaker in the direction of the source is the loudest
+            assert!(energy[2] > 0.0);
+            for c in 0..8 {
+                assert!(energy[c] <= energy[2]);
+            }
+
+            ctrl.panic(0.0, true);
+            for _ in 0..20 {
+                ruff.process(0.0, true);
+            }
+        }
+
+        // not without the ambisonic module
+        let (ctrl, _) = RuffboxConfig::new(44100.0).build::<128, 2>().unwrap();
+        assert!(matches!(
+            ctrl.try_prepare_instance(SynthType::AmbisonicRissetBell, 0.0, 0),
+            Err(RuffboxError::AmbisonicsDisabled)
+        ));
+    }
+
+    #[test]
+    fn test_vbap_synths() {
+        // an irregular layout, left, right, center, left surround, right surround
+        let layout = vec![
+            (-PI / 6.0, 0.0),
+            (PI / 6.0, 0.0),
+            (0.0, 0.0),
+            (-110.0f32.to_radians(), 0.0),
+            (110.0f32.to_radians(), 0.0),
+        ];
+
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .vbap_layout(layout)
+            .build::<128, 5>()
+            .unwrap();
+
+        let desc = || SynthDescription {
+            pre_filter_effects: vec![],
+            filters: vec![FilterType::Dummy, FilterType::Dummy],
+            oscillator_types: vec![OscillatorType::Sine],
+        };
+
+        for synth_type in [
+            SynthType::VbapSingleOscillator(desc()),
+            SynthType::VbapMultiOscillator(desc()),
+            SynthType::VbapKarPlusPlus(desc()),
+            SynthType::VbapRissetBell,
+        ] {
+            let mut inst = ctrl.try_prepare_instance(synth_type, 0.0, 0).unwrap();
+            inst.set_instance_parameter(
+                SynthParameterLabel::PitchFrequency.into(),
+                &SynthParameterValue::ScalarF32(220.0),
+            );
+            inst.set_instance_parameter(
+                SynthParameterLabel::AmbisonicAzimuth.into(),
+                &SynthParameterValue::ScalarF32(110.0f32.to_radians()),
+            );
+            ctrl.trigger(inst);
+
+            let mut energy = [0.0; 5];
+            for _ in 0..10 {
+                let out = ruff.process(0.0, true);
+                for c in 0..5 {
+                    energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+                }
+            }
+
+            // only the speaker in the direction of the source plays
+            assert!(energy[4] > 0.0);
+            for c in 0..4 {
+                assert!(energy[c] < energy[4] * 0.0001);
+            }
+
+            ctrl.panic(0.0, true);
+            for _ in 0..20 {
+                ruff.process(0.0, true);
+            }
+        }
+
+        // not without a speaker layout
+        let (ctrl, _) = RuffboxConfig::new(44100.0).build::<128, 2>().unwrap();
+        assert!(matches!(
+            ctrl.try_prepare_instance(SynthType::VbapRissetBell, 0.0, 0),
+            Err(RuffboxError::VbapDisabled)
+        ));
+    }
+
+    #[test]
+    fn test_set_binaural_filter() {
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .build::<128, 2>()
+            .unwrap();
+
+        // only the omni channel, only to the left ear
+        let mut ir = vec![(vec![0.0; 64], vec![0.0; 64]); 4];
+        ir[0].0[0] = 1.0;
+        assert_eq!(
+            ctrl.try_set_binaural_filter(&ir[..3], 44100.0),
+            Err(RuffboxError::InvalidParameter("binaural filter"))
+        );
+        ctrl.try_set_binaural_filter(&ir, 44100.0).unwrap();
+
+        let bnum = ctrl.load_mono_sample(&mut vec![0.5; 44100], false, 44100.0);
+        let inst = ctrl
+            .try_prepare_instance(
+                SynthType::AmbisonicSampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![],
+                }),
+                0.0,
+                bnum,
+            )
+            .unwrap();
+        ctrl.trigger(inst);
+
+        let mut energy = [0.0; 2];
+        for _ in 0..10 {
+            let out = ruff.process(0.0, true);
+            for c in 0..2 {
+                energy[c] += out[c].iter().map(|x| x * x).sum::<f32>();
+            }
+        }
+        assert!(energy[0] > 0.0);
+        assert_eq!(energy[1], 0.0);
+    }
+
+    #[test]
+    fn test_config_binaural_filter_samplerate() {
+        // a short decay on the omni channel, at a different samplerate than the engine's
+        let mut ir = vec![(vec![0.0; 256], vec![0.0; 256]); 4];
+        for (i, s) in ir[0].0.iter_mut().enumerate() {
+            *s = 0.9_f32.powi(i as i32);
+        }
+        ir[0].1 = ir[0].0.clone();
+
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .binaural_filter(ir.clone(), f32::NAN)
+            .validate()
+            .is_err());
+
+        let render = |ir: Vec<(Vec<f32>, Vec<f32>)>, samplerate: f32| {
+            let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+                .ambisonics_binaural(true)
+                .binaural_filter(ir, samplerate)
+                .build::<128, 2>()
+                .unwrap();
+            let bnum = ctrl.load_mono_sample(&mut vec![0.5; 4410], false, 44100.0);
+            let mut inst = ctrl
+                .try_prepare_instance(
+                    SynthType::AmbisonicSampler(SynthDescription {
+                        pre_filter_effects: vec![],
+                        filters: vec![FilterType::Dummy, FilterType::Dummy],
+                        oscillator_types: vec![],
+                    }),
+                    0.0,
+                    bnum,
+                )
+                .unwrap();
+            // in front
+            inst.set_instance_parameter(
+                SynthParameterLabel::AmbisonicAzimuth.into(),
+                &SynthParameterValue::ScalarF32(0.0),
+            );
+            ctrl.trigger(inst);
+            (0..10).map(|_| ruff.process(0.0, true)).collect::<Vec<_>>()
+        };
+
+        // resampled when building, same as resampling it beforehand
+        let resampled = resample_filter(&ir, 48000.0, 44100.0);
+        let out_1 = render(ir, 48000.0);
+        let out_2 = render(resampled, 44100.0);
+        assert!(out_1[5][0].iter().any(|x| *x != 0.0));
+        assert_eq!(out_1, out_2);
+    }
 }
 
 #[cfg(test)]
 mod memory_tests {
     use super::*;
-    use crate::building_blocks::{EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, ValOp};
+    use crate::building_blocks::ambisonics::DecoderWeighting;
+    use crate::building_blocks::bitcrusher::BitcrusherMode;
+    use crate::building_blocks::{
+        EffectType, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, OscillatorType, ValOp,
+    };
     use crate::synths::{SynthDescription, SynthType};
     use assert_no_alloc::*;
 
@@ -1185,4 +2885,289 @@ mod memory_tests {
             }
         });
     }
+
+    fn short_sine<const BUFSIZE: usize, const NCHAN: usize>(
+        ctrl: &RuffboxControls<BUFSIZE, NCHAN>,
+        timestamp: f64,
+    ) -> PreparedInstance<BUFSIZE, NCHAN> {
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                timestamp,
+                0,
+            )
+            .unwrap();
+        inst.set_instance_parameter(
+            SynthParameterLabel::Envelope.into(),
+            &SynthParameterValue::MultiPointEnvelope(
+                vec![
+                    EnvelopeSegmentInfo {
+                        from: 0.0,
+                        to: 0.5,
+                        time: 0.002,
+                        segment_type: EnvelopeSegmentType::Lin,
+                    },
+                    EnvelopeSegmentInfo {
+                        from: 0.5,
+                        to: 0.0,
+                        time: 0.01,
+                        segment_type: EnvelopeSegmentType::Lin,
+                    },
+                ],
+                false,
+                ValOp::Replace,
+            ),
+        );
+        inst
+    }
+
+    #[test]
+    fn test_no_alloc_under_load() {
+        // ambisonics decoded to a square of speakers, as the binaural
+        // decoder uses an fft that allocates its output buffers
+        let square = (0..4)
+            .map(|i| ((i as f32 + 0.5) * std::f32::consts::PI / 2.0, 0.0))
+            .collect();
+        let (ctrl, mut ruff) = RuffboxConfig::new(44100.0)
+            .live_buffers(1, 2.0)
+            .freeze_buffers(10)
+            .max_buffers(3000)
+            .ambisonics_decoder(square, DecoderWeighting::MaxRe)
+            .build::<128, 4>()
+            .unwrap();
+
+        ctrl.set_voice_limit(Some(64), VoiceStealingPolicy::Oldest);
+
+        for round in 0..10 {
+            let now = ctrl.get_now();
+
+            // lots of short events, some right away, some pending,
+            // a few of them tagged so they can be flushed
+            for i in 0..100 {
+                let mut inst = short_sine(&ctrl, now + (i % 10) as f64 * 0.003);
+                if i % 7 == 0 {
+                    inst.set_tag(round);
+                }
+                let id = ctrl.trigger(inst);
+                if i % 13 == 0 {
+                    ctrl.set_instance_parameter(
+                        id,
+                        SynthParameterLabel::PitchFrequency.into(),
+                        &SynthParameterValue::ScalarF32(330.0),
+                    );
+                }
+            }
+            ctrl.flush_tagged_events(round);
+
+            // master parameters, immediate and timestamped
+            ctrl.set_master_parameter(
+                SynthParameterLabel::ReverbRoomsize,
+                SynthParameterValue::ScalarF32(0.5),
+            );
+            ctrl.set_master_parameter_at(
+                SynthParameterLabel::DelayFeedback,
+                SynthParameterValue::ScalarF32(0.3),
+                now + 0.01,
+            );
+
+            // replaces a buffer
+            let mut sample = vec![0.0, 0.1, 0.2, 0.3, 0.2, 0.1, 0.0];
+            ctrl.load_mono_sample(&mut sample, false, 44100.0);
+
+            // parameter for an instance that's long gone
+            ctrl.set_instance_parameter(
+                0,
+                SynthParameterLabel::PitchFrequency.into(),
+                &SynthParameterValue::ScalarF32(330.0),
+            );
+
+            if round == 9 {
+                ctrl.panic(0.005, true);
+            }
+
+            assert_no_alloc(|| {
+                // long enough for all instances to finish
+                for _ in 0..20 {
+                    let _ = ruff.process(0.0, true);
+                }
+            });
+
+            // free everything on this side
+            ctrl.collect_garbage();
+        }
+
+        assert!(ruff.running_instances.is_empty());
+
+        // instances to change while they're running: one with two effects that both
+        // need a modulator, in a group and sending to an aux bus, a stereo sampler
+        // and an ambisonic one, so the modulators need copies in all kinds of places
+        let bus = ctrl.add_aux_bus(&AuxBusType::Delay).unwrap();
+        let now = ctrl.get_now();
+
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::SingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![
+                        EffectType::Bitcrusher(BitcrusherMode::Cast),
+                        EffectType::Bitcrusher(BitcrusherMode::Floor),
+                    ],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                now,
+                0,
+            )
+            .unwrap();
+        inst.set_instance_parameter(SynthParameterLabel::Envelope.into(), &sustain(0.5));
+        inst.set_instance_parameter(
+            SynthParameterLabel::PitchFrequency.into(),
+            &lfo(3.0, 440.0, 20.0),
+        );
+        inst.set_instance_parameter(
+            SynthParameterLabel::AuxSend.with_index(bus),
+            &SynthParameterValue::ScalarF32(0.5),
+        );
+        inst.set_group(3);
+        let osc_id = ctrl.trigger(inst);
+
+        let mut left = vec![0.1; 44100];
+        let mut right = vec![0.2; 44100];
+        let stereo_buf = ctrl.load_stereo_sample(&mut left, &mut right, false, 44100.0);
+        let mut inst = ctrl
+            .prepare_instance(
+                SynthType::Sampler(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::BiquadHpf12dB, FilterType::BiquadLpf12dB],
+                    oscillator_types: vec![],
+                }),
+                now,
+                stereo_buf,
+            )
+            .unwrap();
+        inst.set_instance_parameter(SynthParameterLabel::Envelope.into(), &sustain(0.5));
+        let sampler_id = ctrl.trigger(inst);
+
+        let mut inst = ctrl
+            .try_prepare_instance(
+                SynthType::AmbisonicSingleOscillator(SynthDescription {
+                    pre_filter_effects: vec![],
+                    filters: vec![FilterType::Dummy, FilterType::Dummy],
+                    oscillator_types: vec![OscillatorType::Sine],
+                }),
+                now,
+                0,
+            )
+            .unwrap();
+        inst.set_instance_parameter(SynthParameterLabel::Envelope.into(), &sustain(0.5));
+        let ambi_id = ctrl.trigger(inst);
+
+        let _ = ruff.process(0.0, true);
+        ctrl.collect_garbage();
+
+        let is_running = |ruff: &RuffboxPlayhead<128, 4>, id| {
+            ruff.running_instances.iter().any(|i| i.id == id)
+                || ruff
+                    .ambisonic_binaural
+                    .as_ref()
+                    .is_some_and(|m| m.running_instances.iter().any(|i| i.id == id))
+        };
+        for id in [osc_id, sampler_id, ambi_id] {
+            assert!(is_running(&ruff, id));
+        }
+
+        // replaces the modulator
+        ctrl.set_instance_parameter(
+            osc_id,
+            SynthParameterLabel::PitchFrequency.into(),
+            &lfo(5.0, 440.0, 20.0),
+        );
+        // both effects need one
+        ctrl.set_instance_parameter(
+            osc_id,
+            SynthParameterLabel::BitcrusherBits.into(),
+            &lfo(2.0, 8.0, 2.0),
+        );
+        // replaces the envelope
+        ctrl.set_instance_parameter(osc_id, SynthParameterLabel::Envelope.into(), &sustain(0.3));
+        // both channels need one
+        ctrl.set_instance_parameter(
+            sampler_id,
+            SynthParameterLabel::LowpassCutoffFrequency.into(),
+            &lfo(2.0, 1000.0, 200.0),
+        );
+        ctrl.set_instance_parameter(
+            ambi_id,
+            SynthParameterLabel::AmbisonicAzimuth.into(),
+            &lfo(1.0, 0.0, 1.0),
+        );
+
+        // all channels of the delays need one, right away and timestamped
+        ctrl.set_master_parameter(SynthParameterLabel::DelayTime, lfo(1.0, 0.25, 0.05));
+        ctrl.set_master_parameter_at(
+            SynthParameterLabel::DelayFeedback,
+            lfo(1.0, 0.5, 0.1),
+            now + 0.005,
+        );
+        ctrl.set_aux_bus_parameter(bus, SynthParameterLabel::DelayTime, lfo(1.0, 0.25, 0.05));
+        // the sound field rotation
+        ctrl.set_master_parameter(SynthParameterLabel::AmbisonicYaw, lfo(0.5, 0.0, 1.0));
+
+        ctrl.set_group_gain(3, 0.5);
+        ctrl.set_group_solo(3, true);
+        ctrl.set_group_mute(3, true);
+
+        assert_no_alloc(|| {
+            for _ in 0..4 {
+                let _ = ruff.process(0.0, true);
+            }
+        });
+
+        for id in [osc_id, sampler_id, ambi_id] {
+            assert!(is_running(&ruff, id));
+        }
+        ctrl.collect_garbage();
+    }
+
+    // an envelope that sustains long enough to change things while it's running
+    fn sustain(level: f32) -> SynthParameterValue {
+        SynthParameterValue::MultiPointEnvelope(
+            vec![
+                EnvelopeSegmentInfo {
+                    from: 0.0,
+                    to: level,
+                    time: 0.01,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+                EnvelopeSegmentInfo {
+                    from: level,
+                    to: level,
+                    time: 0.1,
+                    segment_type: EnvelopeSegmentType::Constant,
+                },
+                EnvelopeSegmentInfo {
+                    from: level,
+                    to: 0.0,
+                    time: 0.01,
+                    segment_type: EnvelopeSegmentType::Lin,
+                },
+            ],
+            false,
+            ValOp::Replace,
+        )
+    }
+
+    fn lfo(freq: f32, center: f32, depth: f32) -> SynthParameterValue {
+        SynthParameterValue::Lfo(
+            center,
+            Box::new(SynthParameterValue::ScalarF32(freq)),
+            0.0,
+            Box::new(SynthParameterValue::ScalarF32(depth)),
+            center,
+            ValOp::Replace,
+        )
+    }
 }
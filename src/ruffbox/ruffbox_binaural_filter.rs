@@ -0,0 +1,229 @@
+use crate::building_blocks::ambisonics::binauralizer::resample_filter;
+use crate::ruffbox::RuffboxError;
+
+/// one left/right pair of impulse responses per ambisonic channel
+pub type BinauralFilter = Vec<(Vec<f32>, Vec<f32>)>;
+
+/*
+ * Loaders for binaural filters, that is, one left/right pair of impulse responses
+ * (decoded HRIRs) per ambisonic channel, in ACN order. The result can be used
+ * with `RuffboxConfig::binaural_filter` or `RuffboxControls::set_binaural_filter`.
+ *
+ * The files hold all left ear responses first, then all right ear responses:
+ *
+ * raw: 32-bit float, little endian, the responses one after another, all of the
+ *      same length (the built-in `binaural_filter/default.raw` is a first-order
+ *      filter with 128 taps per response at 44.1kHz)
+ * WAV: one channel per response, so 8, 18 or 32 channels for first, second
+ *      or third order, 16/24/32-bit integer or 32/64-bit float samples
+ */
+
+/// Load a binaural filter from raw float data, with the given number of
+/// ambisonic channels (4, 9 or 16), resampled from the samplerate
+/// of the file to the given samplerate if necessary.
+pub fn load_binaural_filter_raw(
+    bytes: &[u8],
+    channels: usize,
+    file_samplerate: f32,
+    samplerate: f32,
+) -> Result<BinauralFilter, RuffboxError> {
+    check_channels(channels)?;
+    if bytes.is_empty() || bytes.len() % (4 * 2 * channels) != 0 {
+        return Err(RuffboxError::InvalidFilterFile(
+            "data doesn't divide into responses of equal length",
+        ));
+    }
+
+    let samples: Vec<f32> = bytes
+        .chunks(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+
+    let len = samples.len() / (2 * channels);
+    let responses: Vec<Vec<f32>> = samples.chunks(len).map(|r| r.to_vec()).collect();
+    to_filter(responses, channels, file_samplerate, samplerate)
+}
+
+/// Load a binaural filter from a WAV file, the order follows from the number of
+/// channels. Resampled from the samplerate of the file to the given one if necessary.
+pub fn load_binaural_filter_wav(
+    bytes: &[u8],
+    samplerate: f32,
+) -> Result<BinauralFilter, RuffboxError> {
+    let (file_samplerate, responses) = parse_wav(bytes)?;
+    if responses.len() % 2 != 0 {
+        return Err(RuffboxError::InvalidFilterFile(
+            "needs an even number of channels",
+        ));
+    }
+    let channels = responses.len() / 2;
+    check_channels(channels)?;
+    to_filter(responses, channels, file_samplerate, samplerate)
+}
+
+fn check_channels(channels: usize) -> Result<(), RuffboxError> {
+    if matches!(channels, 4 | 9 | 16) {
+        Ok(())
+    } else {
+        Err(RuffboxError::InvalidFilterFile(
+            "needs 4, 9 or 16 ambisonic channels",
+        ))
+    }
+}
+
+// pair up the left and right ear responses
+fn to_filter(
+    mut responses: Vec<Vec<f32>>,
+    channels: usize,
+    file_samplerate: f32,
+    samplerate: f32,
+) -> Result<BinauralFilter, RuffboxError> {
+    if file_samplerate.is_nan() || file_samplerate <= 0.0 {
+        return Err(RuffboxError::InvalidFilterFile("invalid samplerate"));
+    }
+    if responses.iter().any(|r| r.is_empty()) {
+        return Err(RuffboxError::InvalidFilterFile("empty impulse response"));
+    }
+
+    let right = responses.split_off(channels);
+    let ir: BinauralFilter = responses.into_iter().zip(right).collect();
+    Ok(resample_filter(&ir, file_samplerate, samplerate))
+}
+
+fn read_u16(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+// returns the samplerate and the (de-interleaved) channels
+fn parse_wav(bytes: &[u8]) -> Result<(f32, Vec<Vec<f32>>), RuffboxError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(RuffboxError::InvalidFilterFile("not a WAV file"));
+    }
+
+    // format, channels, samplerate, bits per sample
+    let mut format: Option<(u16, usize, u32, usize)> = None;
+    let mut data: Option<&[u8]> = None;
+
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = read_u32(bytes, pos + 4) as usize;
+        let start = pos + 8;
+        let end = start.saturating_add(size).min(bytes.len());
+        let chunk = &bytes[start..end];
+
+        if id == b"fmt " {
+            if chunk.len() < 16 {
+                return Err(RuffboxError::InvalidFilterFile("broken format chunk"));
+            }
+            let mut tag = read_u16(chunk, 0);
+            // WAVE_FORMAT_EXTENSIBLE, the actual format is in the sub-format
+            if tag == 0xFFFE && chunk.len() >= 26 {
+                tag = read_u16(chunk, 24);
+            }
+            format = Some((
+                tag,
+                read_u16(chunk, 2) as usize,
+                read_u32(chunk, 4),
+                read_u16(chunk, 14) as usize,
+            ));
+        } else if id == b"data" {
+            data = Some(chunk);
+        }
+
+        // chunks are padded to an even size
+        pos = start.saturating_add(size).saturating_add(size % 2);
+    }
+
+    let (Some((tag, channels, samplerate, bits)), Some(data)) = (format, data) else {
+        return Err(RuffboxError::InvalidFilterFile("missing format or data"));
+    };
+    if channels == 0 {
+        return Err(RuffboxError::InvalidFilterFile("no channels"));
+    }
+
+    let bytes_per_sample = bits / 8;
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (1, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2147483648.0,
+        (1, 32) => |b| i32::from_le_bytes(b.try_into().unwrap()) as f32 / 2147483648.0,
+        (3, 32) => |b| f32::from_le_bytes(b.try_into().unwrap()),
+        (3, 64) => |b| f64::from_le_bytes(b.try_into().unwrap()) as f32,
+        _ => return Err(RuffboxError::InvalidFilterFile("unsupported sample format")),
+    };
+
+    let mut responses = vec![Vec::new(); channels];
+    for frame in data.chunks_exact(bytes_per_sample * channels) {
+        for (response, sample) in responses
+            .iter_mut()
+            .zip(frame.chunks_exact(bytes_per_sample))
+        {
+            response.push(decode(sample));
+        }
+    }
+
+    Ok((samplerate as f32, responses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 16 bit WAV file, one channel per response
+    fn wav_file(responses: &[Vec<f32>], samplerate: u32) -> Vec<u8> {
+        let channels = responses.len() as u16;
+        let len = responses[0].len();
+        let mut data = Vec::new();
+        for s in 0..len {
+            for r in responses.iter() {
+                data.extend_from_slice(&((r[s] * 32767.0) as i16).to_le_bytes());
+            }
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&samplerate.to_le_bytes());
+        bytes.extend_from_slice(&(samplerate * 2 * channels as u32).to_le_bytes());
+        bytes.extend_from_slice(&(2 * channels).to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[test]
+    fn test_load_binaural_filter() {
+        // the built-in filter
+        let raw = include_bytes!("../../binaural_filter/default.raw");
+        let ir = load_binaural_filter_raw(raw, 4, 44100.0, 44100.0).unwrap();
+        assert_eq!(ir.len(), 4);
+        assert!(ir.iter().all(|(l, r)| l.len() == 128 && r.len() == 128));
+        assert_eq!(ir[1].1[0].to_le_bytes(), raw[640 * 4..640 * 4 + 4]);
+
+        // left ears first, then right ears
+        let responses: Vec<Vec<f32>> = (0..18).map(|i| vec![i as f32 / 20.0; 64]).collect();
+        let ir = load_binaural_filter_wav(&wav_file(&responses, 48000), 48000.0).unwrap();
+        assert_eq!(ir.len(), 9);
+        assert!((ir[2].0[10] - 0.1).abs() < 0.001);
+        assert!((ir[2].1[10] - 0.55).abs() < 0.001);
+
+        assert_eq!(
+            load_binaural_filter_wav(&wav_file(&responses[..6], 48000), 48000.0),
+            Err(RuffboxError::InvalidFilterFile(
+                "needs 4, 9 or 16 ambisonic channels"
+            ))
+        );
+        assert!(load_binaural_filter_wav(&raw[..], 44100.0).is_err());
+        assert!(load_binaural_filter_raw(&raw[..100], 4, 44100.0, 44100.0).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+/// A tempo change at a certain beat. If `ramp` is set, the tempo
+/// changes gradually (linear over the beats) from the previous tempo
+/// change to this one, otherwise it jumps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempoChange {
+    pub beat: f64,
+    pub bpm: f64,
+    pub ramp: bool,
+}
+
+/// A time signature, valid from the given bar on (bars start at 0).
+/// Beats are always quarter notes, so a bar in 6/8 is three beats long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub bar: usize,
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    fn beats_per_bar(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+// a section with either constant or linearly changing tempo
+struct TempoSegment {
+    start_beat: f64,
+    end_beat: f64,
+    bpm: f64,   // tempo at the start
+    slope: f64, // bpm change per beat
+}
+
+impl TempoSegment {
+    // the time it takes to play the first `beats` beats of this segment
+    fn duration(&self, beats: f64) -> f64 {
+        if self.slope == 0.0 {
+            beats * 60.0 / self.bpm
+        } else {
+            60.0 / self.slope * ((self.bpm + self.slope * beats) / self.bpm).ln()
+        }
+    }
+
+    // the number of beats played after `time` seconds into this segment
+    fn beats(&self, time: f64) -> f64 {
+        if self.slope == 0.0 {
+            time * self.bpm / 60.0
+        } else {
+            self.bpm / self.slope * ((self.slope * time / 60.0).exp() - 1.0)
+        }
+    }
+}
+
+/**
+ * Converts between musical time (beats and bars) and engine time (seconds),
+ * following a tempo map with (optionally ramped) tempo changes and time signatures.
+ *
+ * The conversion is calculated from the tempo map each time, so there's no
+ * accumulated error no matter how long the piece is.
+ */
+#[derive(Clone, Debug)]
+pub struct TempoClock {
+    origin: f64,                         // the engine time of beat 0
+    tempo_changes: Vec<TempoChange>,     // sorted, the first one is at beat 0
+    time_signatures: Vec<TimeSignature>, // sorted, the first one is at bar 0
+}
+
+const DEFAULT_BPM: f64 = 120.0;
+
+// the tempo has to be finite and positive, otherwise the conversions make no sense
+fn valid_tempo(bpm: f64) -> bool {
+    bpm.is_finite() && bpm > 0.0
+}
+
+impl TempoClock {
+    /// A clock with the given tempo, in 4/4, starting at time 0.
+    /// A non-finite tempo, or one of zero or less, falls back to 120 bpm.
+    pub fn new(bpm: f64) -> Self {
+        TempoClock {
+            origin: 0.0,
+            tempo_changes: vec![TempoChange {
+                beat: 0.0,
+                bpm: if valid_tempo(bpm) { bpm } else { DEFAULT_BPM },
+                ramp: false,
+            }],
+            time_signatures: vec![TimeSignature {
+                bar: 0,
+                numerator: 4,
+                denominator: 4,
+            }],
+        }
+    }
+
+    /// set the engine time at which beat 0 happens, a non-finite time is ignored
+    pub fn set_origin(&mut self, time: f64) {
+        if time.is_finite() {
+            self.origin = time;
+        }
+    }
+
+    pub fn origin(&self) -> f64 {
+        self.origin
+    }
+
+    /// Add a tempo change, replacing any previous change at the same beat.
+    /// A change at beat 0 (or before) sets the initial tempo and can't be ramped.
+    /// Changes with a non-finite beat or tempo, or a tempo of zero or less, are ignored.
+    pub fn set_tempo(&mut self, beat: f64, bpm: f64, ramp: bool) {
+        if !beat.is_finite() || !valid_tempo(bpm) {
+            return;
+        }
+
+        if beat <= 0.0 {
+            self.tempo_changes[0].bpm = bpm;
+            return;
+        }
+
+        let change = TempoChange { beat, bpm, ramp };
+        match self
+            .tempo_changes
+            .binary_search_by(|c| c.beat.total_cmp(&beat))
+        {
+            Ok(idx) => self.tempo_changes[idx] = change,
+            Err(idx) => self.tempo_changes.insert(idx, change),
+        }
+    }
+
+    /// remove all tempo changes, keep the initial tempo
+    pub fn clear_tempo_changes(&mut self) {
+        self.tempo_changes.truncate(1);
+    }
+
+    pub fn tempo_changes(&self) -> &[TempoChange] {
+        &self.tempo_changes
+    }
+
+    /// the tempo at the given beat
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        let segment = self.segment_at_beat(beat);
+        segment.bpm + segment.slope * (beat - segment.start_beat).max(0.0)
+    }
+
+    /// Set the time signature from the given bar on, replacing any
+    /// previous one at the same bar.
+    pub fn set_time_signature(&mut self, bar: usize, numerator: u32, denominator: u32) {
+        if numerator == 0 || denominator == 0 {
+            return;
+        }
+
+        let sig = TimeSignature {
+            bar,
+            numerator,
+            denominator,
+        };
+        match self.time_signatures.binary_search_by_key(&bar, |s| s.bar) {
+            Ok(idx) => self.time_signatures[idx] = sig,
+            Err(idx) => self.time_signatures.insert(idx, sig),
+        }
+    }
+
+    /// the time signature that's valid in the given bar
+    pub fn time_signature_at(&self, bar: usize) -> TimeSignature {
+        *self
+            .time_signatures
+            .iter()
+            .rev()
+            .find(|s| s.bar <= bar)
+            .unwrap()
+    }
+
+    // the segments of the tempo map, the last one is open-ended
+    fn segments(&self) -> impl Iterator<Item = TempoSegment> + '_ {
+        (0..self.tempo_changes.len()).map(move |i| {
+            let current = &self.tempo_changes[i];
+            if let Some(next) = self.tempo_changes.get(i + 1) {
+                TempoSegment {
+                    start_beat: current.beat,
+                    end_beat: next.beat,
+                    bpm: current.bpm,
+                    slope: if next.ramp {
+                        (next.bpm - current.bpm) / (next.beat - current.beat)
+                    } else {
+                        0.0
+                    },
+                }
+            } else {
+                TempoSegment {
+                    start_beat: current.beat,
+                    end_beat: f64::INFINITY,
+                    bpm: current.bpm,
+                    slope: 0.0,
+                }
+            }
+        })
+    }
+
+    fn segment_at_beat(&self, beat: f64) -> TempoSegment {
+        // the last segment is open-ended (and also covers a NaN beat)
+        self.segments()
+            .find(|s| beat < s.end_beat)
+            .or_else(|| self.segments().last())
+            .unwrap()
+    }
+
+    /// convert a beat position to engine time (seconds)
+    pub fn beat_to_time(&self, beat: f64) -> f64 {
+        // before the start, keep the initial tempo
+        if beat < 0.0 {
+            return self.origin + beat * 60.0 / self.tempo_changes[0].bpm;
+        }
+
+        let mut time = self.origin;
+        for segment in self.segments() {
+            if beat < segment.end_beat {
+                return time + segment.duration(beat - segment.start_beat);
+            }
+            time += segment.duration(segment.end_beat - segment.start_beat);
+        }
+        time
+    }
+
+    /// convert engine time (seconds) to a beat position
+    pub fn time_to_beat(&self, time: f64) -> f64 {
+        let mut elapsed = time - self.origin;
+
+        if elapsed < 0.0 {
+            return elapsed * self.tempo_changes[0].bpm / 60.0;
+        }
+
+        for segment in self.segments() {
+            let duration = segment.duration(segment.end_beat - segment.start_beat);
+            if elapsed < duration {
+                return segment.start_beat + segment.beats(elapsed);
+            }
+            elapsed -= duration;
+        }
+        // unreachable, as the last segment is open-ended
+        self.tempo_changes.last().unwrap().beat
+    }
+
+    /// the beat at which the given bar starts
+    pub fn bar_to_beat(&self, bar: usize) -> f64 {
+        let mut beat = 0.0;
+        for (i, sig) in self.time_signatures.iter().enumerate() {
+            let section_end = self
+                .time_signatures
+                .get(i + 1)
+                .map_or(bar, |next| next.bar.min(bar));
+            if section_end > sig.bar {
+                beat += (section_end - sig.bar) as f64 * sig.beats_per_bar();
+            }
+        }
+        beat
+    }
+
+    /// convert a beat position to a bar and the beat within that bar
+    pub fn beat_to_bar(&self, beat: f64) -> (usize, f64) {
+        let mut section_start = 0.0;
+        for (i, sig) in self.time_signatures.iter().enumerate() {
+            let bpb = sig.beats_per_bar();
+            let section_beats = self
+                .time_signatures
+                .get(i + 1)
+                .map_or(f64::INFINITY, |next| (next.bar - sig.bar) as f64 * bpb);
+
+            if beat < section_start + section_beats {
+                let offset = (beat - section_start).max(0.0);
+                let bars = (offset / bpb).floor();
+                return (sig.bar + bars as usize, offset - bars * bpb);
+            }
+            section_start += section_beats;
+        }
+        // unreachable, as the last section is open-ended
+        (0, 0.0)
+    }
+
+    /// convert a position in bars and beats to engine time (seconds)
+    pub fn bar_to_time(&self, bar: usize, beat: f64) -> f64 {
+        self.beat_to_time(self.bar_to_beat(bar) + beat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_constant_tempo() {
+        let mut clock = TempoClock::new(120.0);
+        clock.set_origin(10.0);
+
+        assert_approx_eq!(clock.beat_to_time(0.0), 10.0, 0.000001);
+        assert_approx_eq!(clock.beat_to_time(4.0), 12.0, 0.000001);
+        assert_approx_eq!(clock.beat_to_time(-1.0), 9.5, 0.000001);
+        assert_approx_eq!(clock.time_to_beat(13.0), 6.0, 0.000001);
+
+        // jump to 60 bpm after 4 beats
+        clock.set_tempo(4.0, 60.0, false);
+        assert_approx_eq!(clock.beat_to_time(6.0), 14.0, 0.000001);
+        assert_approx_eq!(clock.time_to_beat(14.0), 6.0, 0.000001);
+        assert_approx_eq!(clock.tempo_at(5.0), 60.0, 0.000001);
+
+        // invalid changes are ignored
+        clock.set_tempo(f64::NAN, 90.0, false);
+        clock.set_tempo(8.0, f64::NAN, false);
+        clock.set_tempo(8.0, f64::INFINITY, false);
+        assert_eq!(clock.tempo_changes().len(), 2);
+        assert_approx_eq!(clock.tempo_at(f64::NAN), 60.0, 0.000001);
+
+        clock.set_origin(f64::NAN);
+        assert_approx_eq!(clock.origin(), 10.0, 0.000001);
+
+        for bpm in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+            assert_approx_eq!(TempoClock::new(bpm).tempo_at(0.0), 120.0, 0.000001);
+        }
+    }
+
+    #[test]
+    fn test_tempo_ramp() {
+        let mut clock = TempoClock::new(60.0);
+        // from 60 to 120 bpm over 8 beats, then constant
+        clock.set_tempo(8.0, 120.0, true);
+
+        assert_approx_eq!(clock.tempo_at(4.0), 90.0, 0.000001);
+        assert_approx_eq!(clock.tempo_at(10.0), 120.0, 0.000001);
+
+        // slope of 7.5 bpm per beat, so 60 / 7.5 * ln(2) seconds for the ramp
+        let ramp_end = 8.0 * std::f64::consts::LN_2;
+        assert_approx_eq!(clock.beat_to_time(8.0), ramp_end, 0.000001);
+        assert_approx_eq!(clock.beat_to_time(10.0), ramp_end + 1.0, 0.000001);
+
+        for beat in [0.5, 3.0, 7.9, 8.0, 12.25] {
+            assert_approx_eq!(clock.time_to_beat(clock.beat_to_time(beat)), beat, 0.000001);
+        }
+    }
+
+    #[test]
+    fn test_time_signatures() {
+        let mut clock = TempoClock::new(120.0);
+        // two bars 4/4, then 6/8
+        clock.set_time_signature(2, 6, 8);
+
+        assert_approx_eq!(clock.bar_to_beat(2), 8.0, 0.000001);
+        assert_approx_eq!(clock.bar_to_beat(4), 14.0, 0.000001);
+        assert_approx_eq!(clock.bar_to_time(3, 1.0), 6.0, 0.000001);
+
+        let (bar, beat) = clock.beat_to_bar(12.5);
+        assert_eq!(bar, 3);
+        assert_approx_eq!(beat, 1.5, 0.000001);
+
+        let (bar, beat) = clock.beat_to_bar(5.0);
+        assert_eq!(bar, 1);
+        assert_approx_eq!(beat, 1.0, 0.000001);
+
+        assert_eq!(clock.time_signature_at(5).numerator, 6);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, DecoderWeighting, MAX_AMBISONIC_ORDER,
+};
+use crate::ruffbox::{
+    init_ruffbox_from_config, ReverbMode, RuffboxControls, RuffboxError, RuffboxPlayhead,
+    VoiceStealingPolicy, DEFAULT_QUEUE_CAPACITY,
+};
+
+/**
+ * The engine-wide settings, to create the controls and the playhead with.
+ *
+ * Start with the defaults for a samplerate, change what you need and build, i.e.
+ * `RuffboxConfig::new(44100.0).live_buffers(1, 3.0).freeze_buffers(2).build::<512, 2>()`.
+ * Inconsistent settings are rejected when building.
+ */
+#[derive(Clone)]
+pub struct RuffboxConfig {
+    pub(crate) samplerate: f64,
+    pub(crate) live_buffers: usize,
+    pub(crate) live_buffer_time: f64, // seconds
+    pub(crate) freeze_buffers: usize,
+    pub(crate) max_buffers: usize, // including live and freeze buffers
+    pub(crate) reverb_mode: ReverbMode,
+    pub(crate) delay_max_time: f64, // seconds
+    pub(crate) ambisonics_binaural: bool,
+    pub(crate) ambisonic_order: usize,
+    pub(crate) binaural_filter: Option<Vec<(Vec<f32>, Vec<f32>)>>, // None means the default filter
+    pub(crate) binaural_filter_samplerate: f32,
+    pub(crate) ambisonics_decoder: Option<(Vec<(f32, f32)>, DecoderWeighting)>,
+    pub(crate) vbap_layout: Option<Vec<(f32, f32)>>,
+    pub(crate) queue_capacity: usize,
+    pub(crate) voice_limit: Option<usize>,
+    pub(crate) voice_stealing_policy: VoiceStealingPolicy,
+    pub(crate) random_seed: Option<u64>,
+}
+
+impl RuffboxConfig {
+    pub fn new(samplerate: f64) -> Self {
+        RuffboxConfig {
+            samplerate,
+            live_buffers: 0,
+            live_buffer_time: 3.0,
+            freeze_buffers: 0,
+            max_buffers: 3000,
+            reverb_mode: ReverbMode::FreeVerb,
+            delay_max_time: 2.0,
+            ambisonics_binaural: false,
+            ambisonic_order: 1,
+            binaural_filter: None,
+            binaural_filter_samplerate: samplerate as f32,
+            ambisonics_decoder: None,
+            vbap_layout: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            voice_limit: None,
+            voice_stealing_policy: VoiceStealingPolicy::Oldest,
+            random_seed: None,
+        }
+    }
+
+    /// number and length (in seconds) of the live input buffers
+    pub fn live_buffers(mut self, num: usize, time: f64) -> Self {
+        self.live_buffers = num;
+        self.live_buffer_time = time;
+        self
+    }
+
+    /// number of freeze buffers (same length as the live buffers)
+    pub fn freeze_buffers(mut self, num: usize) -> Self {
+        self.freeze_buffers = num;
+        self
+    }
+
+    /// maximum number of buffers, including the live and freeze buffers
+    pub fn max_buffers(mut self, num: usize) -> Self {
+        self.max_buffers = num;
+        self
+    }
+
+    pub fn reverb(mut self, mode: ReverbMode) -> Self {
+        self.reverb_mode = mode;
+        self
+    }
+
+    /// the maximum time (in seconds) the master delay can be set to
+    pub fn delay_max_time(mut self, time: f64) -> Self {
+        self.delay_max_time = time;
+        self
+    }
+
+    pub fn ambisonics_binaural(mut self, enable: bool) -> Self {
+        self.ambisonics_binaural = enable;
+        self
+    }
+
+    /// The order of the ambisonic module (1 to 3), for both binaural
+    /// and loudspeaker output. Higher orders localize more precisely.
+    /// Note that the default binaural filters for second and third order
+    /// are synthetic ones without elevation cues, see `binaural_filter`.
+    pub fn ambisonic_order(mut self, order: usize) -> Self {
+        self.ambisonic_order = order;
+        self
+    }
+
+    /// Use a custom binaural filter for the ambisonic module, that is, one left/right
+    /// pair of impulse responses per ambisonic channel, at the given samplerate.
+    /// It's resampled to the engine's samplerate when building, if necessary.
+    /// See `load_binaural_filter_wav` and `load_binaural_filter_raw` to load them from files.
+    pub fn binaural_filter(mut self, ir: Vec<(Vec<f32>, Vec<f32>)>, samplerate: f32) -> Self {
+        self.binaural_filter = Some(ir);
+        self.binaural_filter_samplerate = samplerate;
+        self
+    }
+
+    /// Enable the ambisonic module and decode to a loudspeaker array instead
+    /// of binaural, with one (azimuth, elevation) pair (in radians) per output channel.
+    pub fn ambisonics_decoder(
+        mut self,
+        layout: Vec<(f32, f32)>,
+        weighting: DecoderWeighting,
+    ) -> Self {
+        self.ambisonics_decoder = Some((layout, weighting));
+        self
+    }
+
+    /// Enable the VBAP synths (`SynthType::VbapSampler` etc.), which pan on a speaker layout
+    /// with one (azimuth, elevation) pair (in radians) per output channel.
+    pub fn vbap_layout(mut self, layout: Vec<(f32, f32)>) -> Self {
+        self.vbap_layout = Some(layout);
+        self
+    }
+
+    /// capacity of the control queue (see `init_ruffbox_with_queue_capacity`)
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    /// see `RuffboxControls::set_voice_limit`
+    pub fn voice_limit(mut self, max_voices: Option<usize>, policy: VoiceStealingPolicy) -> Self {
+        self.voice_limit = max_voices;
+        self.voice_stealing_policy = policy;
+        self
+    }
+
+    /// see `RuffboxControls::set_random_seed`
+    pub fn random_seed(mut self, seed: Option<u64>) -> Self {
+        self.random_seed = seed;
+        self
+    }
+
+    /// check the settings for consistency
+    pub fn validate(&self) -> Result<(), RuffboxError> {
+        if !self.samplerate.is_finite() || self.samplerate <= 0.0 {
+            return Err(RuffboxError::InvalidConfig("samplerate must be positive"));
+        }
+
+        if self.live_buffers > 0
+            && (!self.live_buffer_time.is_finite() || self.live_buffer_time <= 0.0)
+        {
+            return Err(RuffboxError::InvalidConfig(
+                "live buffer time must be positive",
+            ));
+        }
+
+        if self.freeze_buffers > 0 && self.live_buffers == 0 {
+            return Err(RuffboxError::InvalidConfig(
+                "freeze buffers need live buffers",
+            ));
+        }
+
+        if self.live_buffers + self.freeze_buffers > self.max_buffers {
+            return Err(RuffboxError::InvalidConfig(
+                "max buffers must include the live and freeze buffers",
+            ));
+        }
+
+        if let ReverbMode::Convolution(ir, sr) = &self.reverb_mode {
+            if ir.is_empty() || !sr.is_finite() || *sr <= 0.0 {
+                return Err(RuffboxError::InvalidConfig(
+                    "invalid reverb impulse response",
+                ));
+            }
+        }
+
+        if self.ambisonic_order == 0 || self.ambisonic_order > MAX_AMBISONIC_ORDER {
+            return Err(RuffboxError::InvalidConfig(
+                "ambisonic order must be between 1 and 3",
+            ));
+        }
+
+        if !self.delay_max_time.is_finite() || self.delay_max_time <= 0.0 {
+            return Err(RuffboxError::InvalidConfig(
+                "delay max time must be positive",
+            ));
+        }
+
+        if let Some(ir) = self.binaural_filter.as_ref() {
+            if !self.ambisonics_binaural {
+                return Err(RuffboxError::InvalidConfig(
+                    "binaural filter without ambisonics",
+                ));
+            }
+            if ir.len() != ambisonic_channels(self.ambisonic_order)
+                || ir.iter().any(|(l, r)| l.is_empty() || r.is_empty())
+            {
+                return Err(RuffboxError::InvalidConfig(
+                    "binaural filter needs one pair of impulse responses per ambisonic channel",
+                ));
+            }
+            if !self.binaural_filter_samplerate.is_finite()
+                || self.binaural_filter_samplerate <= 0.0
+            {
+                return Err(RuffboxError::InvalidConfig(
+                    "binaural filter samplerate must be positive",
+                ));
+            }
+        }
+
+        if let Some((layout, _)) = self.ambisonics_decoder.as_ref() {
+            if self.ambisonics_binaural {
+                return Err(RuffboxError::InvalidConfig(
+                    "ambisonics can be either binaural or decoded to speakers",
+                ));
+            }
+            // a ring needs 2N + 1 speakers, a 3D layout (N + 1)^2
+            let is_2d = layout.iter().all(|(_, ele)| ele.abs() < 0.001);
+            let min_speakers = if is_2d {
+                2 * self.ambisonic_order + 1
+            } else {
+                ambisonic_channels(self.ambisonic_order)
+            };
+            if layout.len() < min_speakers {
+                return Err(RuffboxError::InvalidConfig(
+                    "not enough speakers for the ambisonic order",
+                ));
+            }
+        }
+
+        if let Some(layout) = self.vbap_layout.as_ref() {
+            if layout
+                .iter()
+                .any(|(azi, ele)| !azi.is_finite() || !ele.is_finite())
+            {
+                return Err(RuffboxError::InvalidConfig("invalid vbap speaker position"));
+            }
+        }
+
+        if self.queue_capacity == 0 {
+            return Err(RuffboxError::InvalidConfig(
+                "queue capacity must be positive",
+            ));
+        }
+
+        if self.voice_limit == Some(0) {
+            return Err(RuffboxError::InvalidConfig("voice limit must be positive"));
+        }
+
+        Ok(())
+    }
+
+    /// validate the settings and create the controls and the playhead
+    pub fn build<const BUFSIZE: usize, const NCHAN: usize>(
+        &self,
+    ) -> Result<
+        (
+            RuffboxControls<BUFSIZE, NCHAN>,
+            RuffboxPlayhead<BUFSIZE, NCHAN>,
+        ),
+        RuffboxError,
+    > {
+        self.validate()?;
+
+        // the binaural output is stereo
+        if self.ambisonics_binaural && NCHAN != 2 {
+            return Err(RuffboxError::InvalidConfig(
+                "binaural ambisonics need two output channels",
+            ));
+        }
+
+        if let Some((layout, _)) = self.ambisonics_decoder.as_ref() {
+            if layout.len() != NCHAN {
+                return Err(RuffboxError::InvalidConfig(
+                    "ambisonic decoder needs one speaker position per output channel",
+                ));
+            }
+        }
+
+        if let Some(layout) = self.vbap_layout.as_ref() {
+            if layout.len() != NCHAN {
+                return Err(RuffboxError::InvalidConfig(
+                    "vbap layout needs one speaker position per output channel",
+                ));
+            }
+        }
+
+        Ok(init_ruffbox_from_config(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_validation() {
+        assert!(RuffboxConfig::new(44100.0).build::<128, 2>().is_ok());
+
+        assert_eq!(
+            RuffboxConfig::new(44100.0).freeze_buffers(2).validate(),
+            Err(RuffboxError::InvalidConfig(
+                "freeze buffers need live buffers"
+            ))
+        );
+        assert!(RuffboxConfig::new(44100.0)
+            .live_buffers(2, 1.0)
+            .freeze_buffers(2)
+            .max_buffers(3)
+            .validate()
+            .is_err());
+        assert!(RuffboxConfig::new(0.0).validate().is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .binaural_filter(vec![(vec![1.0], vec![1.0]); 4], 44100.0)
+            .validate()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .build::<128, 4>()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .voice_limit(Some(0), VoiceStealingPolicy::Quietest)
+            .validate()
+            .is_err());
+
+        let (ctrl, _ruff) = RuffboxConfig::new(44100.0)
+            .live_buffers(1, 1.0)
+            .freeze_buffers(1)
+            .max_buffers(10)
+            .ambisonics_binaural(true)
+            .binaural_filter(vec![(vec![1.0], vec![1.0]); 4], 44100.0)
+            .queue_capacity(10)
+            .delay_max_time(5.0)
+            .build::<128, 2>()
+            .unwrap();
+        assert!(ctrl.try_freeze_buffer(0, 0).is_ok());
+
+        let ring: Vec<(f32, f32)> = (0..8)
+            .map(|i| (i as f32 * std::f32::consts::PI / 4.0, 0.0))
+            .collect();
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_decoder(ring.clone(), DecoderWeighting::MaxRe)
+            .build::<128, 8>()
+            .is_ok());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_decoder(ring.clone(), DecoderWeighting::MaxRe)
+            .build::<128, 2>()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .ambisonics_decoder(ring.clone(), DecoderWeighting::Basic)
+            .validate()
+            .is_err());
+
+        // higher orders need more speakers and filters
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonic_order(3)
+            .ambisonics_decoder(ring.clone(), DecoderWeighting::MaxRe)
+            .build::<128, 8>()
+            .is_ok());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonic_order(3)
+            .ambisonics_decoder(ring[0..6].to_vec(), DecoderWeighting::MaxRe)
+            .validate()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .vbap_layout(ring.clone())
+            .build::<128, 8>()
+            .is_ok());
+        assert!(RuffboxConfig::new(44100.0)
+            .vbap_layout(ring.clone())
+            .build::<128, 2>()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .vbap_layout(vec![(f32::NAN, 0.0), (0.0, 0.0)])
+            .validate()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonic_order(4)
+            .validate()
+            .is_err());
+        assert!(RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .ambisonic_order(2)
+            .binaural_filter(vec![(vec![1.0], vec![1.0]); 4], 44100.0)
+            .validate()
+            .is_err());
+        let (ctrl, _ruff) = RuffboxConfig::new(44100.0)
+            .ambisonics_binaural(true)
+            .ambisonic_order(2)
+            .build::<128, 2>()
+            .unwrap();
+        assert_eq!(ctrl.ambisonic_order(), 2);
+    }
+}
@@ -5,12 +5,21 @@ use rubato::{FftFixedIn, Resampler};
 // crossbeam for the event queue
 use crossbeam::atomic::AtomicCell;
 use dashmap::DashMap;
+use parking_lot::RwLock;
 
+use crate::building_blocks::ambisonics::ambisonic_channels;
+use crate::building_blocks::ambisonics::binauralizer::{resample_filter, Binauralizer};
+use crate::building_blocks::random::WyRand;
+use crate::building_blocks::routing::VbapLayout;
 use crate::building_blocks::{
-    resolve_parameter_value, SampleBuffer, SynthParameterAddress, SynthParameterLabel,
-    SynthParameterValue,
+    copy_modulator, resolve_parameter_value, SampleBuffer, Synth, SynthParameterAddress,
+    SynthParameterLabel, SynthParameterValue, ValueOrModulator,
+};
+use crate::ruffbox::{
+    is_aux_bus_send_modulator, AuxBus, AuxBusType, ControlMessage, Garbage, MeterPoint,
+    MeterReading, PlayheadNotification, RuffboxError, ScheduledEvent, SharedMeters, TempoClock,
+    VoiceStealingPolicy, MAX_AUX_BUSES, NUM_GROUPS,
 };
-use crate::ruffbox::{ControlMessage, ScheduledEvent};
 use crate::synths::*;
 
 use crate::ruffbox::ScheduledSource;
@@ -25,15 +34,56 @@ pub struct PreparedInstance<const BUFSIZE: usize, const NCHAN: usize> {
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> PreparedInstance<BUFSIZE, NCHAN> {
+    /// the id this instance can be addressed with once it's triggered
+    pub fn id(&self) -> usize {
+        self.ev.id
+    }
+
+    /// the time at which this instance will start
+    pub fn timestamp(&self) -> f64 {
+        self.ev.timestamp
+    }
+
+    /// Set a parameter (or modulator) before the instance is triggered.
+    /// Modulated sends to the additional aux buses aren't supported and are ignored.
     pub fn set_instance_parameter(
         &mut self,
         par: SynthParameterAddress,
         val: &SynthParameterValue,
     ) {
-        self.ev.set_param_or_modulator(
-            par,
-            resolve_parameter_value::<BUFSIZE>(par.label, val, self.sr),
-        );
+        let _ = self.try_set_instance_parameter(par, val);
+    }
+
+    /// like `set_instance_parameter`, but reports modulated sends to the additional aux buses
+    pub fn try_set_instance_parameter(
+        &mut self,
+        par: SynthParameterAddress,
+        val: &SynthParameterValue,
+    ) -> Result<(), RuffboxError> {
+        let val_or_mod = resolve_parameter_value::<BUFSIZE>(par.label, val, self.sr);
+        if is_aux_bus_send_modulator(par, &val_or_mod) {
+            return Err(RuffboxError::InvalidParameter("aux bus send modulator"));
+        }
+        let mut copies = copy_modulator(&val_or_mod, self.ev.modulator_copies());
+        // not in the audio thread, so whatever's left can be dropped right away
+        self.ev
+            .set_param_or_modulator(par, val_or_mod, &mut copies, &mut |_| {});
+        Ok(())
+    }
+
+    /// Tag the instance, so it can be flushed or shifted along
+    /// with other instances with the same tag while it's pending.
+    pub fn set_tag(&mut self, tag: usize) {
+        self.ev.tag = Some(tag);
+    }
+
+    /// Assign the instance to a group, which can be controlled as a whole
+    /// (gain, mute, solo). Groups outside of the range of
+    /// available groups are ignored.
+    pub fn set_group(&mut self, group: usize) {
+        if group < NUM_GROUPS {
+            self.ev.group = group;
+        }
     }
 }
 
@@ -52,18 +102,31 @@ pub struct RuffboxControls<const BUFSIZE: usize, const NCHAN: usize> {
     // actually stateless, but until then, the interior mutability pattern
     // comes in handy ...
     buffer_counter: AtomicCell<usize>,
+    instance_counter: AtomicCell<usize>,
+    random_seed: AtomicCell<Option<u64>>,
+    aux_bus_counter: AtomicCell<usize>,
     buffer_lengths: DashMap<usize, usize>,
     buffer_types: DashMap<usize, BufferType>,
+    // the number of modulator copies the triggered instances need, if any,
+    // so they can be made here and not in the audio thread (see `Synth::modulator_copies`)
+    modulator_copies: DashMap<usize, usize>,
     freeze_buffer_offset: usize,
     num_live_buffers: usize,
     num_freeze_buffers: usize,
     max_buffers: usize,
     control_q_send: crossbeam::channel::Sender<ControlMessage<BUFSIZE, NCHAN>>,
+    notification_q_rec: crossbeam::channel::Receiver<PlayheadNotification>,
+    garbage_q_rec: crossbeam::channel::Receiver<Garbage<BUFSIZE, NCHAN>>,
     now: Arc<AtomicCell<f64>>, // shared reference to global time counter
-    pub samplerate: f32,       // finally after all those years ...
+    ambisonic_order: Arc<AtomicCell<usize>>, // 0 means the ambisonic module is disabled
+    meters: Arc<SharedMeters<NCHAN>>,
+    vbap_layout: Option<Arc<VbapLayout>>, // None means the vbap synths are disabled
+    tempo_clock: RwLock<TempoClock>,
+    pub samplerate: f32, // finally after all those years ...
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         samplerate: f64,
         live_buffers: usize,
@@ -71,7 +134,11 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
         max_buffers: usize,
         freeze_buffers: usize,
         now: &Arc<AtomicCell<f64>>,
+        ambisonic_order: &Arc<AtomicCell<usize>>,
+        meters: &Arc<SharedMeters<NCHAN>>,
         tx: crossbeam::channel::Sender<ControlMessage<BUFSIZE, NCHAN>>,
+        nrx: crossbeam::channel::Receiver<PlayheadNotification>,
+        grx: crossbeam::channel::Receiver<Garbage<BUFSIZE, NCHAN>>,
     ) -> RuffboxControls<BUFSIZE, NCHAN> {
         // dash map is strange, mutable without mut ...
         let buffer_lengths = DashMap::new();
@@ -90,34 +157,148 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
             } else {
                 0
             }),
+            instance_counter: AtomicCell::new(0),
+            random_seed: AtomicCell::new(None),
+            aux_bus_counter: AtomicCell::new(0),
             freeze_buffer_offset: live_buffers,
             num_live_buffers: live_buffers,
             num_freeze_buffers: freeze_buffers,
             buffer_lengths,
             buffer_types,
+            modulator_copies: DashMap::new(),
             max_buffers,
             control_q_send: tx,
+            notification_q_rec: nrx,
+            garbage_q_rec: grx,
             samplerate: samplerate as f32,
             now: Arc::clone(now),
+            ambisonic_order: Arc::clone(ambisonic_order),
+            meters: Arc::clone(meters),
+            vbap_layout: None,
+            tempo_clock: RwLock::new(TempoClock::new(120.0)),
+        }
+    }
+
+    // the speaker layout is analyzed once and shared by all vbap synths
+    pub(crate) fn set_vbap_layout(&mut self, layout: &[(f32, f32)]) {
+        self.vbap_layout = Some(Arc::new(VbapLayout::new(layout)));
+    }
+
+    /// the order of the ambisonic module, 0 if it's not enabled
+    pub fn ambisonic_order(&self) -> usize {
+        self.ambisonic_order.load()
+    }
+
+    /// Prepare a sound source instance at a beat position of the tempo clock.
+    /// If the tempo changes before the instance starts, it's moved accordingly.
+    pub fn prepare_instance_at_beat(
+        &self,
+        src_type: SynthType,
+        beat: f64,
+        sample_buf: usize,
+    ) -> Option<PreparedInstance<BUFSIZE, NCHAN>> {
+        if !beat.is_finite() {
+            return None;
+        }
+        let mut inst = self.prepare_instance(src_type, self.beat_to_time(beat), sample_buf)?;
+        inst.ev.beat = Some(beat);
+        Some(inst)
+    }
+
+    /// Prepare a sound source instance, like `prepare_instance`, but report why
+    /// that isn't possible: the sample buffer doesn't exist (for live and freeze buffers,
+    /// the index is relative to the live and freeze buffers, respectively), the source
+    /// is an ambisonic one and the ambisonic module isn't enabled, or the source is a
+    /// VBAP one and there's no speaker layout.
+    pub fn try_prepare_instance(
+        &self,
+        src_type: SynthType,
+        timestamp: f64,
+        sample_buf: usize,
+    ) -> Result<PreparedInstance<BUFSIZE, NCHAN>, RuffboxError> {
+        if !timestamp.is_finite() {
+            return Err(RuffboxError::InvalidParameter("timestamp"));
+        }
+
+        let buffer_exists = match src_type {
+            SynthType::Sampler(_) | SynthType::AmbisonicSampler(_) | SynthType::VbapSampler(_) => {
+                self.buffer_types.contains_key(&sample_buf)
+            }
+            SynthType::LiveSampler(_) => sample_buf < self.num_live_buffers,
+            SynthType::FrozenSampler(_) => sample_buf < self.num_freeze_buffers,
+            _ => true,
+        };
+
+        if !buffer_exists {
+            return Err(RuffboxError::BufferIndexOutOfRange(sample_buf));
         }
+
+        let is_vbap = matches!(
+            src_type,
+            SynthType::VbapSampler(_)
+                | SynthType::VbapSingleOscillator(_)
+                | SynthType::VbapMultiOscillator(_)
+                | SynthType::VbapKarPlusPlus(_)
+                | SynthType::VbapRissetBell
+        );
+        if is_vbap && self.vbap_layout.is_none() {
+            return Err(RuffboxError::VbapDisabled);
+        }
+
+        let inst = self
+            .prepare_instance(src_type, timestamp, sample_buf)
+            .ok_or(RuffboxError::BufferIndexOutOfRange(sample_buf))?;
+
+        if matches!(inst.ev.source, ScheduledSource::Ambi(_)) && self.ambisonic_order.load() == 0 {
+            return Err(RuffboxError::AmbisonicsDisabled);
+        }
+
+        Ok(inst)
     }
 
-    /// prepare a sound source instance, return instance id
+    /// prepare a sound source instance at a beat position of the tempo clock,
+    /// see `try_prepare_instance`
+    pub fn try_prepare_instance_at_beat(
+        &self,
+        src_type: SynthType,
+        beat: f64,
+        sample_buf: usize,
+    ) -> Result<PreparedInstance<BUFSIZE, NCHAN>, RuffboxError> {
+        if !beat.is_finite() {
+            return Err(RuffboxError::InvalidParameter("beat"));
+        }
+        let mut inst = self.try_prepare_instance(src_type, self.beat_to_time(beat), sample_buf)?;
+        inst.ev.beat = Some(beat);
+        Ok(inst)
+    }
+
+    /// Prepare a sound source instance, the instance id is assigned here.
+    /// Returns `None` if the instance can't be prepared, use `try_prepare_instance`
+    /// to find out why.
     pub fn prepare_instance(
         &self,
         src_type: SynthType,
         timestamp: f64,
         sample_buf: usize,
     ) -> Option<PreparedInstance<BUFSIZE, NCHAN>> {
-        Some(PreparedInstance {
+        // good opportunity to clean up ...
+        self.collect_garbage();
+
+        let id = self.instance_counter.fetch_add(1);
+        let synth_type = std::mem::discriminant(&src_type);
+        let mut inst = PreparedInstance {
             sr: self.samplerate,
             ev: match src_type {
                 SynthType::KarPlusPlus(desc) => ScheduledEvent::new(
                     timestamp,
+                    id,
+                    synth_type,
                     ScheduledSource::Channel(Box::new(KarPlusPlus::new(desc, self.samplerate))),
                 ),
                 SynthType::SingleOscillator(desc) => ScheduledEvent::new(
                     timestamp,
+                    id,
+                    synth_type,
                     ScheduledSource::Channel(Box::new(SingleOscillatorSynth::new(
                         desc,
                         self.samplerate,
@@ -125,6 +306,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                 ),
                 SynthType::MultiOscillator(desc) => ScheduledEvent::new(
                     timestamp,
+                    id,
+                    synth_type,
                     ScheduledSource::Channel(Box::new(MultiOscillatorSynth::new(
                         desc,
                         self.samplerate,
@@ -132,17 +315,97 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                 ),
                 SynthType::RissetBell => ScheduledEvent::new(
                     timestamp,
+                    id,
+                    synth_type,
                     ScheduledSource::Channel(Box::new(RissetBell::new(self.samplerate))),
                 ),
+                SynthType::AmbisonicSingleOscillator(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.ambisonic_source(Box::new(SingleOscillatorSynth::new(
+                        desc,
+                        self.samplerate,
+                    ))),
+                ),
+                SynthType::AmbisonicMultiOscillator(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.ambisonic_source(Box::new(MultiOscillatorSynth::new(
+                        desc,
+                        self.samplerate,
+                    ))),
+                ),
+                SynthType::AmbisonicKarPlusPlus(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.ambisonic_source(Box::new(KarPlusPlus::new(desc, self.samplerate))),
+                ),
+                SynthType::AmbisonicRissetBell => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.ambisonic_source(Box::new(RissetBell::new(self.samplerate))),
+                ),
+                SynthType::VbapSingleOscillator(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.vbap_source(Box::new(SingleOscillatorSynth::new(desc, self.samplerate)))?,
+                ),
+                SynthType::VbapMultiOscillator(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.vbap_source(Box::new(MultiOscillatorSynth::new(desc, self.samplerate)))?,
+                ),
+                SynthType::VbapKarPlusPlus(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.vbap_source(Box::new(KarPlusPlus::new(desc, self.samplerate)))?,
+                ),
+                SynthType::VbapRissetBell => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    self.vbap_source(Box::new(RissetBell::new(self.samplerate)))?,
+                ),
+                SynthType::VbapSampler(desc) => ScheduledEvent::new(
+                    timestamp,
+                    id,
+                    synth_type,
+                    // only mono buffers can be panned
+                    match *self.buffer_types.get(&sample_buf)? {
+                        BufferType::Mono => self.vbap_source(Box::new(NChannelSampler::new(
+                            desc,
+                            sample_buf,
+                            *self.buffer_lengths.get(&sample_buf)?,
+                            self.samplerate,
+                        )))?,
+                        BufferType::Stereo => {
+                            ScheduledSource::Channel(Box::new(NChannelStereoSampler::new(
+                                desc,
+                                sample_buf,
+                                *self.buffer_lengths.get(&sample_buf)?,
+                                self.samplerate,
+                            )))
+                        }
+                    },
+                ),
                 SynthType::Sampler(desc) => ScheduledEvent::new(
                     timestamp,
+                    id,
+                    synth_type,
                     // insert the right sampler type
-                    match *self.buffer_types.get(&sample_buf).unwrap() {
+                    match *self.buffer_types.get(&sample_buf)? {
                         BufferType::Mono => {
                             ScheduledSource::Channel(Box::new(NChannelSampler::new(
                                 desc,
                                 sample_buf,
-                                *self.buffer_lengths.get(&sample_buf).unwrap(),
+                                *self.buffer_lengths.get(&sample_buf)?,
                                 self.samplerate,
                             )))
                         }
@@ -150,7 +413,7 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                             ScheduledSource::Channel(Box::new(NChannelStereoSampler::new(
                                 desc,
                                 sample_buf,
-                                *self.buffer_lengths.get(&sample_buf).unwrap(),
+                                *self.buffer_lengths.get(&sample_buf)?,
                                 self.samplerate,
                             )))
                         }
@@ -159,23 +422,47 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                 SynthType::AmbisonicSampler(desc) => {
                     ScheduledEvent::new(
                         timestamp,
+                        id,
+                        synth_type,
                         // insert the right sampler type
                         // only mono sources are spatialized to ambisonic so far ...
-                        match *self.buffer_types.get(&sample_buf).unwrap() {
+                        match *self.buffer_types.get(&sample_buf)? {
                             BufferType::Mono => {
-                                ScheduledSource::Ambi(Box::new(AmbisonicSamplerO1::new(
-                                    desc,
-                                    sample_buf,
-                                    *self.buffer_lengths.get(&sample_buf).unwrap(),
-                                    self.samplerate,
-                                )))
+                                let buflen = *self.buffer_lengths.get(&sample_buf)?;
+                                // at the order of the ambisonic module
+                                ScheduledSource::Ambi(match self.ambisonic_order.load() {
+                                    2 => Box::new(AmbisonicBusSynth::new(Box::new(
+                                        AmbisonicSamplerO2::new(
+                                            desc,
+                                            sample_buf,
+                                            buflen,
+                                            self.samplerate,
+                                        ),
+                                    ))),
+                                    3 => Box::new(AmbisonicBusSynth::new(Box::new(
+                                        AmbisonicSamplerO3::new(
+                                            desc,
+                                            sample_buf,
+                                            buflen,
+                                            self.samplerate,
+                                        ),
+                                    ))),
+                                    _ => Box::new(AmbisonicBusSynth::new(Box::new(
+                                        AmbisonicSamplerO1::new(
+                                            desc,
+                                            sample_buf,
+                                            buflen,
+                                            self.samplerate,
+                                        ),
+                                    ))),
+                                })
                             }
                             // just ignore for now ...
                             BufferType::Stereo => {
                                 ScheduledSource::Channel(Box::new(NChannelStereoSampler::new(
                                     desc,
                                     sample_buf,
-                                    *self.buffer_lengths.get(&sample_buf).unwrap(),
+                                    *self.buffer_lengths.get(&sample_buf)?,
                                     self.samplerate,
                                 )))
                             }
@@ -190,10 +477,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                     };
                     ScheduledEvent::new(
                         timestamp,
+                        id,
+                        synth_type,
                         ScheduledSource::Channel(Box::new(NChannelSampler::new(
                             desc,
                             final_bufnum,
-                            *self.buffer_lengths.get(&final_bufnum).unwrap(),
+                            *self.buffer_lengths.get(&final_bufnum)?,
                             self.samplerate,
                         ))),
                     )
@@ -206,10 +495,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                     };
                     ScheduledEvent::new(
                         timestamp,
+                        id,
+                        synth_type,
                         ScheduledSource::Channel(Box::new(NChannelSampler::new(
                             desc,
                             final_bufnum,
-                            *self.buffer_lengths.get(&final_bufnum).unwrap(),
+                            *self.buffer_lengths.get(&final_bufnum)?,
                             self.samplerate,
                         ))),
                     )
@@ -218,18 +509,182 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                     return None;
                 } // jump out
             },
-        })
+        };
+
+        // room for the sends to the additional aux buses
+        inst.ev.aux_sends = vec![0.0; self.aux_bus_counter.load()];
+
+        // derive a seed per instance, so that re-rendering the same
+        // sequence of instances gives the same result
+        if let Some(seed) = self.random_seed.load() {
+            let instance_seed = WyRand::with_seed(seed.wrapping_add(id as u64)).u64();
+            inst.set_instance_parameter(
+                SynthParameterLabel::RandomSeed.into(),
+                &SynthParameterValue::ScalarUsize(instance_seed as usize),
+            );
+        }
+
+        Some(inst)
+    }
+
+    /// Seed the random number generators of all instances prepared from now on
+    /// (noise sources, etc), to get reproducible output.
+    /// The instances need to be prepared in the same order for that.
+    /// `None` means random seeds, which is the default.
+    pub fn set_random_seed(&self, seed: Option<u64>) {
+        self.random_seed.store(seed);
     }
 
+    /// Add an aux bus with an additional effect and return its bus number,
+    /// or `None` if the maximum number of aux buses has been reached.
+    /// Bus 0 and 1 are the master reverb and delay, so the first additional bus is 2.
+    /// Instances send to the buses via the `AuxSend` parameter, with the bus number as index.
+    /// Only instances prepared after the bus has been added can send to it.
+    pub fn add_aux_bus(&self, bus_type: &AuxBusType) -> Option<usize> {
+        let bus = self
+            .aux_bus_counter
+            .fetch_update(|n| if n < MAX_AUX_BUSES { Some(n + 1) } else { None })
+            .ok()?;
+
+        self.control_q_send
+            .send(ControlMessage::AddAuxBus(AuxBus::new(
+                bus_type,
+                self.samplerate as f64,
+            )))
+            .unwrap();
+
+        Some(bus + 2)
+    }
+
+    /// Set a parameter of the effect on an additional aux bus (2 and up).
+    /// Buses that haven't been added are ignored, and so are 0 and 1, as the
+    /// master reverb and delay are set with `set_master_parameter`.
+    pub fn set_aux_bus_parameter(
+        &self,
+        bus: usize,
+        par: SynthParameterLabel,
+        val: SynthParameterValue,
+    ) {
+        if let Ok(msg) = self.aux_bus_parameter(bus, par, &val) {
+            self.control_q_send.send(msg).unwrap();
+        }
+    }
+
+    /// like `set_aux_bus_parameter`, but reports buses that don't exist
+    /// (including 0 and 1) and doesn't block if the control queue is full
+    pub fn try_set_aux_bus_parameter(
+        &self,
+        bus: usize,
+        par: SynthParameterLabel,
+        val: SynthParameterValue,
+    ) -> Result<(), RuffboxError> {
+        self.control_q_send
+            .try_send(self.aux_bus_parameter(bus, par, &val)?)?;
+        Ok(())
+    }
+
+    fn aux_bus_parameter(
+        &self,
+        bus: usize,
+        par: SynthParameterLabel,
+        val: &SynthParameterValue,
+    ) -> Result<ControlMessage<BUFSIZE, NCHAN>, RuffboxError> {
+        if bus < 2 || bus - 2 >= self.aux_bus_counter.load() {
+            return Err(RuffboxError::InvalidParameter("aux bus"));
+        }
+        let val_or_mod = resolve_parameter_value(par, val, self.samplerate);
+        // in case it's a delay, one for each channel but the first
+        let copies = copy_modulator(&val_or_mod, NCHAN - 1);
+        Ok(ControlMessage::SetAuxBusParamOrModulator(
+            bus - 2,
+            par,
+            val_or_mod,
+            copies,
+        ))
+    }
+
+    /// Set the gain of an instance group. Changes are smoothed.
+    pub fn set_group_gain(&self, group: usize, gain: f32) {
+        self.control_q_send
+            .send(ControlMessage::SetGroupGain(group, gain))
+            .unwrap();
+    }
+
+    /// Mute or unmute an instance group.
+    pub fn set_group_mute(&self, group: usize, mute: bool) {
+        self.control_q_send
+            .send(ControlMessage::SetGroupMute(group, mute))
+            .unwrap();
+    }
+
+    /// Solo an instance group. If any group is soloed,
+    /// only the soloed groups are audible.
+    pub fn set_group_solo(&self, group: usize, solo: bool) {
+        self.control_q_send
+            .send(ControlMessage::SetGroupSolo(group, solo))
+            .unwrap();
+    }
+
+    /// set a master (reverb, delay) parameter at the beginning of the next block
     pub fn set_master_parameter(&self, par: SynthParameterLabel, val: SynthParameterValue) {
+        self.set_master_parameter_at(par, val, 0.0);
+    }
+
+    /// set a master (reverb, delay) parameter at a precise point in time,
+    /// it'll be applied at the exact sample inside the block.
+    /// A timestamp of 0.0 means "as soon as possible".
+    /// Changes with the same timestamp are applied in the order they were sent.
+    /// Changes with a non-finite timestamp are ignored.
+    pub fn set_master_parameter_at(
+        &self,
+        par: SynthParameterLabel,
+        val: SynthParameterValue,
+        timestamp: f64,
+    ) {
+        if !timestamp.is_finite() {
+            return;
+        }
         self.control_q_send
-            .send(ControlMessage::SetGlobalParamOrModulator(
-                par,
-                resolve_parameter_value(par, &val, self.samplerate),
-            ))
+            .send(self.master_change(timestamp, par, &val))
             .unwrap();
     }
 
+    /// like `set_master_parameter_at`, but doesn't block if the control queue is full
+    pub fn try_set_master_parameter_at(
+        &self,
+        par: SynthParameterLabel,
+        val: SynthParameterValue,
+        timestamp: f64,
+    ) -> Result<(), RuffboxError> {
+        if !timestamp.is_finite() {
+            return Err(RuffboxError::InvalidParameter("timestamp"));
+        }
+        self.control_q_send
+            .try_send(self.master_change(timestamp, par, &val))?;
+        Ok(())
+    }
+
+    fn master_change(
+        &self,
+        timestamp: f64,
+        par: SynthParameterLabel,
+        val: &SynthParameterValue,
+    ) -> ControlMessage<BUFSIZE, NCHAN> {
+        let val_or_mod = resolve_parameter_value(par, val, self.samplerate);
+        // the master delay needs one for each channel but the first
+        let copies = copy_modulator(&val_or_mod, NCHAN - 1);
+        ControlMessage::SetGlobalParamOrModulator(timestamp, par, val_or_mod, copies)
+    }
+
+    /// like `set_master_parameter`, but doesn't block if the control queue is full
+    pub fn try_set_master_parameter(
+        &self,
+        par: SynthParameterLabel,
+        val: SynthParameterValue,
+    ) -> Result<(), RuffboxError> {
+        self.try_set_master_parameter_at(par, val, 0.0)
+    }
+
     pub fn clear_all_buffers(&self) {
         self.control_q_send
             .send(ControlMessage::ClearAllBuffers)
@@ -260,11 +715,422 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
             .unwrap();
     }
 
-    /// triggers a synth for buffer reference or a synth
-    pub fn trigger(&self, instance: PreparedInstance<BUFSIZE, NCHAN>) {
+    /// like `clear_live_buffer`, but checks the buffer number and doesn't block
+    pub fn try_clear_live_buffer(&self, bufnum: usize) -> Result<(), RuffboxError> {
+        if bufnum >= self.num_live_buffers {
+            return Err(RuffboxError::BufferIndexOutOfRange(bufnum));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::ClearLiveBuffer(bufnum))?;
+        Ok(())
+    }
+
+    /// like `clear_freeze_buffer`, but checks the buffer number and doesn't block
+    pub fn try_clear_freeze_buffer(&self, bufnum: usize) -> Result<(), RuffboxError> {
+        if bufnum >= self.num_freeze_buffers {
+            return Err(RuffboxError::BufferIndexOutOfRange(bufnum));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::ClearFreezeBuffer(bufnum))?;
+        Ok(())
+    }
+
+    /// Replace the binaural filter of the ambisonic module while running, with one
+    /// left/right pair of impulse responses per ambisonic channel (i.e. from
+    /// `load_binaural_filter_wav`) at the given samplerate, which is resampled to
+    /// the engine's samplerate if necessary. The filter is prepared here, not in
+    /// the audio thread. Has no effect if the ambisonics are decoded to speakers.
+    pub fn set_binaural_filter(&self, ir: &[(Vec<f32>, Vec<f32>)], samplerate: f32) {
+        self.control_q_send
+            .send(self.prepare_binaural_filter(ir, samplerate))
+            .unwrap();
+    }
+
+    /// like `set_binaural_filter`, but checks the filter and doesn't block
+    pub fn try_set_binaural_filter(
+        &self,
+        ir: &[(Vec<f32>, Vec<f32>)],
+        samplerate: f32,
+    ) -> Result<(), RuffboxError> {
+        let order = self.ambisonic_order.load();
+        if order == 0 {
+            return Err(RuffboxError::AmbisonicsDisabled);
+        }
+        if ir.len() < ambisonic_channels(order)
+            || ir.iter().any(|(l, r)| l.is_empty() || r.is_empty())
+        {
+            return Err(RuffboxError::InvalidParameter("binaural filter"));
+        }
+        if samplerate.is_nan() || samplerate <= 0.0 {
+            return Err(RuffboxError::InvalidParameter("samplerate"));
+        }
         self.control_q_send
-            .send(ControlMessage::ScheduleEvent(instance.ev))
+            .try_send(self.prepare_binaural_filter(ir, samplerate))?;
+        Ok(())
+    }
+
+    // the filters beyond the order of the ambisonic module wouldn't be used anyway
+    fn prepare_binaural_filter(
+        &self,
+        ir: &[(Vec<f32>, Vec<f32>)],
+        samplerate: f32,
+    ) -> ControlMessage<BUFSIZE, NCHAN> {
+        let channels = ambisonic_channels(self.ambisonic_order.load().max(1)).min(ir.len());
+        ControlMessage::SetBinauralFilter(Box::new(Binauralizer::from_ir(resample_filter(
+            &ir[..channels],
+            samplerate,
+            self.samplerate,
+        ))))
+    }
+
+    /// triggers a synth for buffer reference or a synth,
+    /// returns the instance id that can be used to control
+    /// the instance while it's pending or running
+    pub fn trigger(&self, instance: PreparedInstance<BUFSIZE, NCHAN>) -> usize {
+        let id = instance.ev.id;
+        self.keep_modulator_copies(id, instance.ev.modulator_copies());
+        self.control_q_send
+            .send(ControlMessage::ScheduleEvent(self.retime(instance.ev)))
             .unwrap();
+        id
+    }
+
+    // so the offline renderer knows when to let the playhead catch up
+    pub(crate) fn control_queue_full(&self) -> bool {
+        self.control_q_send.is_full()
+    }
+
+    /// Like `trigger`, but doesn't block if the control queue is full.
+    /// In that case, the instance is dropped.
+    pub fn try_trigger(
+        &self,
+        instance: PreparedInstance<BUFSIZE, NCHAN>,
+    ) -> Result<usize, RuffboxError> {
+        let id = instance.ev.id;
+        self.keep_modulator_copies(id, instance.ev.modulator_copies());
+        if let Err(err) = self
+            .control_q_send
+            .try_send(ControlMessage::ScheduleEvent(self.retime(instance.ev)))
+        {
+            self.modulator_copies.remove(&id);
+            return Err(err.into());
+        }
+        Ok(id)
+    }
+
+    // only instances that need copies are kept track of, until they come back
+    // as garbage (so this has to happen before the instance is sent)
+    fn keep_modulator_copies(&self, id: usize, copies: usize) {
+        if copies > 0 {
+            self.modulator_copies.insert(id, copies);
+        }
+    }
+
+    // the modulator comes with copies for the parts of the instance that need one each
+    fn instance_parameter(
+        &self,
+        id: usize,
+        par: SynthParameterAddress,
+        val: &SynthParameterValue,
+    ) -> Result<ControlMessage<BUFSIZE, NCHAN>, RuffboxError> {
+        let val_or_mod: ValueOrModulator<BUFSIZE> =
+            resolve_parameter_value(par.label, val, self.samplerate);
+        if is_aux_bus_send_modulator(par, &val_or_mod) {
+            return Err(RuffboxError::InvalidParameter("aux bus send modulator"));
+        }
+        let num_copies = self.modulator_copies.get(&id).map_or(0, |c| *c);
+        let copies = copy_modulator(&val_or_mod, num_copies);
+        Ok(ControlMessage::SetInstanceParamOrModulator(
+            id, par, val_or_mod, copies,
+        ))
+    }
+
+    // the tempo might have changed since a beat-scheduled instance was prepared
+    fn retime(&self, mut ev: ScheduledEvent<BUFSIZE, NCHAN>) -> ScheduledEvent<BUFSIZE, NCHAN> {
+        if let Some(beat) = ev.beat {
+            ev.timestamp = self.beat_to_time(beat);
+        }
+        ev
+    }
+
+    /// Set a parameter (or modulator) on an instance that has already been triggered,
+    /// if the instance has finished already, nothing happens.
+    /// Modulated sends to the additional aux buses aren't supported and are ignored.
+    pub fn set_instance_parameter(
+        &self,
+        id: usize,
+        par: SynthParameterAddress,
+        val: &SynthParameterValue,
+    ) {
+        if let Ok(msg) = self.instance_parameter(id, par, val) {
+            self.control_q_send.send(msg).unwrap();
+        }
+    }
+
+    /// like `set_instance_parameter`, but reports modulated sends to the additional
+    /// aux buses and doesn't block if the control queue is full
+    pub fn try_set_instance_parameter(
+        &self,
+        id: usize,
+        par: SynthParameterAddress,
+        val: &SynthParameterValue,
+    ) -> Result<(), RuffboxError> {
+        self.control_q_send
+            .try_send(self.instance_parameter(id, par, val)?)?;
+        Ok(())
+    }
+
+    /// release (note-off) an instance that has already been triggered,
+    /// which only has an effect on instances with a gated envelope
+    /// (see `SynthParameterLabel::EnvelopeGate`)
+    pub fn release(&self, id: usize) {
+        self.control_q_send
+            .send(ControlMessage::ReleaseInstance(id))
+            .unwrap();
+    }
+
+    /// like `release`, but doesn't block if the control queue is full
+    pub fn try_release(&self, id: usize) -> Result<(), RuffboxError> {
+        self.control_q_send
+            .try_send(ControlMessage::ReleaseInstance(id))?;
+        Ok(())
+    }
+
+    /// All sounds off! Fades out all running instances (channel-based and ambisonic)
+    /// over the given time (in seconds), and drops all pending events.
+    /// If `flush_effects` is set, the reverb and delay tails (including the aux buses)
+    /// are faded out as well and cleared afterwards.
+    pub fn panic(&self, fade_time: f64, flush_effects: bool) {
+        self.control_q_send
+            .send(ControlMessage::Panic(fade_time, flush_effects))
+            .unwrap();
+    }
+
+    /// like `panic`, but doesn't block if the control queue is full
+    pub fn try_panic(&self, fade_time: f64, flush_effects: bool) -> Result<(), RuffboxError> {
+        if fade_time.is_nan() || fade_time < 0.0 {
+            return Err(RuffboxError::InvalidParameter("fade time"));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::Panic(fade_time, flush_effects))?;
+        Ok(())
+    }
+
+    /// Drop all pending events at or after the given time.
+    /// Instances that are already playing aren't affected.
+    /// A non-finite timestamp is ignored.
+    pub fn flush_pending_events(&self, timestamp: f64) {
+        if !timestamp.is_finite() {
+            return;
+        }
+        self.control_q_send
+            .send(ControlMessage::FlushPendingEvents(timestamp))
+            .unwrap();
+    }
+
+    /// like `flush_pending_events`, but doesn't block if the control queue is full
+    pub fn try_flush_pending_events(&self, timestamp: f64) -> Result<(), RuffboxError> {
+        if !timestamp.is_finite() {
+            return Err(RuffboxError::InvalidParameter("timestamp"));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::FlushPendingEvents(timestamp))?;
+        Ok(())
+    }
+
+    /// drop all pending events with the given tag
+    pub fn flush_tagged_events(&self, tag: usize) {
+        self.control_q_send
+            .send(ControlMessage::FlushTaggedEvents(tag))
+            .unwrap();
+    }
+
+    /// Move all pending events by the given offset (in seconds, might be negative).
+    /// Events moved into the past will be played right away (and reported as late).
+    /// A non-finite offset is ignored.
+    pub fn shift_pending_events(&self, offset: f64) {
+        if !offset.is_finite() {
+            return;
+        }
+        self.control_q_send
+            .send(ControlMessage::ShiftPendingEvents(None, offset))
+            .unwrap();
+    }
+
+    /// like `shift_pending_events`, but doesn't block if the control queue is full
+    pub fn try_shift_pending_events(&self, offset: f64) -> Result<(), RuffboxError> {
+        if !offset.is_finite() {
+            return Err(RuffboxError::InvalidParameter("offset"));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::ShiftPendingEvents(None, offset))?;
+        Ok(())
+    }
+
+    /// move all pending events with the given tag by the given offset (in seconds),
+    /// a non-finite offset is ignored
+    pub fn shift_tagged_events(&self, tag: usize, offset: f64) {
+        if !offset.is_finite() {
+            return;
+        }
+        self.control_q_send
+            .send(ControlMessage::ShiftPendingEvents(Some(tag), offset))
+            .unwrap();
+    }
+
+    /// like `shift_tagged_events`, but doesn't block if the control queue is full
+    pub fn try_shift_tagged_events(&self, tag: usize, offset: f64) -> Result<(), RuffboxError> {
+        if !offset.is_finite() {
+            return Err(RuffboxError::InvalidParameter("offset"));
+        }
+        self.control_q_send
+            .try_send(ControlMessage::ShiftPendingEvents(Some(tag), offset))?;
+        Ok(())
+    }
+
+    /// Limit the number of simultaneously running voices (channel-based and ambisonic
+    /// voices are counted separately). If the limit is reached, a running voice
+    /// will be faded out according to the stealing policy. `None` means no limit.
+    pub fn set_voice_limit(&self, max_voices: Option<usize>, policy: VoiceStealingPolicy) {
+        self.control_q_send
+            .send(ControlMessage::SetVoiceLimit(max_voices, policy))
+            .unwrap();
+    }
+
+    /// Fetch the notifications the playhead sent since the last call
+    /// (late events, finished instances, completed freezes, etc).
+    /// Doesn't block, so it's fine to call it periodically from a UI or sequencer thread.
+    pub fn notifications(&self) -> impl Iterator<Item = PlayheadNotification> + '_ {
+        self.collect_garbage();
+        self.notification_q_rec.try_iter()
+    }
+
+    /// Free whatever the playhead doesn't need anymore (finished instances,
+    /// replaced sample buffers, etc.), so that doesn't happen in the audio thread.
+    /// This is called when preparing instances and fetching notifications, so you
+    /// only need to call it yourself if you do neither of those for a longer time.
+    /// If the garbage isn't collected, the playhead frees the memory itself once the
+    /// garbage queue is full.
+    pub fn collect_garbage(&self) {
+        for garbage in self.garbage_q_rec.try_iter() {
+            // once an instance is gone, there's no need to make copies for it anymore
+            match garbage {
+                Garbage::Instance(ref inst) => self.modulator_copies.remove(&inst.id),
+                Garbage::AmbiInstance(ref inst) => self.modulator_copies.remove(&inst.id),
+                Garbage::Event(ref ev) => self.modulator_copies.remove(&ev.id),
+                _ => None,
+            };
+            drop(garbage);
+        }
+    }
+
+    /// Per-channel peak, RMS and true peak of the last block processed
+    /// at the given point, plus the number of clipped samples since the last reset.
+    pub fn meter(&self, point: MeterPoint) -> [MeterReading; NCHAN] {
+        self.meters.reading(point)
+    }
+
+    /// the current gain reduction of the master dynamics (compressor and limiter), in dB
+    pub fn gain_reduction(&self) -> f32 {
+        self.meters.gain_reduction()
+    }
+
+    /// reset the clip counters of all meters
+    pub fn reset_clip_counters(&self) {
+        self.meters.reset_clips();
+    }
+
+    /// Start the tempo clock, that is, set the engine time at which beat 0 happens.
+    /// Pass `get_now()` (plus some latency) to start right away.
+    /// Pending beat-scheduled events are moved accordingly.
+    /// A non-finite time is ignored.
+    pub fn start_clock(&self, time: f64) {
+        if !time.is_finite() {
+            return;
+        }
+        let mut clock = self.tempo_clock.write();
+        clock.set_origin(time);
+        self.retime_pending_events(&clock);
+    }
+
+    /// like `start_clock`, but reports a non-finite time
+    pub fn try_start_clock(&self, time: f64) -> Result<(), RuffboxError> {
+        if !time.is_finite() {
+            return Err(RuffboxError::InvalidParameter("clock origin"));
+        }
+        self.start_clock(time);
+        Ok(())
+    }
+
+    /// Set the tempo from the given beat on. If `ramp` is set, the tempo
+    /// changes gradually from the previous tempo change to this one.
+    /// Pending events that were scheduled at a beat position are moved
+    /// accordingly, those moved into the past are played right away
+    /// (and reported as late). Non-finite values are ignored.
+    pub fn set_tempo(&self, beat: f64, bpm: f64, ramp: bool) {
+        if !beat.is_finite() || !bpm.is_finite() || bpm <= 0.0 {
+            return;
+        }
+        let mut clock = self.tempo_clock.write();
+        clock.set_tempo(beat, bpm, ramp);
+        self.retime_pending_events(&clock);
+    }
+
+    /// like `set_tempo`, but reports a non-finite beat and a tempo
+    /// that's not finite or zero or less
+    pub fn try_set_tempo(&self, beat: f64, bpm: f64, ramp: bool) -> Result<(), RuffboxError> {
+        if !beat.is_finite() {
+            return Err(RuffboxError::InvalidParameter("beat"));
+        }
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return Err(RuffboxError::InvalidParameter("tempo"));
+        }
+        self.set_tempo(beat, bpm, ramp);
+        Ok(())
+    }
+
+    // let the playhead recalculate the timestamps of the beat-scheduled events,
+    // the lock is held until the message is sent so the updates arrive in order
+    fn retime_pending_events(&self, clock: &TempoClock) {
+        self.control_q_send
+            .send(ControlMessage::RetimePendingEvents(clock.clone()))
+            .unwrap();
+    }
+
+    /// set the time signature from the given bar on
+    pub fn set_time_signature(&self, bar: usize, numerator: u32, denominator: u32) {
+        self.tempo_clock
+            .write()
+            .set_time_signature(bar, numerator, denominator);
+    }
+
+    /// a copy of the tempo clock, for sequencers that need many conversions at once
+    pub fn tempo_clock(&self) -> TempoClock {
+        self.tempo_clock.read().clone()
+    }
+
+    /// Replace the tempo clock altogether, pending beat-scheduled events are moved accordingly.
+    /// No need to check the clock here, `TempoClock` never takes a non-finite origin
+    /// or a tempo that's not finite or zero or less.
+    pub fn set_tempo_clock(&self, clock: TempoClock) {
+        let mut current = self.tempo_clock.write();
+        *current = clock;
+        self.retime_pending_events(&current);
+    }
+
+    /// convert a beat position to a timestamp
+    pub fn beat_to_time(&self, beat: f64) -> f64 {
+        self.tempo_clock.read().beat_to_time(beat)
+    }
+
+    /// convert a timestamp to a beat position
+    pub fn time_to_beat(&self, time: f64) -> f64 {
+        self.tempo_clock.read().time_to_beat(time)
+    }
+
+    /// the current beat position
+    pub fn get_beat(&self) -> f64 {
+        self.time_to_beat(self.get_now())
     }
 
     /// get the current timestamp
@@ -310,20 +1176,175 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
             .unwrap();
     }
 
+    fn check_freeze_buffers(&self, freezbuf: usize, inbuf: usize) -> Result<(), RuffboxError> {
+        if freezbuf >= self.num_freeze_buffers {
+            Err(RuffboxError::BufferIndexOutOfRange(freezbuf))
+        } else if inbuf >= self.num_live_buffers {
+            Err(RuffboxError::BufferIndexOutOfRange(inbuf))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// like `freeze_buffer`, but checks the buffer numbers and doesn't block
+    pub fn try_freeze_buffer(&self, freezbuf: usize, inbuf: usize) -> Result<(), RuffboxError> {
+        self.check_freeze_buffers(freezbuf, inbuf)?;
+        self.control_q_send.try_send(ControlMessage::FreezeBuffer(
+            freezbuf + self.freeze_buffer_offset,
+            inbuf,
+        ))?;
+        Ok(())
+    }
+
+    /// like `freeze_add_buffer`, but checks the buffer numbers and doesn't block
+    pub fn try_freeze_add_buffer(&self, freezbuf: usize, inbuf: usize) -> Result<(), RuffboxError> {
+        self.check_freeze_buffers(freezbuf, inbuf)?;
+        self.control_q_send
+            .try_send(ControlMessage::FreezeAddBuffer(
+                freezbuf + self.freeze_buffer_offset,
+                inbuf,
+            ))?;
+        Ok(())
+    }
+
+    /// like `freeze_after_rec`, but checks the buffer numbers and time and doesn't block
+    pub fn try_freeze_after_rec(
+        &self,
+        freezbuf: usize,
+        inbuf: usize,
+        time_secs: f64,
+        add: bool,
+    ) -> Result<(), RuffboxError> {
+        self.check_freeze_buffers(freezbuf, inbuf)?;
+        if !time_secs.is_finite() || time_secs < 0.0 {
+            return Err(RuffboxError::InvalidParameter("freeze time"));
+        }
+        let num_samples = (self.samplerate as f64 * time_secs).ceil() as usize;
+        self.control_q_send
+            .try_send(ControlMessage::FreezeAfterRec(
+                freezbuf + self.freeze_buffer_offset,
+                inbuf,
+                num_samples,
+                add,
+            ))?;
+        Ok(())
+    }
+
+    // the next free buffer number, if there's any left
+    fn reserve_buffer_id(&self) -> Result<usize, RuffboxError> {
+        self.buffer_counter
+            .fetch_update(|n| {
+                if n < self.max_buffers {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .map_err(|_| RuffboxError::MaxBuffersExhausted)
+    }
+
+    // hand back a buffer number that wasn't used after all,
+    // only possible if no other one has been reserved in the meantime
+    fn unreserve_buffer_id(&self, buffer_id: usize) {
+        let _ = self
+            .buffer_counter
+            .compare_exchange(buffer_id + 1, buffer_id);
+    }
+
     /// Loads a mono sample and returns the assigned buffer number.
     ///
     /// Resample to current samplerate if necessary and specified.
     /// The sample buffer is passed as mutable because the method adds
     /// interpolation samples without the need of a copy.
+    /// If the maximum number of buffers has been reached, nothing is loaded and
+    /// `max_buffers` is returned (`try_load_mono_sample` reports that as an error).
     pub fn load_mono_sample(&self, samples: &mut Vec<f32>, resample: bool, sr: f32) -> usize {
-        let buffer_id = self.buffer_counter.fetch_add(1);
+        let Ok(buffer_id) = self.reserve_buffer_id() else {
+            return self.max_buffers;
+        };
+
+        let (buflen, buffer) = self.prepare_mono_buffer(samples, resample, sr);
 
-        if buffer_id > self.max_buffers {
-            println!("warning, this buffer won't be loaded, as the maximum allowed number of buffers has been reached!");
-            return buffer_id;
+        self.buffer_lengths.insert(buffer_id, buflen);
+        self.buffer_types.insert(buffer_id, BufferType::Mono);
+        self.control_q_send
+            .send(ControlMessage::LoadSample(
+                buffer_id,
+                buflen,
+                SampleBuffer::Mono(buffer),
+            ))
+            .unwrap();
+        // return bufnum
+        buffer_id
+    }
+
+    /// Like `load_mono_sample`, but reports if the buffer can't be loaded.
+    /// The samples are left as they are. If the control queue is full, the buffer
+    /// number is handed back, unless another buffer has been loaded in the meantime.
+    pub fn try_load_mono_sample(
+        &self,
+        samples: &[f32],
+        resample: bool,
+        sr: f32,
+    ) -> Result<usize, RuffboxError> {
+        let buffer_id = self.reserve_buffer_id()?;
+        let (buflen, buffer) = self.prepare_mono_buffer(&mut samples.to_vec(), resample, sr);
+
+        if let Err(err) = self.control_q_send.try_send(ControlMessage::LoadSample(
+            buffer_id,
+            buflen,
+            SampleBuffer::Mono(buffer),
+        )) {
+            self.unreserve_buffer_id(buffer_id);
+            return Err(err.into());
         }
+        self.buffer_lengths.insert(buffer_id, buflen);
+        self.buffer_types.insert(buffer_id, BufferType::Mono);
+        Ok(buffer_id)
+    }
+
+    // encode a mono synth at the order of the ambisonic module
+    fn ambisonic_source(
+        &self,
+        synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>,
+    ) -> ScheduledSource<BUFSIZE, NCHAN> {
+        ScheduledSource::Ambi(match self.ambisonic_order.load() {
+            2 => Box::new(AmbisonicBusSynth::new(Box::new(EncodedSynthO2::new(
+                synth,
+                self.samplerate,
+            )))),
+            3 => Box::new(AmbisonicBusSynth::new(Box::new(EncodedSynthO3::new(
+                synth,
+                self.samplerate,
+            )))),
+            _ => Box::new(AmbisonicBusSynth::new(Box::new(EncodedSynthO1::new(
+                synth,
+                self.samplerate,
+            )))),
+        })
+    }
+
+    // pan a mono synth on the vbap speaker layout, if there is one
+    fn vbap_source(
+        &self,
+        synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>,
+    ) -> Option<ScheduledSource<BUFSIZE, NCHAN>> {
+        let layout = self.vbap_layout.as_ref()?;
+        Some(ScheduledSource::Channel(Box::new(VbapSynth::new(
+            synth,
+            Arc::clone(layout),
+        ))))
+    }
 
-        let (buflen, buffer) = if resample && (self.samplerate != sr) {
+    // add interpolation samples and resample if necessary, returns the length
+    // (without interpolation samples) and the buffer
+    fn prepare_mono_buffer(
+        &self,
+        samples: &mut Vec<f32>,
+        resample: bool,
+        sr: f32,
+    ) -> (usize, Vec<f32>) {
+        if resample && (self.samplerate != sr) {
             // zero-pad for resampling blocks
             if (samples.len() as f32 % 1024.0) > 0.0 {
                 let diff = 1024 - (samples.len() % 1024);
@@ -354,49 +1375,96 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
             samples.push(0.0);
             samples.push(0.0);
             (samples.len() - 4, samples.to_vec())
+        }
+    }
+
+    /// Loads a stereo sample and returns the assigned buffer number.
+    ///
+    /// Resample to current samplerate if necessary and specified.
+    /// The sample buffer is passed as mutable because the method adds
+    /// interpolation samples without the need of a copy.
+    /// If the maximum number of buffers has been reached, nothing is loaded and
+    /// `max_buffers` is returned (`try_load_stereo_sample` reports that as an error).
+    pub fn load_stereo_sample(
+        &self,
+        samples_left: &mut Vec<f32>,
+        samples_right: &mut Vec<f32>,
+        resample: bool,
+        sr: f32,
+    ) -> usize {
+        let Ok(buffer_id) = self.reserve_buffer_id() else {
+            return self.max_buffers;
         };
 
+        let (buflen, buffer_left, buffer_right) =
+            self.prepare_stereo_buffer(samples_left, samples_right, resample, sr);
+
         self.buffer_lengths.insert(buffer_id, buflen);
-        self.buffer_types.insert(buffer_id, BufferType::Mono);
+        self.buffer_types.insert(buffer_id, BufferType::Stereo);
         self.control_q_send
             .send(ControlMessage::LoadSample(
                 buffer_id,
                 buflen,
-                SampleBuffer::Mono(buffer),
+                SampleBuffer::Stereo(buffer_left, buffer_right),
             ))
             .unwrap();
         // return bufnum
         buffer_id
     }
 
-    /// Loads a stereo sample and returns the assigned buffer number.
-    ///
-    /// Resample to current samplerate if necessary and specified.
-    /// The sample buffer is passed as mutable because the method adds
-    /// interpolation samples without the need of a copy.
-    pub fn load_stereo_sample(
+    /// Like `load_stereo_sample`, but reports if the buffer can't be loaded.
+    /// The samples are left as they are. If the control queue is full, the buffer
+    /// number is handed back, unless another buffer has been loaded in the meantime.
+    pub fn try_load_stereo_sample(
         &self,
-        samples_left: &mut Vec<f32>,
-        samples_right: &mut Vec<f32>,
+        samples_left: &[f32],
+        samples_right: &[f32],
         resample: bool,
         sr: f32,
-    ) -> usize {
-        let buffer_id = self.buffer_counter.fetch_add(1);
+    ) -> Result<usize, RuffboxError> {
+        let buffer_id = self.reserve_buffer_id()?;
+        let (buflen, buffer_left, buffer_right) = self.prepare_stereo_buffer(
+            &mut samples_left.to_vec(),
+            &mut samples_right.to_vec(),
+            resample,
+            sr,
+        );
 
-        if buffer_id > self.max_buffers {
-            println!("warning, this buffer won't be loaded, as the maximum allowed number of buffers has been reached!");
-            return buffer_id;
+        if let Err(err) = self.control_q_send.try_send(ControlMessage::LoadSample(
+            buffer_id,
+            buflen,
+            SampleBuffer::Stereo(buffer_left, buffer_right),
+        )) {
+            self.unreserve_buffer_id(buffer_id);
+            return Err(err.into());
         }
+        self.buffer_lengths.insert(buffer_id, buflen);
+        self.buffer_types.insert(buffer_id, BufferType::Stereo);
+        Ok(buffer_id)
+    }
 
+    // add interpolation samples and resample if necessary, returns the length
+    // (without interpolation samples) and the buffers
+    fn prepare_stereo_buffer(
+        &self,
+        samples_left: &mut Vec<f32>,
+        samples_right: &mut Vec<f32>,
+        resample: bool,
+        sr: f32,
+    ) -> (usize, Vec<f32>, Vec<f32>) {
+        // both channels need the same length
         if samples_right.len() < samples_left.len() {
             samples_right.append(&mut vec![0.0; samples_left.len() - samples_right.len()]);
+        } else if samples_left.len() < samples_right.len() {
+            samples_left.append(&mut vec![0.0; samples_right.len() - samples_left.len()]);
         }
 
-        let (buflen, buffer_left, buffer_right) = if resample && (self.samplerate != sr) {
+        if resample && (self.samplerate != sr) {
             // zero-pad for resampling blocks
             if (samples_left.len() as f32 % 1024.0) > 0.0 {
                 let diff = 1024 - (samples_left.len() % 1024);
                 samples_left.append(&mut vec![0.0; diff]);
+                samples_right.append(&mut vec![0.0; diff]);
             }
 
             let mut samples_left_resampled: Vec<f32> = Vec::new();
@@ -418,7 +1486,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                 let chunk_left = vec![samples_left[(1024 * chunk)..(1024 * (chunk + 1))].to_vec()];
                 let mut waves_out_left = resampler_left.process(&chunk_left).unwrap();
                 samples_left_resampled.append(&mut waves_out_left[0]);
-                let chunk_right = vec![samples_left[(1024 * chunk)..(1024 * (chunk + 1))].to_vec()];
+                let chunk_right =
+                    vec![samples_right[(1024 * chunk)..(1024 * (chunk + 1))].to_vec()];
                 let mut waves_out_right = resampler_right.process(&chunk_right).unwrap();
                 samples_right_resampled.append(&mut waves_out_right[0]);
             }
@@ -447,18 +1516,6 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxControls<BUFSIZE, NCHAN> {
                 samples_left.to_vec(),
                 samples_right.to_vec(),
             )
-        };
-
-        self.buffer_lengths.insert(buffer_id, buflen);
-        self.buffer_types.insert(buffer_id, BufferType::Stereo);
-        self.control_q_send
-            .send(ControlMessage::LoadSample(
-                buffer_id,
-                buflen,
-                SampleBuffer::Stereo(buffer_left, buffer_right),
-            ))
-            .unwrap();
-        // return bufnum
-        buffer_id
+        }
     }
 }
@@ -0,0 +1,47 @@
+use crossbeam::channel::TrySendError;
+
+/// The reasons why a control operation might fail.
+/// Returned by the `try_*` methods of the controls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuffboxError {
+    QueueFull,                    // the control queue is full, the playhead can't keep up
+    Disconnected,                 // the playhead has been dropped
+    BufferIndexOutOfRange(usize), // the buffer doesn't exist
+    MaxBuffersExhausted,          // no more sample buffers can be loaded
+    InvalidParameter(&'static str),
+    AmbisonicsDisabled, // ambisonic sources need the ambisonic module
+    InvalidConfig(&'static str),
+    InvalidFilterFile(&'static str), // binaural filters that can't be loaded
+    VbapDisabled,                    // vbap sources need a speaker layout
+}
+
+impl std::fmt::Display for RuffboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuffboxError::QueueFull => write!(f, "control queue is full"),
+            RuffboxError::Disconnected => write!(f, "playhead is gone"),
+            RuffboxError::BufferIndexOutOfRange(idx) => {
+                write!(f, "buffer index {idx} out of range")
+            }
+            RuffboxError::MaxBuffersExhausted => {
+                write!(f, "maximum number of buffers has been reached")
+            }
+            RuffboxError::InvalidParameter(par) => write!(f, "invalid parameter: {par}"),
+            RuffboxError::AmbisonicsDisabled => write!(f, "ambisonics are not enabled"),
+            RuffboxError::InvalidConfig(reason) => write!(f, "invalid configuration: {reason}"),
+            RuffboxError::InvalidFilterFile(reason) => write!(f, "invalid filter file: {reason}"),
+            RuffboxError::VbapDisabled => write!(f, "no vbap speaker layout"),
+        }
+    }
+}
+
+impl std::error::Error for RuffboxError {}
+
+impl<T> From<TrySendError<T>> for RuffboxError {
+    fn from(err: TrySendError<T>) -> Self {
+        match err {
+            TrySendError::Full(_) => RuffboxError::QueueFull,
+            TrySendError::Disconnected(_) => RuffboxError::Disconnected,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crossbeam::atomic::AtomicCell;
+
+/// the points in the signal chain that are metered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeterPoint {
+    Master, // the final output, after the master dynamics
+    Reverb, // the master reverb return
+    Delay,  // the master delay return
+}
+
+impl MeterPoint {
+    fn index(&self) -> usize {
+        match self {
+            MeterPoint::Master => 0,
+            MeterPoint::Reverb => 1,
+            MeterPoint::Delay => 2,
+        }
+    }
+}
+
+/// The measurements of a single channel, for the last block
+/// (all values linear, not in dB).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeterReading {
+    pub peak: f32,
+    pub rms: f32,
+    pub true_peak: f32, // estimated with 4x oversampling
+    pub clips: usize,   // samples above full scale, since the last reset
+}
+
+struct ChannelMeter {
+    peak: AtomicCell<f32>,
+    rms: AtomicCell<f32>,
+    true_peak: AtomicCell<f32>,
+    clips: AtomicCell<usize>,
+}
+
+impl ChannelMeter {
+    fn new() -> Self {
+        ChannelMeter {
+            peak: AtomicCell::new(0.0),
+            rms: AtomicCell::new(0.0),
+            true_peak: AtomicCell::new(0.0),
+            clips: AtomicCell::new(0),
+        }
+    }
+
+    fn reading(&self) -> MeterReading {
+        MeterReading {
+            peak: self.peak.load(),
+            rms: self.rms.load(),
+            true_peak: self.true_peak.load(),
+            clips: self.clips.load(),
+        }
+    }
+}
+
+/// The meter values, written by the playhead once per block
+/// and read by the controls. Only atomics, so no locking.
+pub(crate) struct SharedMeters<const NCHAN: usize> {
+    points: [[ChannelMeter; NCHAN]; 3],
+    gain_reduction: AtomicCell<f32>,
+}
+
+impl<const NCHAN: usize> SharedMeters<NCHAN> {
+    pub(crate) fn new() -> Self {
+        SharedMeters {
+            points: std::array::from_fn(|_| std::array::from_fn(|_| ChannelMeter::new())),
+            gain_reduction: AtomicCell::new(0.0),
+        }
+    }
+
+    pub(crate) fn reading(&self, point: MeterPoint) -> [MeterReading; NCHAN] {
+        std::array::from_fn(|c| self.points[point.index()][c].reading())
+    }
+
+    pub(crate) fn gain_reduction(&self) -> f32 {
+        self.gain_reduction.load()
+    }
+
+    pub(crate) fn set_gain_reduction(&self, gr: f32) {
+        self.gain_reduction.store(gr);
+    }
+
+    pub(crate) fn reset_clips(&self) {
+        for point in self.points.iter() {
+            for chan in point.iter() {
+                chan.clips.store(0);
+            }
+        }
+    }
+}
+
+// 4-point hermite interpolation between y1 and y2
+fn hermite(frac: f32, y0: f32, y1: f32, y2: f32, y3: f32) -> f32 {
+    let c1 = 0.5 * (y2 - y0);
+    let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+    let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+    ((c3 * frac + c2) * frac + c1) * frac + y1
+}
+
+/// The playhead side of the meter, keeps the history needed
+/// to estimate the inter-sample peaks across block boundaries.
+pub(crate) struct BlockMeter<const BUFSIZE: usize, const NCHAN: usize> {
+    point: MeterPoint,
+    history: [[f32; 3]; NCHAN],
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> BlockMeter<BUFSIZE, NCHAN> {
+    pub(crate) fn new(point: MeterPoint) -> Self {
+        BlockMeter {
+            point,
+            history: [[0.0; 3]; NCHAN],
+        }
+    }
+
+    pub(crate) fn measure(
+        &mut self,
+        block: &[[f32; BUFSIZE]; NCHAN],
+        meters: &SharedMeters<NCHAN>,
+    ) {
+        for c in 0..NCHAN {
+            let mut peak: f32 = 0.0;
+            let mut sum: f32 = 0.0;
+            let mut clips = 0;
+
+            // the true peak lags behind one sample, as we
+            // need one sample of "future" for the interpolation
+            let [mut y0, mut y1, mut y2] = self.history[c];
+            let mut true_peak = y2.abs();
+
+            for s in 0..BUFSIZE {
+                let y3 = block[c][s];
+                let abs = y3.abs();
+                peak = peak.max(abs);
+                sum += y3 * y3;
+                if abs > 1.0 {
+                    clips += 1;
+                }
+
+                // the three points between y1 and y2
+                for frac in [0.25, 0.5, 0.75] {
+                    true_peak = true_peak.max(hermite(frac, y0, y1, y2, y3).abs());
+                }
+                true_peak = true_peak.max(y2.abs());
+
+                y0 = y1;
+                y1 = y2;
+                y2 = y3;
+            }
+
+            self.history[c] = [y0, y1, y2];
+
+            let meter = &meters.points[self.point.index()][c];
+            meter.peak.store(peak);
+            meter.rms.store((sum / BUFSIZE as f32).sqrt());
+            meter.true_peak.store(true_peak.max(peak));
+            if clips > 0 {
+                meter.clips.fetch_add(clips);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_meter() {
+        let meters = SharedMeters::<1>::new();
+        let mut meter = BlockMeter::<128, 1>::new(MeterPoint::Master);
+
+        // a sine at a quarter of the samplerate, sampled at 45 degrees
+        // phase offset, so the sample peaks are below the actual peak
+        let mut block = [[0.0; 128]; 1];
+        for (s, sample) in block[0].iter_mut().enumerate() {
+            *sample = (std::f32::consts::FRAC_PI_2 * s as f32 + std::f32::consts::FRAC_PI_4).sin();
+        }
+
+        meter.measure(&block, &meters);
+        meter.measure(&block, &meters);
+
+        let reading = meters.reading(MeterPoint::Master)[0];
+        assert!((reading.peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001);
+        assert!((reading.rms - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.001);
+        assert!(reading.true_peak > reading.peak);
+        assert_eq!(reading.clips, 0);
+
+        // overload
+        meter.measure(&[[1.5; 128]; 1], &meters);
+        assert_eq!(meters.reading(MeterPoint::Master)[0].clips, 128);
+        meters.reset_clips();
+        assert_eq!(meters.reading(MeterPoint::Master)[0].clips, 0);
+    }
+}
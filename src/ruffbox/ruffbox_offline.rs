@@ -0,0 +1,109 @@
+use crate::building_blocks::{SynthParameterAddress, SynthParameterValue};
+use crate::ruffbox::{RuffboxControls, RuffboxPlayhead};
+use crate::synths::SynthType;
+
+/// an event in a score to be rendered offline
+pub struct ScoreEvent {
+    pub timestamp: f64, // seconds, relative to the beginning of the rendering
+    pub synth_type: SynthType,
+    pub sample_buffer: usize, // only needed for samplers
+    pub parameters: Vec<(SynthParameterAddress, SynthParameterValue)>,
+}
+
+impl ScoreEvent {
+    pub fn new(timestamp: f64, synth_type: SynthType) -> Self {
+        ScoreEvent {
+            timestamp,
+            synth_type,
+            sample_buffer: 0,
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_sample_buffer(mut self, sample_buffer: usize) -> Self {
+        self.sample_buffer = sample_buffer;
+        self
+    }
+
+    pub fn with_parameter(mut self, par: SynthParameterAddress, val: SynthParameterValue) -> Self {
+        self.parameters.push((par, val));
+        self
+    }
+}
+
+/// the result of an offline rendering
+pub struct OfflineRendering {
+    pub channels: Vec<Vec<f32>>,
+    /// events that couldn't be played (non-finite timestamp, missing sample buffer, etc.)
+    pub dropped_events: usize,
+}
+
+/// Render a score faster than realtime, without any audio device.
+///
+/// Drives the playhead block by block, with internal time tracking, and triggers each
+/// event right before the block it belongs to. If there are more events in a block
+/// than fit into the control queue, the block is rendered with the ones that fit,
+/// the rest are played late (and reported as such).
+/// Load samples beforehand if the score needs them.
+///
+/// The result is `duration + tail` seconds long, the tail leaves room for the
+/// reverb and delay tails (and long envelopes) after the last event.
+/// Events at or after `duration` are ignored.
+pub fn render_offline<const BUFSIZE: usize, const NCHAN: usize>(
+    controls: &RuffboxControls<BUFSIZE, NCHAN>,
+    playhead: &mut RuffboxPlayhead<BUFSIZE, NCHAN>,
+    mut events: Vec<ScoreEvent>,
+    duration: f64,
+    tail: f64,
+) -> OfflineRendering {
+    let samplerate = controls.samplerate as f64;
+    let num_samples = ((duration + tail) * samplerate).ceil() as usize;
+    let block_duration = BUFSIZE as f64 / samplerate;
+    let start = controls.get_now();
+
+    let num_events = events.len();
+    events.retain(|ev| ev.timestamp.is_finite());
+    let mut dropped_events = num_events - events.len();
+
+    // latest first, so we can just pop the next event
+    events.sort_by(|a, b| b.timestamp.total_cmp(&a.timestamp));
+
+    let mut out: Vec<Vec<f32>> = vec![Vec::with_capacity(num_samples); NCHAN];
+
+    while out[0].len() < num_samples {
+        let block_end = controls.get_now() + block_duration;
+
+        // if the queue is full, the playhead needs to process a block first
+        while !controls.control_queue_full()
+            && events
+                .last()
+                .is_some_and(|ev| ev.timestamp < duration && start + ev.timestamp < block_end)
+        {
+            let ev = events.pop().unwrap();
+            match controls.prepare_instance(ev.synth_type, start + ev.timestamp, ev.sample_buffer) {
+                Some(mut inst) => {
+                    for (par, val) in ev.parameters.iter() {
+                        inst.set_instance_parameter(*par, val);
+                    }
+                    // only fails if someone else filled the queue in the meantime
+                    if controls.try_trigger(inst).is_err() {
+                        dropped_events += 1;
+                    }
+                }
+                None => dropped_events += 1,
+            }
+        }
+
+        let block = playhead.process(0.0, true);
+
+        let remaining = num_samples - out[0].len();
+        for c in 0..NCHAN {
+            out[c].extend_from_slice(&block[c][..BUFSIZE.min(remaining)]);
+        }
+    }
+
+    OfflineRendering {
+        channels: out,
+        dropped_events,
+    }
+}
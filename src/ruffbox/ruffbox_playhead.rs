@@ -2,18 +2,36 @@ use rubato::{FftFixedIn, Resampler};
 
 // crossbeam for the event queue
 use crossbeam::atomic::AtomicCell;
+use crossbeam::channel::TrySendError;
 
+use std::mem::Discriminant;
 use std::sync::Arc;
 
-use crate::building_blocks::ambisonics::binauralizer_o1::BinauralizerO1;
+use crate::building_blocks::ambisonics::binauralizer::Binauralizer;
+use crate::building_blocks::ambisonics::decoder::AmbisonicDecoder;
+use crate::building_blocks::ambisonics::rotator::{is_rotation_parameter, AmbisonicRotator};
+use crate::building_blocks::ambisonics::{
+    ambisonic_channels, ambisonic_order, DecoderWeighting, MAX_AMBISONIC_CHANNELS,
+    MAX_AMBISONIC_ORDER,
+};
 use crate::building_blocks::delay::MultichannelDelay;
+use crate::building_blocks::dynamics::{MultichannelCompressor, MultichannelLimiter};
 use crate::building_blocks::reverb::convolution::MultichannelConvolutionReverb;
 use crate::building_blocks::reverb::freeverb::MultichannelFreeverb;
-use crate::building_blocks::{MultichannelReverb, SampleBuffer, Synth};
-
-use crate::ruffbox::{ControlMessage, ReverbMode, ScheduledEvent};
+use crate::building_blocks::reverb::AmbisonicReverb;
+use crate::building_blocks::{
+    Modulator, MultichannelReverb, SampleBuffer, Synth, SynthParameterAddress, SynthParameterLabel,
+    ValueOrModulator,
+};
+
+use crate::ruffbox::{
+    set_modulator_with_aux_sends, set_parameter_with_aux_sends, AuxBusType, BlockMeter,
+    ControlMessage, Garbage, MeterPoint, PlayheadNotification, ReverbMode, ScheduledEvent,
+    SharedMeters, VoiceStealingPolicy, MAX_AUX_BUSES, NUM_GROUPS,
+};
 
 use crate::ruffbox::ScheduledSource;
+use crate::synths::SynthType;
 
 pub(crate) struct FreezeAfterRec {
     freeze_buffer_number: usize, // freeze to after recording
@@ -31,37 +49,515 @@ pub(crate) struct LiveBufferMetadata<const BUFSIZE: usize> {
     freeze_after_recs: Vec<FreezeAfterRec>,
 }
 
-/// ambisonic binaural module (order 1 for now)
+/// create a reverb according to the reverb mode, used for the
+/// master reverb as well as for reverbs on aux buses
+pub(crate) fn build_reverb<const BUFSIZE: usize, const NCHAN: usize>(
+    reverb_mode: &ReverbMode,
+    samplerate: f64,
+) -> Box<dyn MultichannelReverb<BUFSIZE, NCHAN> + Send + Sync> {
+    match reverb_mode {
+        ReverbMode::FreeVerb => {
+            let mut mrev = MultichannelFreeverb::new(samplerate as f32);
+            // tweak some reverb values for freeverb
+            mrev.set_roomsize(0.65);
+            mrev.set_damp(0.43);
+            mrev.set_wet(1.0);
+            Box::new(mrev)
+        }
+        ReverbMode::Convolution(ir, sr) => {
+            let mut ir_clone = ir.clone();
+            // resample IR if needed ...
+            if *sr as f64 != samplerate {
+                // zero-pad for resampling blocks
+                if (ir.len() as f32 % 1024.0) > 0.0 {
+                    let diff = 1024 - (ir.len() % 1024);
+                    ir_clone.append(&mut vec![0.0; diff]);
+                }
+
+                let mut ir_resampled: Vec<f32> = Vec::new();
+                let mut resampler =
+                    FftFixedIn::<f32>::new(*sr as usize, samplerate as usize, 1024, 1, 1);
+
+                let num_chunks = ir.len() / 1024;
+                for chunk in 0..num_chunks {
+                    let chunk = vec![ir_clone[(1024 * chunk)..(1024 * (chunk + 1))].to_vec()];
+                    let mut waves_out = resampler.process(&chunk).unwrap();
+                    ir_resampled.append(&mut waves_out[0]);
+                }
+                Box::new(MultichannelConvolutionReverb::with_ir(&ir_resampled))
+            } else {
+                Box::new(MultichannelConvolutionReverb::with_ir(ir))
+            }
+        }
+    }
+}
+
+/// an effect on an additional aux bus
+pub(crate) enum AuxBus<const BUFSIZE: usize, const NCHAN: usize> {
+    Reverb(Box<dyn MultichannelReverb<BUFSIZE, NCHAN> + Send + Sync>),
+    Delay(MultichannelDelay<BUFSIZE, NCHAN>),
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> AuxBus<BUFSIZE, NCHAN> {
+    pub(crate) fn new(bus_type: &AuxBusType, samplerate: f64) -> Self {
+        match bus_type {
+            AuxBusType::Reverb(reverb_mode) => {
+                AuxBus::Reverb(build_reverb(reverb_mode, samplerate))
+            }
+            AuxBusType::Delay => AuxBus::Delay(MultichannelDelay::new(samplerate as f32)),
+        }
+    }
+
+    fn set_param_or_modulator(
+        &mut self,
+        par: SynthParameterLabel,
+        val_or_mod: ValueOrModulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+    ) {
+        match self {
+            AuxBus::Reverb(rev) => rev.set_param_or_modulator(par, val_or_mod, leftover),
+            AuxBus::Delay(del) => del.set_param_or_modulator(par, val_or_mod, copies, leftover),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            AuxBus::Reverb(rev) => rev.clear(),
+            AuxBus::Delay(del) => del.clear(),
+        }
+    }
+
+    fn process(
+        &mut self,
+        block: [[f32; BUFSIZE]; NCHAN],
+        sample_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; NCHAN] {
+        match self {
+            AuxBus::Reverb(rev) => rev.process(block),
+            AuxBus::Delay(del) => del.process(block, sample_buffers),
+        }
+    }
+}
+
+/// a master parameter change that's waiting for its time to come
+struct ScheduledMasterChange<const BUFSIZE: usize> {
+    timestamp: f64,
+    par: SynthParameterLabel,
+    val: ValueOrModulator<BUFSIZE>,
+    copies: Vec<Modulator<BUFSIZE>>,
+}
+
+/// A group of instances, mixed into its own sub-bus.
+/// Gain, mute and solo changes are smoothed to avoid clicks.
+pub(crate) struct InstanceGroup<const BUFSIZE: usize, const NCHAN: usize> {
+    gain: f32,
+    mute: bool,
+    solo: bool,
+    current_gain: f32,
+    smoothing_coef: f32,
+    gain_block: [f32; BUFSIZE], // per-sample gain for the current block
+    bus: [[f32; BUFSIZE]; NCHAN],
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> InstanceGroup<BUFSIZE, NCHAN> {
+    fn new(samplerate: f32) -> Self {
+        InstanceGroup {
+            gain: 1.0,
+            mute: false,
+            solo: false,
+            current_gain: 1.0,
+            smoothing_coef: (-1.0 / (0.005 * samplerate)).exp(), // 5ms
+            gain_block: [1.0; BUFSIZE],
+            bus: [[0.0; BUFSIZE]; NCHAN],
+        }
+    }
+
+    /// calculate the gain for the next block and clear the bus
+    fn next_block(&mut self, any_solo: bool) {
+        let target = if self.mute || (any_solo && !self.solo) {
+            0.0
+        } else {
+            self.gain
+        };
+
+        if self.current_gain == target {
+            self.gain_block = [target; BUFSIZE];
+        } else {
+            for s in 0..BUFSIZE {
+                self.current_gain = target + (self.current_gain - target) * self.smoothing_coef;
+                self.gain_block[s] = self.current_gain;
+            }
+            // close enough ...
+            if (self.current_gain - target).abs() < 0.00001 {
+                self.current_gain = target;
+            }
+        }
+
+        self.bus = [[0.0; BUFSIZE]; NCHAN];
+    }
+
+    fn apply_gain<const CHAN: usize>(&self, block: &mut [[f32; BUFSIZE]; CHAN]) {
+        self.apply_gain_to_channels(block, CHAN);
+    }
+
+    // for the ambisonic bus, where the channels above the order are unused
+    fn apply_gain_to_channels<const CHAN: usize>(
+        &self,
+        block: &mut [[f32; BUFSIZE]; CHAN],
+        channels: usize,
+    ) {
+        for channel in block.iter_mut().take(channels) {
+            for s in 0..BUFSIZE {
+                channel[s] *= self.gain_block[s];
+            }
+        }
+    }
+}
+
+/// a running synth, along with the id it can be addressed with
+/// and some info needed for voice stealing
+pub(crate) struct RunningInstance<const BUFSIZE: usize, const NCHAN: usize> {
+    pub(crate) id: usize, // crate public for test
+    synth_type: Discriminant<SynthType>,
+    synth: Box<dyn Synth<BUFSIZE, NCHAN> + Send + Sync>,
+    aux_sends: Vec<f32>,
+    group: usize,
+    level: f32,     // peak level of the last block
+    fade_gain: f32, // fade-out for stolen voices
+    fade_dec: f32,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> RunningInstance<BUFSIZE, NCHAN> {
+    fn new(
+        id: usize,
+        synth_type: Discriminant<SynthType>,
+        synth: Box<dyn Synth<BUFSIZE, NCHAN> + Send + Sync>,
+        aux_sends: Vec<f32>,
+        group: usize,
+    ) -> Self {
+        RunningInstance {
+            id,
+            synth_type,
+            synth,
+            aux_sends,
+            group,
+            level: 0.0,
+            fade_gain: 1.0,
+            fade_dec: 0.0,
+        }
+    }
+
+    fn set_param_or_modulator(
+        &mut self,
+        par: SynthParameterAddress,
+        val_or_mod: ValueOrModulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+    ) {
+        match val_or_mod {
+            ValueOrModulator::Val(val) => {
+                set_parameter_with_aux_sends(self.synth.as_mut(), &mut self.aux_sends, par, &val);
+                leftover(ValueOrModulator::Val(val));
+            }
+            ValueOrModulator::Mod(init, modulator) => set_modulator_with_aux_sends(
+                self.synth.as_mut(),
+                par,
+                init,
+                modulator,
+                copies,
+                leftover,
+            ),
+        }
+    }
+
+    fn fade_out(&mut self, fade_samples: usize) {
+        if !self.is_fading() {
+            self.fade_dec = self.fade_gain / fade_samples.max(1) as f32;
+        }
+    }
+
+    fn is_fading(&self) -> bool {
+        self.fade_dec > 0.0
+    }
+
+    fn is_finished(&self) -> bool {
+        self.synth.is_finished() || (self.is_fading() && self.fade_gain <= 0.0)
+    }
+
+    fn get_next_block(
+        &mut self,
+        start_sample: usize,
+        sample_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; NCHAN] {
+        let mut block = self.synth.get_next_block(start_sample, sample_buffers);
+
+        if self.is_fading() {
+            for s in start_sample..BUFSIZE {
+                for c in 0..NCHAN {
+                    block[c][s] *= self.fade_gain;
+                }
+                self.fade_gain = (self.fade_gain - self.fade_dec).max(0.0);
+            }
+        }
+
+        self.level = 0.0;
+        for c in 0..NCHAN {
+            for s in start_sample..BUFSIZE {
+                self.level = self.level.max(block[c][s].abs());
+            }
+        }
+
+        block
+    }
+}
+
+/// Push to a vector without ever growing it, so nothing gets allocated
+/// on the audio thread. If there's no room left, the item is handed back.
+fn push_within_capacity<T>(vec: &mut Vec<T>, item: T) -> Result<(), T> {
+    if vec.len() < vec.capacity() {
+        vec.push(item);
+        Ok(())
+    } else {
+        Err(item)
+    }
+}
+
+/// Two different buffers at once, to copy from one to the other.
+/// `None` if they're the same or one of them doesn't exist.
+fn buffer_pair(
+    buffers: &mut [SampleBuffer],
+    a: usize,
+    b: usize,
+) -> Option<(&mut SampleBuffer, &mut SampleBuffer)> {
+    if a == b || a.max(b) >= buffers.len() {
+        return None;
+    }
+    if a < b {
+        let (lower, upper) = buffers.split_at_mut(b);
+        Some((&mut lower[a], &mut upper[0]))
+    } else {
+        let (lower, upper) = buffers.split_at_mut(a);
+        Some((&mut upper[0], &mut lower[b]))
+    }
+}
+
+/// Sends the garbage to the control side, to be freed there. If the garbage
+/// queue is full because the garbage hasn't been collected in a while, it's
+/// kept here and sent later on, so it doesn't have to be freed in the audio thread.
+/// Only if this is full as well, there's no way around that.
+pub(crate) struct GarbageDisposal<const BUFSIZE: usize, const NCHAN: usize> {
+    queue: crossbeam::channel::Sender<Garbage<BUFSIZE, NCHAN>>,
+    overflow: Vec<Garbage<BUFSIZE, NCHAN>>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> GarbageDisposal<BUFSIZE, NCHAN> {
+    fn new(queue: crossbeam::channel::Sender<Garbage<BUFSIZE, NCHAN>>) -> Self {
+        // as much room as there is in the queue
+        let overflow = Vec::with_capacity(queue.capacity().unwrap_or(0));
+        GarbageDisposal { queue, overflow }
+    }
+
+    fn dispose(&mut self, garbage: Garbage<BUFSIZE, NCHAN>) {
+        if let Err(TrySendError::Full(garbage)) = self.queue.try_send(garbage) {
+            let _ = push_within_capacity(&mut self.overflow, garbage);
+        }
+    }
+
+    /// hand over whatever didn't fit into the queue before
+    fn retry(&mut self) {
+        while let Some(garbage) = self.overflow.pop() {
+            if let Err(TrySendError::Full(garbage)) = self.queue.try_send(garbage) {
+                self.overflow.push(garbage);
+                break;
+            }
+        }
+    }
+
+    /// use for things that have been set and might
+    /// need to be freed, i.e. values and modulators
+    fn leftover(&mut self) -> impl FnMut(ValueOrModulator<BUFSIZE>) + '_ {
+        |val_or_mod| self.dispose(Garbage::Value(val_or_mod))
+    }
+
+    /// the copies of a modulator that weren't needed, if there were any
+    fn dispose_copies(&mut self, copies: Vec<Modulator<BUFSIZE>>) {
+        if copies.capacity() > 0 {
+            self.dispose(Garbage::ModulatorCopies(copies));
+        }
+    }
+}
+
+/// Remove the pending events matching the predicate,
+/// and send them to the control side to be freed there.
+fn flush_events<const BUFSIZE: usize, const NCHAN: usize>(
+    pending_events: &mut Vec<ScheduledEvent<BUFSIZE, NCHAN>>,
+    predicate: impl Fn(&ScheduledEvent<BUFSIZE, NCHAN>) -> bool,
+    garbage: &mut GarbageDisposal<BUFSIZE, NCHAN>,
+) {
+    let mut i = 0;
+    while i < pending_events.len() {
+        if predicate(&pending_events[i]) {
+            let ev = pending_events.swap_remove(i);
+            garbage.dispose(Garbage::Event(ev));
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Make room for a new voice, if the voice limit is reached,
+/// by fading out running voices according to the stealing policy.
+fn steal_voices<const BUFSIZE: usize, const NCHAN: usize>(
+    instances: &mut [RunningInstance<BUFSIZE, NCHAN>],
+    max_voices: usize,
+    policy: VoiceStealingPolicy,
+    synth_type: Discriminant<SynthType>,
+    fade_samples: usize,
+) {
+    let active = instances.iter().filter(|i| !i.is_fading()).count();
+    if active < max_voices {
+        return;
+    }
+
+    // might be more than one in case the limit has been lowered
+    for _ in 0..(active + 1 - max_voices) {
+        let victim = match policy {
+            VoiceStealingPolicy::Oldest => instances.iter_mut().find(|i| !i.is_fading()),
+            VoiceStealingPolicy::Quietest => instances
+                .iter_mut()
+                .filter(|i| !i.is_fading())
+                .min_by(|a, b| a.level.total_cmp(&b.level)),
+            VoiceStealingPolicy::SameSynthTypeFirst => {
+                if instances
+                    .iter()
+                    .any(|i| !i.is_fading() && i.synth_type == synth_type)
+                {
+                    instances
+                        .iter_mut()
+                        .find(|i| !i.is_fading() && i.synth_type == synth_type)
+                } else {
+                    instances.iter_mut().find(|i| !i.is_fading())
+                }
+            }
+        };
+
+        if let Some(v) = victim {
+            v.fade_out(fade_samples);
+        }
+    }
+}
+
+// the sound field rotation takes modulators as well, the replaced
+// ones (or all of them, without ambisonics) are dropped elsewhere
+fn set_ambisonic_rotation_modulator<const BUFSIZE: usize, const NCHAN: usize>(
+    ambi_module: Option<&mut AmbisonicBinaural<BUFSIZE, NCHAN>>,
+    garbage: &mut GarbageDisposal<BUFSIZE, NCHAN>,
+    par: SynthParameterLabel,
+    init: f32,
+    modulator: Modulator<BUFSIZE>,
+) {
+    let old = if let Some(ambi_module) = ambi_module {
+        ambi_module.rotator.set_modulator(par, init, modulator)
+    } else {
+        Some(modulator)
+    };
+    if let Some(old) = old {
+        garbage.dispose(Garbage::Value(ValueOrModulator::Mod(init, old)));
+    }
+}
+
+// how the ambisonic bus is rendered to the output channels
+enum AmbisonicOutput<const BUFSIZE: usize, const NCHAN: usize> {
+    Binaural(Box<Binauralizer<BUFSIZE, MAX_AMBISONIC_CHANNELS>>), // boxed to be swapped at runtime
+    Speakers(AmbisonicDecoder<BUFSIZE, MAX_AMBISONIC_CHANNELS, NCHAN>),
+}
+
+/// ambisonic module (up to third order), rendered either binaurally
+/// or to a loudspeaker array
 pub struct AmbisonicBinaural<const BUFSIZE: usize, const NCHAN: usize> {
-    running_instances: Vec<Box<dyn Synth<BUFSIZE, 4> + Send + Sync>>, // first order ambisonic sources
+    pub(crate) running_instances: Vec<RunningInstance<BUFSIZE, MAX_AMBISONIC_CHANNELS>>, // crate public for test
     // has to be n-channel unfotunately ..
     pending_events: Vec<ScheduledEvent<BUFSIZE, NCHAN>>,
-    // this has to do until I manage to implement a proper ambisonic reverb ...
-    binauralizer: BinauralizerO1<BUFSIZE>,
-    binauralizer_rev: BinauralizerO1<BUFSIZE>,
-    ambi_master: [[f32; BUFSIZE]; 4],
-    ambi_reverb_in: [[f32; BUFSIZE]; 4],
+    output: AmbisonicOutput<BUFSIZE, NCHAN>,
+    reverb: AmbisonicReverb<BUFSIZE, MAX_AMBISONIC_CHANNELS>, // fed from ambi_reverb_in
+    rotator: AmbisonicRotator<BUFSIZE, MAX_AMBISONIC_CHANNELS>, // applied before the output
+    order: usize,
+    channels: usize, // the channels of the bus that are used at this order
+    ambi_master: [[f32; BUFSIZE]; MAX_AMBISONIC_CHANNELS],
+    ambi_reverb_in: [[f32; BUFSIZE]; MAX_AMBISONIC_CHANNELS],
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> AmbisonicBinaural<BUFSIZE, NCHAN> {
     pub fn new(samplerate: f32) -> Self {
+        AmbisonicBinaural::with_order(1, samplerate)
+    }
+
+    /// binaural rendering with the default filter for the given order (1 to 3)
+    pub fn with_order(order: usize, samplerate: f32) -> Self {
+        let order = order.clamp(1, MAX_AMBISONIC_ORDER);
+        AmbisonicBinaural::with_output(
+            order,
+            samplerate,
+            AmbisonicOutput::Binaural(Box::new(Binauralizer::default_filter_with_order(
+                order, samplerate,
+            ))),
+        )
+    }
+
+    /// use a custom binaural filter (one left/right pair of impulse responses
+    /// per ambisonic channel, at the engine's samplerate), the order
+    /// is the highest one the filter has enough channels for
+    pub fn with_filter(ir: &[(Vec<f32>, Vec<f32>)], samplerate: f32) -> Self {
+        AmbisonicBinaural::with_output(
+            ambisonic_order(ir.len()),
+            samplerate,
+            AmbisonicOutput::Binaural(Box::new(Binauralizer::from_ir(ir.to_vec()))),
+        )
+    }
+
+    /// decode to a loudspeaker array, with one (azimuth, elevation)
+    /// pair (in radians) per output channel
+    pub fn with_decoder(
+        order: usize,
+        layout: &[(f32, f32)],
+        weighting: DecoderWeighting,
+        samplerate: f32,
+    ) -> Self {
+        let order = order.clamp(1, MAX_AMBISONIC_ORDER);
+        AmbisonicBinaural::with_output(
+            order,
+            samplerate,
+            AmbisonicOutput::Speakers(AmbisonicDecoder::with_order(order, layout, weighting)),
+        )
+    }
+
+    fn with_output(order: usize, samplerate: f32, output: AmbisonicOutput<BUFSIZE, NCHAN>) -> Self {
         AmbisonicBinaural {
             running_instances: Vec::with_capacity(600),
             pending_events: Vec::with_capacity(600),
-            binauralizer: BinauralizerO1::default_filter(samplerate),
-            binauralizer_rev: BinauralizerO1::default_filter(samplerate),
-            ambi_master: [[0.0; BUFSIZE]; 4],
-            ambi_reverb_in: [[0.0; BUFSIZE]; 4],
+            output,
+            reverb: AmbisonicReverb::with_order(order, samplerate),
+            rotator: AmbisonicRotator::with_order(order, samplerate),
+            order,
+            channels: ambisonic_channels(order),
+            ambi_master: [[0.0; BUFSIZE]; MAX_AMBISONIC_CHANNELS],
+            ambi_reverb_in: [[0.0; BUFSIZE]; MAX_AMBISONIC_CHANNELS],
         }
     }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
 }
 
 /// This is the "Playhead", that is, the part you use in the
 /// output callback funtion of your application
 pub struct RuffboxPlayhead<const BUFSIZE: usize, const NCHAN: usize> {
-    running_instances: Vec<Box<dyn Synth<BUFSIZE, NCHAN> + Send + Sync>>,
+    pub(crate) running_instances: Vec<RunningInstance<BUFSIZE, NCHAN>>, // crate public for test
     pending_events: Vec<ScheduledEvent<BUFSIZE, NCHAN>>,
-    ambisonic_binaural: Option<AmbisonicBinaural<BUFSIZE, NCHAN>>,
+    pending_master_changes: Vec<ScheduledMasterChange<BUFSIZE>>,
+    pub(crate) ambisonic_binaural: Option<AmbisonicBinaural<BUFSIZE, NCHAN>>, // crate public for test
     pub(crate) buffers: Vec<SampleBuffer>, // crate public for test
     pub(crate) buffer_lengths: Vec<usize>, // crate public for test
     max_buffers: usize,
@@ -73,11 +569,27 @@ pub struct RuffboxPlayhead<const BUFSIZE: usize, const NCHAN: usize> {
     num_freeze_buffers: usize,
     samplerate: f32,
     control_q_rec: crossbeam::channel::Receiver<ControlMessage<BUFSIZE, NCHAN>>,
+    notification_q_send: crossbeam::channel::Sender<PlayheadNotification>,
+    garbage: GarbageDisposal<BUFSIZE, NCHAN>,
     block_duration: f64,
     sec_per_sample: f64,
     now: Arc<AtomicCell<f64>>,
+    ambisonic_order: Arc<AtomicCell<usize>>, // shared with the controls, 0 means disabled
     master_reverb: Box<dyn MultichannelReverb<BUFSIZE, NCHAN> + Send + Sync>,
     master_delay: MultichannelDelay<BUFSIZE, NCHAN>,
+    master_compressor: MultichannelCompressor<BUFSIZE, NCHAN>,
+    master_limiter: MultichannelLimiter<BUFSIZE, NCHAN>,
+    meters: Arc<SharedMeters<NCHAN>>,
+    master_meter: BlockMeter<BUFSIZE, NCHAN>,
+    reverb_meter: BlockMeter<BUFSIZE, NCHAN>,
+    delay_meter: BlockMeter<BUFSIZE, NCHAN>,
+    aux_buses: Vec<AuxBus<BUFSIZE, NCHAN>>,
+    aux_bus_ins: Vec<[[f32; BUFSIZE]; NCHAN]>,
+    groups: Vec<InstanceGroup<BUFSIZE, NCHAN>>,
+    max_voices: Option<usize>,
+    voice_stealing_policy: VoiceStealingPolicy,
+    steal_fade_samples: usize,
+    effects_flush: Option<(usize, usize)>, // remaining, total fade samples
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
@@ -89,45 +601,16 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
         samplerate: f64,
         max_buffers: usize,
         freeze_buffers: usize,
+        delay_max_time: f64,
         now: &Arc<AtomicCell<f64>>,
+        ambisonic_order: &Arc<AtomicCell<usize>>,
+        meters: &Arc<SharedMeters<NCHAN>>,
         rx: crossbeam::channel::Receiver<ControlMessage<BUFSIZE, NCHAN>>,
+        ntx: crossbeam::channel::Sender<PlayheadNotification>,
+        gtx: crossbeam::channel::Sender<Garbage<BUFSIZE, NCHAN>>,
     ) -> RuffboxPlayhead<BUFSIZE, NCHAN> {
         // create reverb
-        let rev: Box<dyn MultichannelReverb<BUFSIZE, NCHAN> + Send + Sync> = match reverb_mode {
-            ReverbMode::FreeVerb => {
-                let mut mrev = MultichannelFreeverb::new(samplerate as f32);
-                // tweak some reverb values for freeverb
-                mrev.set_roomsize(0.65);
-                mrev.set_damp(0.43);
-                mrev.set_wet(1.0);
-                Box::new(mrev)
-            }
-            ReverbMode::Convolution(ir, sr) => {
-                let mut ir_clone = ir.clone();
-                // resample IR if needed ...
-                if *sr as f64 != samplerate {
-                    // zero-pad for resampling blocks
-                    if (ir.len() as f32 % 1024.0) > 0.0 {
-                        let diff = 1024 - (ir.len() % 1024);
-                        ir_clone.append(&mut vec![0.0; diff]);
-                    }
-
-                    let mut ir_resampled: Vec<f32> = Vec::new();
-                    let mut resampler =
-                        FftFixedIn::<f32>::new(*sr as usize, samplerate as usize, 1024, 1, 1);
-
-                    let num_chunks = ir.len() / 1024;
-                    for chunk in 0..num_chunks {
-                        let chunk = vec![ir_clone[(1024 * chunk)..(1024 * (chunk + 1))].to_vec()];
-                        let mut waves_out = resampler.process(&chunk).unwrap();
-                        ir_resampled.append(&mut waves_out[0]);
-                    }
-                    Box::new(MultichannelConvolutionReverb::with_ir(&ir_resampled))
-                } else {
-                    Box::new(MultichannelConvolutionReverb::with_ir(ir))
-                }
-            }
-        };
+        let rev = build_reverb(reverb_mode, samplerate);
 
         // init buffer memory
         let mut buffers = Vec::new();
@@ -173,13 +656,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 buffers[b] = SampleBuffer::Mono(vec![0.0; buflen_norinterp + 4]);
                 buffer_lengths[b] = buflen_norinterp;
             }
-
-            println!("live buf time samples: {}", buffer_lengths[0]);
         }
 
         RuffboxPlayhead {
             running_instances: Vec::with_capacity(600),
             pending_events: Vec::with_capacity(600),
+            pending_master_changes: Vec::with_capacity(100),
             ambisonic_binaural: None,
             buffers,
             buffer_lengths,
@@ -189,21 +671,94 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
             stitch_size,
             samplerate: samplerate as f32,
             control_q_rec: rx,
+            notification_q_send: ntx,
+            garbage: GarbageDisposal::new(gtx),
             // timing stuff
             block_duration: BUFSIZE as f64 / samplerate,
             sec_per_sample: 1.0 / samplerate,
             now: Arc::clone(now),
+            ambisonic_order: Arc::clone(ambisonic_order),
             master_reverb: rev,
-            master_delay: MultichannelDelay::new(samplerate as f32),
+            master_delay: MultichannelDelay::with_max_time(
+                samplerate as f32,
+                delay_max_time as f32,
+            ),
+            master_compressor: MultichannelCompressor::new(samplerate as f32),
+            master_limiter: MultichannelLimiter::new(samplerate as f32),
+            meters: Arc::clone(meters),
+            master_meter: BlockMeter::new(MeterPoint::Master),
+            reverb_meter: BlockMeter::new(MeterPoint::Reverb),
+            delay_meter: BlockMeter::new(MeterPoint::Delay),
+            aux_buses: Vec::with_capacity(MAX_AUX_BUSES),
+            aux_bus_ins: Vec::with_capacity(MAX_AUX_BUSES),
+            groups: (0..NUM_GROUPS)
+                .map(|_| InstanceGroup::new(samplerate as f32))
+                .collect(),
             freeze_buffer_offset: live_buffers,
             num_live_buffers: live_buffers,
             num_freeze_buffers: freeze_buffers,
+            max_voices: None,
+            voice_stealing_policy: VoiceStealingPolicy::Oldest,
+            steal_fade_samples: (samplerate * 0.005) as usize, // 5ms
+            effects_flush: None,
         }
     }
 
+    /// enable the first order ambisonic module, rendered binaurally
     pub fn enable_ambisonics_binaural(&mut self) {
-        println!("activate ambisonic-binaural module");
-        self.ambisonic_binaural = Some(AmbisonicBinaural::new(self.samplerate));
+        self.enable_ambisonics_binaural_with_order(1);
+    }
+
+    /// Enable the ambisonic module at the given order (1 to 3), rendered binaurally.
+    /// Ambisonic sources are created at this order.
+    pub fn enable_ambisonics_binaural_with_order(&mut self, order: usize) {
+        self.enable_ambisonic_module(AmbisonicBinaural::with_order(order, self.samplerate));
+    }
+
+    /// Enable the first order ambisonic module and decode to a loudspeaker array, given as one
+    /// (azimuth, elevation) pair per output channel, in radians (the same
+    /// conventions as the `AmbisonicAzimuth` and `AmbisonicElevation` parameters).
+    pub fn enable_ambisonics_decoder(
+        &mut self,
+        layout: &[(f32, f32)],
+        weighting: DecoderWeighting,
+    ) {
+        self.enable_ambisonics_decoder_with_order(1, layout, weighting);
+    }
+
+    /// Same as `enable_ambisonics_decoder`, at the given order (1 to 3).
+    pub fn enable_ambisonics_decoder_with_order(
+        &mut self,
+        order: usize,
+        layout: &[(f32, f32)],
+        weighting: DecoderWeighting,
+    ) {
+        self.enable_ambisonic_module(AmbisonicBinaural::with_decoder(
+            order,
+            layout,
+            weighting,
+            self.samplerate,
+        ));
+    }
+
+    /// Enable the ambisonic module with a custom binaural filter, that is, one
+    /// left/right pair of impulse responses per ambisonic channel (at the engine's samplerate).
+    /// The order follows from the number of pairs (4, 9 or 16).
+    pub fn enable_ambisonics_binaural_with_filter(&mut self, ir: &[(Vec<f32>, Vec<f32>)]) {
+        self.enable_ambisonic_module(AmbisonicBinaural::with_filter(ir, self.samplerate));
+    }
+
+    fn enable_ambisonic_module(&mut self, module: AmbisonicBinaural<BUFSIZE, NCHAN>) {
+        self.ambisonic_order.store(module.order());
+        self.ambisonic_binaural = Some(module);
+    }
+
+    /// Limit the number of simultaneously running voices (channel-based and
+    /// ambisonic voices are counted separately). `None` means no limit.
+    pub fn set_voice_limit(&mut self, max_voices: Option<usize>, policy: VoiceStealingPolicy) {
+        // there has to be room for at least one voice ...
+        self.max_voices = max_voices.map(|m| m.max(1));
+        self.voice_stealing_policy = policy;
     }
 
     pub fn write_samples_to_live_buffer(&mut self, bufnum: usize) {
@@ -312,15 +867,23 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
         stream_time: f64,
         track_time_internally: bool,
     ) -> [[f32; BUFSIZE]; NCHAN] {
+        self.garbage.retry();
+
         let mut out_buf: [[f32; BUFSIZE]; NCHAN] = [[0.0; BUFSIZE]; NCHAN];
 
         let mut master_delay_in: [[f32; BUFSIZE]; NCHAN] = [[0.0; BUFSIZE]; NCHAN];
         let mut master_reverb_in: [[f32; BUFSIZE]; NCHAN] = [[0.0; BUFSIZE]; NCHAN];
 
+        for bus_in in self.aux_bus_ins.iter_mut() {
+            *bus_in = [[0.0; BUFSIZE]; NCHAN];
+        }
+
         // clear ambi master if necessary
         if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
-            ambi_module.ambi_master = [[0.0; BUFSIZE]; 4];
-            ambi_module.ambi_reverb_in = [[0.0; BUFSIZE]; 4];
+            for c in 0..ambi_module.channels {
+                ambi_module.ambi_master[c] = [0.0; BUFSIZE];
+                ambi_module.ambi_reverb_in[c] = [0.0; BUFSIZE];
+            }
         }
 
         let now = if !track_time_internally {
@@ -330,15 +893,36 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
             self.now.load()
         };
 
-        // remove finished instances ...
-        self.running_instances
-            .retain(|instance| !&instance.is_finished());
+        // remove finished instances, they're freed on the control side
+        // (remove rather than swap_remove, as the order matters for voice stealing)
+        let mut i = 0;
+        while i < self.running_instances.len() {
+            if self.running_instances[i].is_finished() {
+                let instance = self.running_instances.remove(i);
+                // if nobody listens, the notification is dropped, that's fine
+                let _ = self
+                    .notification_q_send
+                    .try_send(PlayheadNotification::InstanceFinished(instance.id));
+                self.garbage.dispose(Garbage::Instance(instance));
+            } else {
+                i += 1;
+            }
+        }
 
         // in case we have ambisonic mode enabled
         if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
-            ambi_module
-                .running_instances
-                .retain(|instance| !&instance.is_finished());
+            let mut i = 0;
+            while i < ambi_module.running_instances.len() {
+                if ambi_module.running_instances[i].is_finished() {
+                    let instance = ambi_module.running_instances.remove(i);
+                    let _ = self
+                        .notification_q_send
+                        .try_send(PlayheadNotification::InstanceFinished(instance.id));
+                    self.garbage.dispose(Garbage::AmbiInstance(instance));
+                } else {
+                    i += 1;
+                }
+            }
         }
 
         for cm in self.control_q_rec.try_iter() {
@@ -377,6 +961,18 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                         }
                     }
                 }
+                ControlMessage::SetBinauralFilter(mut binauralizer) => {
+                    // swap in the new filter, the old one (or the new one, if
+                    // there's no binaural output) is dropped elsewhere
+                    if let Some(AmbisonicBinaural {
+                        output: AmbisonicOutput::Binaural(current),
+                        ..
+                    }) = self.ambisonic_binaural.as_mut()
+                    {
+                        std::mem::swap(current, &mut binauralizer);
+                    }
+                    self.garbage.dispose(Garbage::Binauralizer(binauralizer));
+                }
                 ControlMessage::ClearAllBuffers => {
                     for i in 0..self.freeze_buffer_offset + self.num_freeze_buffers {
                         if let Some(xbuf) = self.buffers.get_mut(i) {
@@ -422,63 +1018,368 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                         }
                     }
                 }
-                ControlMessage::SetGlobalParamOrModulator(par, val) => {
-                    // BAD CLONE in audio thread, but it should happen only very rarely ...
-                    self.master_reverb.set_param_or_modulator(par, val.clone());
-                    self.master_delay.set_param_or_modulator(par, val);
+                ControlMessage::SetGlobalParamOrModulator(timestamp, par, val, mut copies) => {
+                    if timestamp <= now {
+                        match val {
+                            ValueOrModulator::Val(v) => {
+                                self.master_compressor.set_parameter(par, &v);
+                                self.master_limiter.set_parameter(par, &v);
+                                self.master_reverb.set_parameter(par, &v);
+                                if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                                    ambi_module.reverb.set_parameter(par, &v);
+                                    if let Some(old) = ambi_module.rotator.set_parameter(par, &v) {
+                                        self.garbage.dispose(Garbage::Value(
+                                            ValueOrModulator::Mod(0.0, old),
+                                        ));
+                                    }
+                                }
+                                self.master_delay.set_parameter(par, &v);
+                                self.garbage
+                                    .dispose(Garbage::Value(ValueOrModulator::Val(v)));
+                            }
+                            ValueOrModulator::Mod(init, modulator)
+                                if is_rotation_parameter(par) =>
+                            {
+                                set_ambisonic_rotation_modulator(
+                                    self.ambisonic_binaural.as_mut(),
+                                    &mut self.garbage,
+                                    par,
+                                    init,
+                                    modulator,
+                                )
+                            }
+                            // the reverbs don't take modulators
+                            modulator => self.master_delay.set_param_or_modulator(
+                                par,
+                                modulator,
+                                &mut copies,
+                                &mut self.garbage.leftover(),
+                            ),
+                        }
+                        self.garbage.dispose_copies(copies);
+                    } else if self.pending_master_changes.len()
+                        < self.pending_master_changes.capacity()
+                    {
+                        // keep the changes sorted, so no sorting is needed later on,
+                        // changes with the same timestamp stay in the order they were sent
+                        let idx = self
+                            .pending_master_changes
+                            .partition_point(|c| c.timestamp.total_cmp(&timestamp).is_le());
+                        self.pending_master_changes.insert(
+                            idx,
+                            ScheduledMasterChange {
+                                timestamp,
+                                par,
+                                val,
+                                copies,
+                            },
+                        );
+                    } else {
+                        self.garbage.dispose(Garbage::Value(val));
+                        self.garbage.dispose_copies(copies);
+                    }
+                }
+                ControlMessage::SetInstanceParamOrModulator(id, par, val, mut copies) => {
+                    // the instance might be running or still pending ...
+                    if let Some(inst) = self.running_instances.iter_mut().find(|i| i.id == id) {
+                        inst.set_param_or_modulator(
+                            par,
+                            val,
+                            &mut copies,
+                            &mut self.garbage.leftover(),
+                        );
+                    } else if let Some(ev) = self.pending_events.iter_mut().find(|e| e.id == id) {
+                        ev.set_param_or_modulator(
+                            par,
+                            val,
+                            &mut copies,
+                            &mut self.garbage.leftover(),
+                        );
+                    } else if let Some(inst) = self
+                        .ambisonic_binaural
+                        .as_mut()
+                        .and_then(|m| m.running_instances.iter_mut().find(|i| i.id == id))
+                    {
+                        inst.set_param_or_modulator(
+                            par,
+                            val,
+                            &mut copies,
+                            &mut self.garbage.leftover(),
+                        );
+                    } else if let Some(ev) = self
+                        .ambisonic_binaural
+                        .as_mut()
+                        .and_then(|m| m.pending_events.iter_mut().find(|e| e.id == id))
+                    {
+                        ev.set_param_or_modulator(
+                            par,
+                            val,
+                            &mut copies,
+                            &mut self.garbage.leftover(),
+                        );
+                    } else {
+                        // the instance is already gone ...
+                        self.garbage.dispose(Garbage::Value(val));
+                    }
+                    self.garbage.dispose_copies(copies);
+                }
+                ControlMessage::ReleaseInstance(id) => {
+                    if let Some(inst) = self.running_instances.iter_mut().find(|i| i.id == id) {
+                        inst.synth.release();
+                    } else if let Some(ev) = self.pending_events.iter_mut().find(|e| e.id == id) {
+                        ev.release();
+                    } else if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        if let Some(inst) = ambi_module
+                            .running_instances
+                            .iter_mut()
+                            .find(|i| i.id == id)
+                        {
+                            inst.synth.release();
+                        } else if let Some(ev) =
+                            ambi_module.pending_events.iter_mut().find(|e| e.id == id)
+                        {
+                            ev.release();
+                        }
+                    }
                 }
                 ControlMessage::ScheduleEvent(sched_event) => {
-                    // add new instances
-                    match sched_event.source {
-                        ScheduledSource::Channel(src) => {
-                            if sched_event.timestamp == 0.0 || sched_event.timestamp == now {
-                                self.running_instances.push(src);
-                            //println!("now");
-                            } else if sched_event.timestamp < now {
-                                // late events
-                                self.running_instances.push(src);
-                                // how to send out a late message ??
-                                // some lock-free message queue to a printer thread or something ....
-                                println!("late");
-                            } else {
-                                self.pending_events.push(ScheduledEvent {
-                                    timestamp: sched_event.timestamp,
-                                    source: ScheduledSource::Channel(src),
-                                });
-                            }
+                    let id = sched_event.id;
+                    let synth_type = sched_event.synth_type;
+                    let is_ambi = matches!(sched_event.source, ScheduledSource::Ambi(_));
+
+                    if is_ambi && self.ambisonic_binaural.is_none() {
+                        // nowhere to go ...
+                        let _ = self
+                            .notification_q_send
+                            .try_send(PlayheadNotification::AmbiEventDropped(id));
+                        self.garbage.dispose(Garbage::Event(sched_event));
+                    } else if sched_event.timestamp > now {
+                        let pending_events = if let Some(ambi_module) =
+                            self.ambisonic_binaural.as_mut().filter(|_| is_ambi)
+                        {
+                            &mut ambi_module.pending_events
+                        } else {
+                            &mut self.pending_events
+                        };
+                        if let Err(ev) = push_within_capacity(pending_events, sched_event) {
+                            let _ = self
+                                .notification_q_send
+                                .try_send(PlayheadNotification::EventDropped(id));
+                            self.garbage.dispose(Garbage::Event(ev));
                         }
-                        // handle ambisonic sources ...
-                        ScheduledSource::Ambi(src) => {
-                            if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
-                                if sched_event.timestamp == 0.0 || sched_event.timestamp == now {
-                                    ambi_module.running_instances.push(src);
-                                //println!("now");
-                                } else if sched_event.timestamp < now {
-                                    // late events
-                                    ambi_module.running_instances.push(src);
-                                    // how to send out a late message ??
-                                    // some lock-free message queue to a printer thread or something ....
-                                    println!("ambi late");
-                                } else {
-                                    ambi_module.pending_events.push(ScheduledEvent {
-                                        timestamp: sched_event.timestamp,
-                                        source: ScheduledSource::Ambi(src),
-                                    });
+                    } else {
+                        // add new instances
+                        let started = match sched_event.source {
+                            ScheduledSource::Channel(src) => {
+                                if let Some(max_voices) = self.max_voices {
+                                    steal_voices(
+                                        &mut self.running_instances,
+                                        max_voices,
+                                        self.voice_stealing_policy,
+                                        synth_type,
+                                        self.steal_fade_samples,
+                                    );
+                                }
+                                push_within_capacity(
+                                    &mut self.running_instances,
+                                    RunningInstance::new(
+                                        id,
+                                        synth_type,
+                                        src,
+                                        sched_event.aux_sends,
+                                        sched_event.group,
+                                    ),
+                                )
+                                .map_err(|inst| {
+                                    self.garbage.dispose(Garbage::Instance(inst));
+                                })
+                            }
+                            // handle ambisonic sources ...
+                            ScheduledSource::Ambi(src) => {
+                                // checked above
+                                let ambi_module = self.ambisonic_binaural.as_mut().unwrap();
+                                if let Some(max_voices) = self.max_voices {
+                                    steal_voices(
+                                        &mut ambi_module.running_instances,
+                                        max_voices,
+                                        self.voice_stealing_policy,
+                                        synth_type,
+                                        self.steal_fade_samples,
+                                    );
                                 }
+                                push_within_capacity(
+                                    &mut ambi_module.running_instances,
+                                    RunningInstance::new(
+                                        id,
+                                        synth_type,
+                                        src,
+                                        sched_event.aux_sends,
+                                        sched_event.group,
+                                    ),
+                                )
+                                .map_err(|inst| {
+                                    self.garbage.dispose(Garbage::AmbiInstance(inst));
+                                })
+                            }
+                        };
+
+                        if started.is_err() {
+                            let _ = self
+                                .notification_q_send
+                                .try_send(PlayheadNotification::EventDropped(id));
+                        } else if sched_event.timestamp != 0.0 && sched_event.timestamp < now {
+                            // late events
+                            let late = (now - sched_event.timestamp) / self.sec_per_sample;
+                            let _ =
+                                self.notification_q_send
+                                    .try_send(PlayheadNotification::EventLate(
+                                        id,
+                                        late.round() as usize,
+                                    ));
+                        }
+                    }
+                }
+                ControlMessage::AddAuxBus(bus) => {
+                    // capacity is reserved, so this shouldn't allocate
+                    if self.aux_buses.len() < MAX_AUX_BUSES {
+                        self.aux_buses.push(bus);
+                        self.aux_bus_ins.push([[0.0; BUFSIZE]; NCHAN]);
+                    } else {
+                        self.garbage.dispose(Garbage::AuxBus(bus));
+                    }
+                }
+                ControlMessage::SetGroupGain(group, gain) => {
+                    if let Some(g) = self.groups.get_mut(group) {
+                        g.gain = gain;
+                    }
+                }
+                ControlMessage::SetGroupMute(group, mute) => {
+                    if let Some(g) = self.groups.get_mut(group) {
+                        g.mute = mute;
+                    }
+                }
+                ControlMessage::SetGroupSolo(group, solo) => {
+                    if let Some(g) = self.groups.get_mut(group) {
+                        g.solo = solo;
+                    }
+                }
+                ControlMessage::SetAuxBusParamOrModulator(bus, par, val, mut copies) => {
+                    if let Some(aux_bus) = self.aux_buses.get_mut(bus) {
+                        aux_bus.set_param_or_modulator(
+                            par,
+                            val,
+                            &mut copies,
+                            &mut self.garbage.leftover(),
+                        );
+                    } else {
+                        self.garbage.dispose(Garbage::Value(val));
+                    }
+                    self.garbage.dispose_copies(copies);
+                }
+                ControlMessage::Panic(fade_time, flush_effects) => {
+                    let fade_samples = ((fade_time / self.sec_per_sample) as usize).max(1);
+                    for inst in self.running_instances.iter_mut() {
+                        inst.fade_out(fade_samples);
+                    }
+                    for ev in self.pending_events.drain(..) {
+                        self.garbage.dispose(Garbage::Event(ev));
+                    }
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        for inst in ambi_module.running_instances.iter_mut() {
+                            inst.fade_out(fade_samples);
+                        }
+                        for ev in ambi_module.pending_events.drain(..) {
+                            self.garbage.dispose(Garbage::Event(ev));
+                        }
+                    }
+                    if flush_effects {
+                        self.effects_flush = Some((fade_samples, fade_samples));
+                    }
+                }
+                ControlMessage::FlushPendingEvents(timestamp) => {
+                    flush_events(
+                        &mut self.pending_events,
+                        |ev| ev.timestamp >= timestamp,
+                        &mut self.garbage,
+                    );
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        flush_events(
+                            &mut ambi_module.pending_events,
+                            |ev| ev.timestamp >= timestamp,
+                            &mut self.garbage,
+                        );
+                    }
+                }
+                ControlMessage::FlushTaggedEvents(tag) => {
+                    flush_events(
+                        &mut self.pending_events,
+                        |ev| ev.tag == Some(tag),
+                        &mut self.garbage,
+                    );
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        flush_events(
+                            &mut ambi_module.pending_events,
+                            |ev| ev.tag == Some(tag),
+                            &mut self.garbage,
+                        );
+                    }
+                }
+                ControlMessage::ShiftPendingEvents(tag, offset) => {
+                    // events shifted into the past will just be late (and reported as such) ...
+                    // shifted events don't follow the tempo clock anymore
+                    for ev in self.pending_events.iter_mut() {
+                        if tag.is_none() || ev.tag == tag {
+                            ev.timestamp += offset;
+                            ev.beat = None;
+                        }
+                    }
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        for ev in ambi_module.pending_events.iter_mut() {
+                            if tag.is_none() || ev.tag == tag {
+                                ev.timestamp += offset;
+                                ev.beat = None;
+                            }
+                        }
+                    }
+                }
+                ControlMessage::RetimePendingEvents(clock) => {
+                    // the tempo has changed, events moved into the past will be late
+                    for ev in self.pending_events.iter_mut() {
+                        if let Some(beat) = ev.beat {
+                            ev.timestamp = clock.beat_to_time(beat);
+                        }
+                    }
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        for ev in ambi_module.pending_events.iter_mut() {
+                            if let Some(beat) = ev.beat {
+                                ev.timestamp = clock.beat_to_time(beat);
                             }
                         }
                     }
+                    self.garbage.dispose(Garbage::TempoClock(clock));
+                }
+                ControlMessage::SetVoiceLimit(max_voices, policy) => {
+                    // there has to be room for at least one voice ...
+                    self.max_voices = max_voices.map(|m| m.max(1));
+                    self.voice_stealing_policy = policy;
                 }
                 ControlMessage::LoadSample(id, len, content) => {
                     if id < self.max_buffers {
-                        self.buffers[id] = content; // transfer to samples
+                        // transfer to samples, the old buffer is freed on the control side
+                        let old = std::mem::replace(&mut self.buffers[id], content);
+                        self.garbage.dispose(Garbage::SampleBuffer(old));
                         self.buffer_lengths[id] = len;
+                        let _ = self
+                            .notification_q_send
+                            .try_send(PlayheadNotification::SampleLoaded(id));
+                    } else {
+                        self.garbage.dispose(Garbage::SampleBuffer(content));
                     }
                 }
                 ControlMessage::FreezeBuffer(fb, ib) => {
                     // start at one to account for interpolation sample.
-                    if let Ok([SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf)]) =
-                        self.buffers.get_disjoint_mut([ib, fb])
+                    if let Some((SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf))) =
+                        buffer_pair(&mut self.buffers, ib, fb)
                     {
                         freezbuf[2..(self.buffer_lengths[ib] + 2)]
                             .copy_from_slice(&inbuf[2..(self.buffer_lengths[ib] + 2)]);
@@ -486,8 +1387,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 }
                 ControlMessage::FreezeAddBuffer(fb, ib) => {
                     // start at one to account for interpolation sample.
-                    if let Ok([SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf)]) =
-                        self.buffers.get_disjoint_mut([ib, fb])
+                    if let Some((SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf))) =
+                        buffer_pair(&mut self.buffers, ib, fb)
                     {
                         for i in 2..(self.buffer_lengths[ib] + 2) {
                             freezbuf[i] += inbuf[i];
@@ -496,17 +1397,19 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 }
                 ControlMessage::FreezeAfterRec(fb, ib, num_samples, add) => {
                     // just checking ... don't need the actual data ...
-                    if let Ok([SampleBuffer::Mono(_), SampleBuffer::Mono(_)]) =
-                        self.buffers.get_disjoint_mut([ib, fb])
+                    if let Some((SampleBuffer::Mono(_), SampleBuffer::Mono(_))) =
+                        buffer_pair(&mut self.buffers, ib, fb)
                     {
-                        self.live_buffer_metadata[ib]
-                            .freeze_after_recs
-                            .push(FreezeAfterRec {
+                        // ignored if there's too many of them already
+                        let _ = push_within_capacity(
+                            &mut self.live_buffer_metadata[ib].freeze_after_recs,
+                            FreezeAfterRec {
                                 freeze_buffer_number: fb,
                                 freeze_after: num_samples,
                                 recorded: 0,
                                 add,
-                            });
+                            },
+                        );
                     }
                 }
             }
@@ -518,9 +1421,8 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 //println!("{}", far.recorded);
                 if far.recorded >= far.freeze_after {
                     // freeze the number of recorded samples, copy to beginning of freezebuffer
-                    if let Ok([SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf)]) = self
-                        .buffers
-                        .get_disjoint_mut([bufnum, far.freeze_buffer_number])
+                    if let Some((SampleBuffer::Mono(inbuf), SampleBuffer::Mono(freezbuf))) =
+                        buffer_pair(&mut self.buffers, bufnum, far.freeze_buffer_number)
                     {
                         if lbm.live_buffer_idx - 1 >= far.freeze_after {
                             let ib_offset = (lbm.live_buffer_idx - far.freeze_after) + 1;
@@ -558,6 +1460,12 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                             }
                         }
                     }
+                    let _ = self.notification_q_send.try_send(
+                        PlayheadNotification::FreezeAfterRecCompleted(
+                            far.freeze_buffer_number - self.freeze_buffer_offset,
+                            bufnum,
+                        ),
+                    );
                 }
             }
             // purge the ones that are donw ...
@@ -565,30 +1473,55 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 .retain(|far| far.recorded < far.freeze_after);
         }
 
+        // calculate the group gains for this block
+        let any_solo = self.groups.iter().any(|g| g.solo);
+        for group in self.groups.iter_mut() {
+            group.next_block(any_solo);
+        }
+
         // handle already running instances
         for running_inst in self.running_instances.iter_mut() {
-            let block = running_inst.get_next_block(0, &self.buffers);
+            let mut block = running_inst.get_next_block(0, &self.buffers);
+
+            let group = &mut self.groups[running_inst.group];
+            group.apply_gain(&mut block);
 
             // this should benefit from unrolling outer loop with macro ...
             for c in 0..NCHAN {
                 for s in 0..BUFSIZE {
-                    out_buf[c][s] += block[c][s];
-                    master_reverb_in[c][s] += block[c][s] * running_inst.reverb_level();
-                    master_delay_in[c][s] += block[c][s] * running_inst.delay_level();
+                    group.bus[c][s] += block[c][s];
+                    master_reverb_in[c][s] += block[c][s] * running_inst.synth.reverb_level();
+                    master_delay_in[c][s] += block[c][s] * running_inst.synth.delay_level();
+                }
+            }
+
+            for (bus_in, send) in self
+                .aux_bus_ins
+                .iter_mut()
+                .zip(running_inst.aux_sends.iter())
+            {
+                if *send > 0.0 {
+                    for c in 0..NCHAN {
+                        for s in 0..BUFSIZE {
+                            bus_in[c][s] += block[c][s] * send;
+                        }
+                    }
                 }
             }
         }
 
         if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
             for running_inst in ambi_module.running_instances.iter_mut() {
-                let ambi_block = running_inst.get_next_block(0, &self.buffers);
+                let mut ambi_block = running_inst.get_next_block(0, &self.buffers);
+                self.groups[running_inst.group]
+                    .apply_gain_to_channels(&mut ambi_block, ambi_module.channels);
 
                 // this should benefit from unrolling outer loop with macro ...
-                for c in 0..4 {
+                for c in 0..ambi_module.channels {
                     for s in 0..BUFSIZE {
                         ambi_module.ambi_master[c][s] += ambi_block[c][s];
                         ambi_module.ambi_reverb_in[c][s] +=
-                            ambi_block[c][s] * running_inst.reverb_level();
+                            ambi_block[c][s] * running_inst.synth.reverb_level();
                     }
                 }
             }
@@ -606,25 +1539,91 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
             //println!("on time ts: {} st: {}", current_event.timestamp, self.now);
             // calculate precise timing
             let sample_offset = (current_event.timestamp - now) / self.sec_per_sample;
-            if let ScheduledSource::Channel(mut src) = current_event.source {
-                let block = src.get_next_block(sample_offset.round() as usize, &self.buffers);
+            if let ScheduledSource::Channel(src) = current_event.source {
+                // events shifted into the past start right away
+                if sample_offset < 0.0 {
+                    let _ = self
+                        .notification_q_send
+                        .try_send(PlayheadNotification::EventLate(
+                            current_event.id,
+                            (-sample_offset).round() as usize,
+                        ));
+                }
+                if let Some(max_voices) = self.max_voices {
+                    steal_voices(
+                        &mut self.running_instances,
+                        max_voices,
+                        self.voice_stealing_policy,
+                        current_event.synth_type,
+                        self.steal_fade_samples,
+                    );
+                }
+                let mut inst = RunningInstance::new(
+                    current_event.id,
+                    current_event.synth_type,
+                    src,
+                    current_event.aux_sends,
+                    current_event.group,
+                );
+                let mut block =
+                    inst.get_next_block(sample_offset.max(0.0).round() as usize, &self.buffers);
+
+                let group = &mut self.groups[inst.group];
+                group.apply_gain(&mut block);
 
                 for c in 0..NCHAN {
                     for s in 0..BUFSIZE {
-                        out_buf[c][s] += block[c][s];
-                        master_reverb_in[c][s] += block[c][s] * src.reverb_level();
-                        master_delay_in[c][s] += block[c][s] * src.delay_level();
+                        group.bus[c][s] += block[c][s];
+                        master_reverb_in[c][s] += block[c][s] * inst.synth.reverb_level();
+                        master_delay_in[c][s] += block[c][s] * inst.synth.delay_level();
+                    }
+                }
+
+                for (bus_in, send) in self.aux_bus_ins.iter_mut().zip(inst.aux_sends.iter()) {
+                    if *send > 0.0 {
+                        for c in 0..NCHAN {
+                            for s in 0..BUFSIZE {
+                                bus_in[c][s] += block[c][s] * send;
+                            }
+                        }
                     }
                 }
 
                 // if length of sample event is longer than the rest of the block,
                 // add to running instances
-                if !src.is_finished() {
-                    self.running_instances.push(src);
+                if !inst.is_finished() {
+                    if let Err(inst) = push_within_capacity(&mut self.running_instances, inst) {
+                        let _ = self
+                            .notification_q_send
+                            .try_send(PlayheadNotification::EventDropped(inst.id));
+                        self.garbage.dispose(Garbage::Instance(inst));
+                    }
+                } else {
+                    let _ = self
+                        .notification_q_send
+                        .try_send(PlayheadNotification::InstanceFinished(inst.id));
+                    self.garbage.dispose(Garbage::Instance(inst));
+                }
+            }
+        }
+
+        // sum up group buses
+        for group in self.groups.iter() {
+            for c in 0..NCHAN {
+                for s in 0..BUFSIZE {
+                    out_buf[c][s] += group.bus[c][s];
                 }
             }
         }
 
+        // after a panic, fade out the effect tails before clearing them
+        let mut flush_gain = [1.0; BUFSIZE];
+        if let Some((remaining, total)) = self.effects_flush {
+            for (s, gain) in flush_gain.iter_mut().enumerate() {
+                *gain = remaining.saturating_sub(s) as f32 / total as f32;
+            }
+        }
+
         if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
             // sort new events by timestamp, order of already sorted elements doesn't matter
             ambi_module.pending_events.sort_unstable_by(|a, b| b.cmp(a));
@@ -637,46 +1636,206 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
                 //println!("on time ts: {} st: {}", current_event.timestamp, self.now);
                 // calculate precise timing
                 let sample_offset = (current_event.timestamp - now) / self.sec_per_sample;
-                if let ScheduledSource::Ambi(mut src) = current_event.source {
-                    let ambi_block =
-                        src.get_next_block(sample_offset.round() as usize, &self.buffers);
-
-                    for c in 0..4 {
+                if let ScheduledSource::Ambi(src) = current_event.source {
+                    // events shifted into the past start right away
+                    if sample_offset < 0.0 {
+                        let _ = self
+                            .notification_q_send
+                            .try_send(PlayheadNotification::EventLate(
+                                current_event.id,
+                                (-sample_offset).round() as usize,
+                            ));
+                    }
+                    if let Some(max_voices) = self.max_voices {
+                        steal_voices(
+                            &mut ambi_module.running_instances,
+                            max_voices,
+                            self.voice_stealing_policy,
+                            current_event.synth_type,
+                            self.steal_fade_samples,
+                        );
+                    }
+                    let mut inst = RunningInstance::new(
+                        current_event.id,
+                        current_event.synth_type,
+                        src,
+                        current_event.aux_sends,
+                        current_event.group,
+                    );
+                    let mut ambi_block =
+                        inst.get_next_block(sample_offset.max(0.0).round() as usize, &self.buffers);
+                    self.groups[inst.group]
+                        .apply_gain_to_channels(&mut ambi_block, ambi_module.channels);
+
+                    for c in 0..ambi_module.channels {
                         for s in 0..BUFSIZE {
                             ambi_module.ambi_master[c][s] += ambi_block[c][s];
                             ambi_module.ambi_reverb_in[c][s] +=
-                                ambi_block[c][s] * src.reverb_level();
+                                ambi_block[c][s] * inst.synth.reverb_level();
                         }
                     }
 
                     // if length of sample event is longer than the rest of the block,
                     // add to running instances
-                    if !src.is_finished() {
-                        ambi_module.running_instances.push(src);
+                    if !inst.is_finished() {
+                        if let Err(inst) =
+                            push_within_capacity(&mut ambi_module.running_instances, inst)
+                        {
+                            let _ = self
+                                .notification_q_send
+                                .try_send(PlayheadNotification::EventDropped(inst.id));
+                            self.garbage.dispose(Garbage::AmbiInstance(inst));
+                        }
+                    } else {
+                        let _ = self
+                            .notification_q_send
+                            .try_send(PlayheadNotification::InstanceFinished(inst.id));
+                        self.garbage.dispose(Garbage::AmbiInstance(inst));
+                    }
+                }
+            }
+
+            // the reverb works on the sound field, and is rendered along with it
+            let mut reverb_out = [[0.0; BUFSIZE]; MAX_AMBISONIC_CHANNELS];
+            ambi_module.reverb.process_segment(
+                &ambi_module.ambi_reverb_in,
+                &mut reverb_out,
+                0,
+                BUFSIZE,
+            );
+            for c in 0..ambi_module.channels {
+                for s in 0..BUFSIZE {
+                    ambi_module.ambi_master[c][s] += reverb_out[c][s] * flush_gain[s];
+                }
+            }
+
+            // rotate the whole sound field, reverb tails included
+            ambi_module
+                .rotator
+                .process(&mut ambi_module.ambi_master, &self.buffers);
+
+            match &mut ambi_module.output {
+                AmbisonicOutput::Binaural(binauralizer) => {
+                    let block = binauralizer.binauralize(&ambi_module.ambi_master);
+
+                    // mix binauralized block in with master
+                    for c in 0..NCHAN.min(2) {
+                        for s in 0..BUFSIZE {
+                            out_buf[c][s] += block[c][s];
+                        }
+                    }
+                }
+                AmbisonicOutput::Speakers(decoder) => {
+                    let block = decoder.decode(&ambi_module.ambi_master);
+
+                    for c in 0..NCHAN {
+                        for s in 0..BUFSIZE {
+                            out_buf[c][s] += block[c][s];
+                        }
                     }
                 }
             }
+        }
 
-            let block = ambi_module
-                .binauralizer
-                .binauralize(ambi_module.ambi_master);
+        // process master effects, split at the sample positions of
+        // the master parameter changes that belong to this block
+        let mut reverb_out = [[0.0; BUFSIZE]; NCHAN];
+        let mut delay_out = [[0.0; BUFSIZE]; NCHAN];
+        let mut segment_start = 0;
+
+        // the pending changes are sorted by timestamp
+        let due = self
+            .pending_master_changes
+            .partition_point(|c| c.timestamp < block_end);
+
+        for change in self.pending_master_changes.drain(..due) {
+            let mut copies = change.copies;
+            let sample_offset =
+                (((change.timestamp - now) / self.sec_per_sample).round() as usize).min(BUFSIZE);
+
+            if sample_offset > segment_start {
+                self.master_reverb.process_segment(
+                    &master_reverb_in,
+                    &mut reverb_out,
+                    segment_start,
+                    sample_offset,
+                );
+                self.master_delay.process_segment(
+                    &master_delay_in,
+                    &mut delay_out,
+                    segment_start,
+                    sample_offset,
+                    &self.buffers,
+                );
+                segment_start = sample_offset;
+            }
+
+            match change.val {
+                ValueOrModulator::Val(v) => {
+                    // the dynamics only change per block
+                    self.master_compressor.set_parameter(change.par, &v);
+                    self.master_limiter.set_parameter(change.par, &v);
+                    self.master_reverb.set_parameter(change.par, &v);
+                    // the ambisonic reverb and rotation have already processed
+                    // this block, so the change takes effect on the next one
+                    if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                        ambi_module.reverb.set_parameter(change.par, &v);
+                        if let Some(old) = ambi_module.rotator.set_parameter(change.par, &v) {
+                            self.garbage
+                                .dispose(Garbage::Value(ValueOrModulator::Mod(0.0, old)));
+                        }
+                    }
+                    self.master_delay.set_parameter(change.par, &v);
+                    self.garbage
+                        .dispose(Garbage::Value(ValueOrModulator::Val(v)));
+                }
+                ValueOrModulator::Mod(init, modulator) if is_rotation_parameter(change.par) => {
+                    set_ambisonic_rotation_modulator(
+                        self.ambisonic_binaural.as_mut(),
+                        &mut self.garbage,
+                        change.par,
+                        init,
+                        modulator,
+                    )
+                }
+                // the reverbs don't take modulators
+                modulator => self.master_delay.set_param_or_modulator(
+                    change.par,
+                    modulator,
+                    &mut copies,
+                    &mut self.garbage.leftover(),
+                ),
+            }
+            self.garbage.dispose_copies(copies);
+        }
 
-            // this has to do for a reverb until I manage to implement a proper ambisonic reverb ...
-            let block_rev = ambi_module
-                .binauralizer_rev
-                .binauralize(ambi_module.ambi_reverb_in);
+        if segment_start < BUFSIZE {
+            self.master_reverb.process_segment(
+                &master_reverb_in,
+                &mut reverb_out,
+                segment_start,
+                BUFSIZE,
+            );
+            self.master_delay.process_segment(
+                &master_delay_in,
+                &mut delay_out,
+                segment_start,
+                BUFSIZE,
+                &self.buffers,
+            );
+        }
 
-            // mix binauralized block in with master
+        if self.effects_flush.is_some() {
             for c in 0..NCHAN {
                 for s in 0..BUFSIZE {
-                    out_buf[c][s] += block[c][s];
-                    master_reverb_in[c][s] += block_rev[c][s];
+                    reverb_out[c][s] *= flush_gain[s];
+                    delay_out[c][s] *= flush_gain[s];
                 }
             }
         }
 
-        let reverb_out = self.master_reverb.process(master_reverb_in);
-        let delay_out = self.master_delay.process(master_delay_in, &self.buffers);
+        self.reverb_meter.measure(&reverb_out, &self.meters);
+        self.delay_meter.measure(&delay_out, &self.meters);
 
         for c in 0..NCHAN {
             for s in 0..BUFSIZE {
@@ -684,6 +1843,41 @@ impl<const BUFSIZE: usize, const NCHAN: usize> RuffboxPlayhead<BUFSIZE, NCHAN> {
             }
         }
 
+        for (aux_bus, bus_in) in self.aux_buses.iter_mut().zip(self.aux_bus_ins.iter()) {
+            let bus_out = aux_bus.process(*bus_in, &self.buffers);
+            for c in 0..NCHAN {
+                for s in 0..BUFSIZE {
+                    out_buf[c][s] += bus_out[c][s] * flush_gain[s];
+                }
+            }
+        }
+
+        if let Some((remaining, total)) = self.effects_flush {
+            if remaining <= BUFSIZE {
+                self.master_reverb.clear();
+                self.master_delay.clear();
+                if let Some(ambi_module) = self.ambisonic_binaural.as_mut() {
+                    ambi_module.reverb.clear();
+                }
+                for aux_bus in self.aux_buses.iter_mut() {
+                    aux_bus.clear();
+                }
+                self.effects_flush = None;
+            } else {
+                self.effects_flush = Some((remaining - BUFSIZE, total));
+            }
+        }
+
+        // master dynamics, compressor first, the limiter
+        // makes sure nothing exceeds the ceiling
+        out_buf = self.master_compressor.process(out_buf);
+        out_buf = self.master_limiter.process(out_buf);
+
+        self.master_meter.measure(&out_buf, &self.meters);
+        self.meters.set_gain_reduction(
+            self.master_compressor.gain_reduction() + self.master_limiter.gain_reduction(),
+        );
+
         if track_time_internally {
             self.now.store(now + self.block_duration);
         }
@@ -6,9 +6,16 @@ pub use crate::synths::n_channel::n_channel_sampler::NChannelSampler;
 pub use crate::synths::n_channel::n_channel_stereo_sampler::NChannelStereoSampler;
 pub use crate::synths::n_channel::risset_bell::RissetBell;
 pub use crate::synths::n_channel::single_oscillator_synth::SingleOscillatorSynth;
+pub use crate::synths::n_channel::vbap_synth::VbapSynth;
 
 // ambisonic synths
-pub use crate::synths::ambisonic::ambisonic_sampler_o1::AmbisonicSamplerO1;
+pub use crate::synths::ambisonic::ambisonic_sampler::{
+    AmbisonicSampler, AmbisonicSamplerO1, AmbisonicSamplerO2, AmbisonicSamplerO3,
+};
+pub use crate::synths::ambisonic::encoded_synth::{
+    AmbisonicEncodedSynth, EncodedSynthO1, EncodedSynthO2, EncodedSynthO3,
+};
+pub use crate::synths::ambisonic::AmbisonicBusSynth;
 
 use crate::building_blocks::{EffectType, FilterType, OscillatorType};
 
@@ -35,4 +42,15 @@ pub enum SynthType {
     MultiOscillator(SynthDescription),
     KarPlusPlus(SynthDescription),
     RissetBell,
+    // the oscillator synths, encoded to ambisonics
+    AmbisonicSingleOscillator(SynthDescription),
+    AmbisonicMultiOscillator(SynthDescription),
+    AmbisonicKarPlusPlus(SynthDescription),
+    AmbisonicRissetBell,
+    // mono synths, panned on the VBAP speaker layout
+    VbapSampler(SynthDescription),
+    VbapSingleOscillator(SynthDescription),
+    VbapMultiOscillator(SynthDescription),
+    VbapKarPlusPlus(SynthDescription),
+    VbapRissetBell,
 }
@@ -1,2 +1,76 @@
-pub mod ambisonic_sampler_o1;
-pub mod single_oscillator_synth_o1;
+pub mod ambisonic_sampler;
+pub mod encoded_synth;
+pub mod single_oscillator_synth;
+
+use crate::building_blocks::ambisonics::MAX_AMBISONIC_CHANNELS;
+use crate::building_blocks::{
+    Modulator, SampleBuffer, Synth, SynthParameterAddress, SynthParameterValue, ValueOrModulator,
+};
+
+/// Puts an ambisonic synth of any order on the ambisonic bus, which has
+/// the width of the highest supported order. Only the channels of the
+/// synth's order are copied, the ones above stay silent.
+pub struct AmbisonicBusSynth<const BUFSIZE: usize, const ACH: usize> {
+    synth: Box<dyn Synth<BUFSIZE, ACH> + Send + Sync>,
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicBusSynth<BUFSIZE, ACH> {
+    pub fn new(synth: Box<dyn Synth<BUFSIZE, ACH> + Send + Sync>) -> Self {
+        AmbisonicBusSynth { synth }
+    }
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> Synth<BUFSIZE, MAX_AMBISONIC_CHANNELS>
+    for AmbisonicBusSynth<BUFSIZE, ACH>
+{
+    fn set_parameter(&mut self, par: SynthParameterAddress, value: &SynthParameterValue) {
+        self.synth.set_parameter(par, value);
+    }
+
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterAddress,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+    ) {
+        self.synth
+            .set_modulator(par, init, modulator, copies, leftover);
+    }
+
+    fn modulator_copies(&self) -> usize {
+        self.synth.modulator_copies()
+    }
+
+    fn finish(&mut self) {
+        self.synth.finish();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.synth.is_finished()
+    }
+
+    fn release(&mut self) {
+        self.synth.release();
+    }
+
+    fn get_next_block(
+        &mut self,
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; MAX_AMBISONIC_CHANNELS] {
+        let block = self.synth.get_next_block(start_sample, in_buffers);
+        let mut out = [[0.0; BUFSIZE]; MAX_AMBISONIC_CHANNELS];
+        out[..ACH].copy_from_slice(&block);
+        out
+    }
+
+    fn reverb_level(&self) -> f32 {
+        self.synth.reverb_level()
+    }
+
+    fn delay_level(&self) -> f32 {
+        self.synth.delay_level()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::building_blocks::ambisonics::encoder_o1::EncoderO1;
+use crate::building_blocks::ambisonics::encoder::AmbisonicEncoder;
 use crate::building_blocks::bitcrusher::Bitcrusher;
 use crate::building_blocks::envelopes::*;
 use crate::building_blocks::filters::*;
@@ -6,13 +6,15 @@ use crate::building_blocks::sampler::MonoSampler;
 use crate::building_blocks::EffectType;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    waveshaper::Waveshaper, FilterType, Modulator, MonoEffect, MonoSource, SampleBuffer, Synth,
-    SynthParameterLabel, SynthParameterValue,
+    pass_leftover, set_modulator_on_effects, waveshaper::Waveshaper, FilterType, Modulator,
+    MonoEffect, MonoSource, SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue,
+    ValueOrModulator,
 };
 use crate::synths::SynthDescription;
 
-/// a sampler with envelope etc.
-pub struct AmbisonicSamplerO1<const BUFSIZE: usize> {
+/// a sampler with envelope etc., encoded to ambisonics (4, 9 or 16 channels
+/// for first, second or third order)
+pub struct AmbisonicSampler<const BUFSIZE: usize, const ACH: usize> {
     sampler: MonoSampler<BUFSIZE>,
     pre_filter_effects: Vec<Box<dyn MonoEffect<BUFSIZE> + Send + Sync>>,
     envelope: LinearASREnvelope<BUFSIZE>,
@@ -20,18 +22,22 @@ pub struct AmbisonicSamplerO1<const BUFSIZE: usize> {
     peak_eq_1: Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
     peak_eq_2: Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
     lpf: Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
-    encoder: EncoderO1<BUFSIZE>,
+    encoder: AmbisonicEncoder<BUFSIZE, ACH>,
     reverb: f32,
     delay: f32,
 }
 
-impl<const BUFSIZE: usize> AmbisonicSamplerO1<BUFSIZE> {
+pub type AmbisonicSamplerO1<const BUFSIZE: usize> = AmbisonicSampler<BUFSIZE, 4>;
+pub type AmbisonicSamplerO2<const BUFSIZE: usize> = AmbisonicSampler<BUFSIZE, 9>;
+pub type AmbisonicSamplerO3<const BUFSIZE: usize> = AmbisonicSampler<BUFSIZE, 16>;
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicSampler<BUFSIZE, ACH> {
     pub fn new(
         desc: SynthDescription,
         bufnum: usize,
         buflen: usize,
         sr: f32,
-    ) -> AmbisonicSamplerO1<BUFSIZE> {
+    ) -> AmbisonicSampler<BUFSIZE, ACH> {
         let dur = (buflen as f32 / sr) - 0.0002;
 
         // fixed filter order for now ...
@@ -48,7 +54,7 @@ impl<const BUFSIZE: usize> AmbisonicSamplerO1<BUFSIZE> {
             }
         }
 
-        AmbisonicSamplerO1 {
+        AmbisonicSampler {
             sampler: MonoSampler::with_bufnum_len(bufnum, buflen, true),
             pre_filter_effects,
             envelope: LinearASREnvelope::new(1.0, 0.0001, dur, 0.0001, sr),
@@ -75,46 +81,56 @@ impl<const BUFSIZE: usize> AmbisonicSamplerO1<BUFSIZE> {
                 FilterType::Dummy => Box::new(DummyFilter::new()),
                 _ => Box::new(Lpf18::new(19000.0, 0.1, 0.01, sr)),
             },
-            encoder: EncoderO1::new(),
+            encoder: AmbisonicEncoder::new(sr),
             reverb: 0.0,
             delay: 0.0,
         }
     }
 }
 
-impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for AmbisonicSamplerO1<BUFSIZE> {
+impl<const BUFSIZE: usize, const ACH: usize> Synth<BUFSIZE, ACH>
+    for AmbisonicSampler<BUFSIZE, ACH>
+{
     fn set_modulator(
         &mut self,
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.sampler
-            .set_modulator(par.label, init, modulator.clone());
-        self.hpf.set_modulator(par.label, init, modulator.clone());
-
-        for ef in self.pre_filter_effects.iter_mut() {
-            ef.set_modulator(par.label, init, modulator.clone());
-        }
-
-        match par.label {
-            SynthParameterLabel::PeakFrequency
-            | SynthParameterLabel::PeakBandwidth
-            | SynthParameterLabel::PeakGain => match par.idx {
-                Some(1) => self
-                    .peak_eq_2
-                    .set_modulator(par.label, init, modulator.clone()),
-                _ => self
-                    .peak_eq_1
-                    .set_modulator(par.label, init, modulator.clone()),
-            },
-            _ => {}
-        }
+        let result = self
+            .sampler
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| self.hpf.set_modulator(par.label, init, m))
+            .or_else(|m| {
+                set_modulator_on_effects(
+                    &mut self.pre_filter_effects,
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| match par.label {
+                SynthParameterLabel::PeakFrequency
+                | SynthParameterLabel::PeakBandwidth
+                | SynthParameterLabel::PeakGain => match par.idx {
+                    Some(1) => self.peak_eq_2.set_modulator(par.label, init, m),
+                    _ => self.peak_eq_1.set_modulator(par.label, init, m),
+                },
+                _ => Err(m),
+            })
+            .or_else(|m| self.lpf.set_modulator(par.label, init, m))
+            .or_else(|m| self.envelope.set_modulator(par.label, init, m))
+            .or_else(|m| self.encoder.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
+    }
 
-        self.lpf.set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .set_modulator(par.label, init, modulator.clone());
-        self.encoder.set_modulator(par.label, init, modulator);
+    fn modulator_copies(&self) -> usize {
+        // one for each effect but the first
+        self.pre_filter_effects.len().saturating_sub(1)
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -163,11 +179,15 @@ impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for AmbisonicSamplerO1<BUFSIZE> {
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
         sample_buffers: &[SampleBuffer],
-    ) -> [[f32; BUFSIZE]; 4] {
+    ) -> [[f32; BUFSIZE]; ACH] {
         let mut out: [f32; BUFSIZE] = self.sampler.get_next_block(start_sample, sample_buffers);
 
         for ef in self.pre_filter_effects.iter_mut() {
@@ -190,7 +210,7 @@ impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for AmbisonicSamplerO1<BUFSIZE> {
     }
 
     fn reverb_level(&self) -> f32 {
-        self.reverb
+        self.reverb * self.encoder.reverb_scale()
     }
 
     fn delay_level(&self) -> f32 {
@@ -0,0 +1,135 @@
+use crate::building_blocks::ambisonics::encoder::AmbisonicEncoder;
+use crate::building_blocks::{
+    pass_leftover, Modulator, SampleBuffer, Synth, SynthParameterAddress, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
+};
+
+/// Encodes a mono (channel-based) synth to ambisonics (4, 9 or 16 channels
+/// for first, second or third order), so it can be used as an ambisonic source.
+/// The position is set by the `AmbisonicAzimuth` and `AmbisonicElevation`
+/// parameters, the channel position of the synth is ignored. `AmbisonicDistance`
+/// and `AmbisonicDoppler` move the source away.
+pub struct AmbisonicEncodedSynth<const BUFSIZE: usize, const ACH: usize> {
+    synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>,
+    encoder: AmbisonicEncoder<BUFSIZE, ACH>,
+}
+
+pub type EncodedSynthO1<const BUFSIZE: usize> = AmbisonicEncodedSynth<BUFSIZE, 4>;
+pub type EncodedSynthO2<const BUFSIZE: usize> = AmbisonicEncodedSynth<BUFSIZE, 9>;
+pub type EncodedSynthO3<const BUFSIZE: usize> = AmbisonicEncodedSynth<BUFSIZE, 16>;
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicEncodedSynth<BUFSIZE, ACH> {
+    pub fn new(synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>, samplerate: f32) -> Self {
+        AmbisonicEncodedSynth {
+            synth,
+            encoder: AmbisonicEncoder::new(samplerate),
+        }
+    }
+}
+
+impl<const BUFSIZE: usize, const ACH: usize> Synth<BUFSIZE, ACH>
+    for AmbisonicEncodedSynth<BUFSIZE, ACH>
+{
+    fn set_parameter(&mut self, par: SynthParameterAddress, value: &SynthParameterValue) {
+        match par.label {
+            SynthParameterLabel::AmbisonicAzimuth
+            | SynthParameterLabel::AmbisonicElevation
+            | SynthParameterLabel::AmbisonicDistance
+            | SynthParameterLabel::AmbisonicDoppler => self.encoder.set_parameter(par.label, value),
+            // with a single channel, the panner would only attenuate
+            SynthParameterLabel::ChannelPosition => {}
+            _ => self.synth.set_parameter(par, value),
+        }
+    }
+
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterAddress,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+    ) {
+        match par.label {
+            SynthParameterLabel::AmbisonicAzimuth
+            | SynthParameterLabel::AmbisonicElevation
+            | SynthParameterLabel::AmbisonicDistance => pass_leftover(
+                self.encoder.set_modulator(par.label, init, modulator),
+                leftover,
+            ),
+            // same as above, the modulator goes back to be disposed of
+            SynthParameterLabel::ChannelPosition => {
+                leftover(ValueOrModulator::Mod(init, modulator))
+            }
+            _ => self
+                .synth
+                .set_modulator(par, init, modulator, copies, leftover),
+        }
+    }
+
+    fn modulator_copies(&self) -> usize {
+        self.synth.modulator_copies()
+    }
+
+    fn finish(&mut self) {
+        self.synth.finish();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.synth.is_finished()
+    }
+
+    fn release(&mut self) {
+        self.synth.release();
+    }
+
+    fn get_next_block(
+        &mut self,
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; ACH] {
+        let [mono] = self.synth.get_next_block(start_sample, in_buffers);
+        self.encoder.process_block(mono, start_sample, in_buffers)
+    }
+
+    fn reverb_level(&self) -> f32 {
+        self.synth.reverb_level() * self.encoder.reverb_scale()
+    }
+
+    fn delay_level(&self) -> f32 {
+        self.synth.delay_level()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synths::RissetBell;
+
+    #[test]
+    fn test_encoded_synth() {
+        let mut synth =
+            EncodedSynthO1::<128>::new(Box::new(RissetBell::<128, 1>::new(44100.0)), 44100.0);
+
+        // from the right, the panner is bypassed
+        synth.set_parameter(
+            SynthParameterLabel::AmbisonicAzimuth.into(),
+            &SynthParameterValue::ScalarF32(std::f32::consts::PI / 2.0),
+        );
+        synth.set_parameter(
+            SynthParameterLabel::ChannelPosition.into(),
+            &SynthParameterValue::ScalarF32(0.5),
+        );
+
+        let mut energy = 0.0;
+        for _ in 0..10 {
+            let out = synth.get_next_block(0, &[]);
+            for s in 0..128 {
+                assert!((out[1][s] + out[0][s]).abs() < 0.00001);
+                assert!(out[2][s].abs() < 0.00001);
+                energy += out[0][s] * out[0][s];
+            }
+        }
+        assert!(energy > 0.0);
+    }
+}
@@ -1,35 +1,42 @@
-use crate::building_blocks::ambisonics::encoder_o1::EncoderO1;
+use crate::building_blocks::ambisonics::encoder::AmbisonicEncoder;
 use crate::building_blocks::envelopes::*;
 use crate::building_blocks::filters::*;
 use crate::building_blocks::oscillators::*;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    waveshaper::Waveshaper, FilterType, Modulator, MonoEffect, MonoSource, OscillatorType,
-    SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue,
+    pass_leftover, waveshaper::Waveshaper, FilterType, Modulator, MonoEffect, MonoSource,
+    OscillatorType, SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue,
+    ValueOrModulator,
 };
 
 use self::naive_blit::NaiveBlitOsc;
 
-/// a triangle synth with envelope etc.
-pub struct SingleOscillatorSynthO1<const BUFSIZE: usize> {
+/// a single oscillator synth with envelope etc., encoded to ambisonics
+/// (4, 9 or 16 channels for first, second or third order)
+pub struct AmbisonicSingleOscillatorSynth<const BUFSIZE: usize, const ACH: usize> {
     oscillator: Box<dyn MonoSource<BUFSIZE> + Sync + Send>,
     waveshaper: Waveshaper<BUFSIZE>,
     lp_filter: Box<dyn MonoEffect<BUFSIZE> + Sync + Send>,
     hp_filter: Box<dyn MonoEffect<BUFSIZE> + Sync + Send>,
     envelope: LinearASREnvelope<BUFSIZE>,
-    encoder: EncoderO1<BUFSIZE>,
+    encoder: AmbisonicEncoder<BUFSIZE, ACH>,
     reverb: f32,
     delay: f32,
 }
 
-impl<const BUFSIZE: usize> SingleOscillatorSynthO1<BUFSIZE> {
+pub type SingleOscillatorSynthO1<const BUFSIZE: usize> = AmbisonicSingleOscillatorSynth<BUFSIZE, 4>;
+pub type SingleOscillatorSynthO2<const BUFSIZE: usize> = AmbisonicSingleOscillatorSynth<BUFSIZE, 9>;
+pub type SingleOscillatorSynthO3<const BUFSIZE: usize> =
+    AmbisonicSingleOscillatorSynth<BUFSIZE, 16>;
+
+impl<const BUFSIZE: usize, const ACH: usize> AmbisonicSingleOscillatorSynth<BUFSIZE, ACH> {
     pub fn new(
         osc_type: OscillatorType,
         lpf_type: FilterType,
         hpf_type: FilterType,
         sr: f32,
     ) -> Self {
-        SingleOscillatorSynthO1 {
+        AmbisonicSingleOscillatorSynth {
             oscillator: match osc_type {
                 OscillatorType::Sine => Box::new(SineOsc::new(440.0, 0.5, sr)),
                 OscillatorType::LFTri => Box::new(LFTri::new(440.0, 0.5, sr)),
@@ -66,28 +73,33 @@ impl<const BUFSIZE: usize> SingleOscillatorSynthO1<BUFSIZE> {
                 _ => Box::new(BiquadHpf12dB::new(20.0, 0.5, sr)),
             },
             envelope: LinearASREnvelope::new(0.3, 0.05, 0.1, 0.05, sr),
-            encoder: EncoderO1::new(),
+            encoder: AmbisonicEncoder::new(sr),
             reverb: 0.0,
             delay: 0.0,
         }
     }
 }
 
-impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for SingleOscillatorSynthO1<BUFSIZE> {
+impl<const BUFSIZE: usize, const ACH: usize> Synth<BUFSIZE, ACH>
+    for AmbisonicSingleOscillatorSynth<BUFSIZE, ACH>
+{
     fn set_modulator(
         &mut self,
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        _copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.oscillator
-            .set_modulator(par.label, init, modulator.clone());
-        self.lp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.hp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.envelope.set_modulator(par.label, init, modulator);
-        //self.encoder.set_modulator(par.label, init, modulator);
+        // a modulator goes to the first block that takes it, so it doesn't need to be cloned
+        let result = self
+            .oscillator
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| self.lp_filter.set_modulator(par.label, init, m))
+            .or_else(|m| self.hp_filter.set_modulator(par.label, init, m))
+            .or_else(|m| self.envelope.set_modulator(par.label, init, m))
+            .or_else(|m| self.encoder.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -120,11 +132,15 @@ impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for SingleOscillatorSynthO1<BUFSIZE
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
         sample_buffers: &[SampleBuffer],
-    ) -> [[f32; BUFSIZE]; 4] {
+    ) -> [[f32; BUFSIZE]; ACH] {
         let mut out: [f32; BUFSIZE] = self.oscillator.get_next_block(start_sample, sample_buffers);
         out = self
             .waveshaper
@@ -143,7 +159,7 @@ impl<const BUFSIZE: usize> Synth<BUFSIZE, 4> for SingleOscillatorSynthO1<BUFSIZE
     }
 
     fn reverb_level(&self) -> f32 {
-        self.reverb
+        self.reverb * self.encoder.reverb_scale()
     }
 
     fn delay_level(&self) -> f32 {
@@ -5,9 +5,11 @@ pub mod n_channel_sampler;
 pub mod n_channel_stereo_sampler;
 pub mod risset_bell;
 pub mod single_oscillator_synth;
+pub mod vbap_synth;
 
 pub use crate::synths::n_channel::karplusplus::KarPlusPlus;
 pub use crate::synths::n_channel::multi_oscillator_synth::MultiOscillatorSynth;
 pub use crate::synths::n_channel::n_channel_sampler::NChannelSampler;
 pub use crate::synths::n_channel::risset_bell::RissetBell;
 pub use crate::synths::n_channel::single_oscillator_synth::SingleOscillatorSynth;
+pub use crate::synths::n_channel::vbap_synth::VbapSynth;
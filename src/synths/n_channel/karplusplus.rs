@@ -20,7 +20,10 @@ use crate::building_blocks::FilterType;
 use crate::building_blocks::OscillatorType;
 use crate::building_blocks::Synth;
 use crate::building_blocks::SynthParameterAddress;
-use crate::building_blocks::{MonoEffect, MonoSource, SynthParameterLabel, SynthParameterValue};
+use crate::building_blocks::{
+    pass_leftover, set_modulator_on_effects, MonoEffect, MonoSource, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
+};
 use crate::synths::SynthDescription;
 
 use self::naive_blit::NaiveBlitOsc;
@@ -153,6 +156,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
                         self.source_gain = *g;
                     }
                 }
+                SynthParameterLabel::RandomSeed => {
+                    self.source.set_parameter(label, val);
+                }
                 _ => {}
             }
         }
@@ -186,22 +192,33 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: crate::building_blocks::Modulator<BUFSIZE>,
+        copies: &mut Vec<crate::building_blocks::Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
         let SynthParameterAddress { label, idx } = par;
 
-        if let Some(0) = idx {
-            self.source.set_modulator(label, init, modulator.clone());
+        let result = if let Some(0) = idx {
+            self.source.set_modulator(label, init, modulator)
         } else {
-            for ef in self.pre_filter_effects.iter_mut() {
-                ef.set_modulator(label, init, modulator.clone());
-            }
+            set_modulator_on_effects(
+                &mut self.pre_filter_effects,
+                label,
+                init,
+                modulator,
+                copies,
+                leftover,
+            )
+            .or_else(|m| self.envelope.set_modulator(label, init, m))
+            .or_else(|m| self.balance.set_modulator(label, init, m))
+            .or_else(|m| self.fb_delay.set_modulator(label, init, m))
+            .or_else(|m| self.post_filter.set_modulator(label, init, m))
+        };
+        pass_leftover(result, leftover);
+    }
 
-            self.envelope.set_modulator(label, init, modulator.clone());
-            self.balance.set_modulator(label, init, modulator.clone());
-            self.fb_delay.set_modulator(label, init, modulator.clone());
-            self.post_filter
-                .set_modulator(label, init, modulator.clone());
-        }
+    fn modulator_copies(&self) -> usize {
+        // one for each effect but the first
+        self.pre_filter_effects.len().saturating_sub(1)
     }
 
     fn finish(&mut self) {
@@ -212,6 +229,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
@@ -2,13 +2,14 @@ use crate::building_blocks::bitcrusher::Bitcrusher;
 use crate::building_blocks::envelopes::*;
 use crate::building_blocks::filters::*;
 use crate::building_blocks::oscillators::*;
+use crate::building_blocks::random::seed_from_parameter;
 use crate::building_blocks::routing::PanChan;
 use crate::building_blocks::EffectType;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    waveshaper::Waveshaper, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, Modulator,
-    MonoEffect, MonoSource, OscillatorType, SampleBuffer, Synth, SynthParameterLabel,
-    SynthParameterValue,
+    pass_leftover, set_modulator_on_effects, waveshaper::Waveshaper, EnvelopeSegmentInfo,
+    EnvelopeSegmentType, FilterType, Modulator, MonoEffect, MonoSource, OscillatorType,
+    SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue, ValueOrModulator,
 };
 use crate::synths::SynthDescription;
 
@@ -128,8 +129,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        match par.label {
+        let result = match par.label {
             SynthParameterLabel::OscillatorAmplitude
             | SynthParameterLabel::OscillatorPhaseEffective
             | SynthParameterLabel::OscillatorPhaseRelative
@@ -138,26 +141,33 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
             | SynthParameterLabel::WavematrixTableIndex
             | SynthParameterLabel::Wavetable
             | SynthParameterLabel::Wavematrix => {
-                if let Some(idx) = par.idx {
-                    if let Some(osc) = self.oscillators.get_mut(idx) {
-                        osc.set_modulator(par.label, init, modulator.clone());
-                    }
-                };
+                match par.idx.and_then(|idx| self.oscillators.get_mut(idx)) {
+                    Some(osc) => osc.set_modulator(par.label, init, modulator),
+                    None => Err(modulator),
+                }
             }
-            _ => {}
-        }
-
-        for ef in self.pre_filter_effects.iter_mut() {
-            ef.set_modulator(par.label, init, modulator.clone());
+            _ => Err(modulator),
         }
+        .or_else(|m| {
+            set_modulator_on_effects(
+                &mut self.pre_filter_effects,
+                par.label,
+                init,
+                m,
+                copies,
+                leftover,
+            )
+        })
+        .or_else(|m| self.lp_filter.set_modulator(par.label, init, m))
+        .or_else(|m| self.hp_filter.set_modulator(par.label, init, m))
+        .or_else(|m| self.envelope.set_modulator(par.label, init, m))
+        .or_else(|m| self.balance.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
+    }
 
-        self.lp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.hp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .set_modulator(par.label, init, modulator.clone());
-        self.balance.set_modulator(par.label, init, modulator);
+    fn modulator_copies(&self) -> usize {
+        // one for each effect but the first
+        self.pre_filter_effects.len().saturating_sub(1)
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -177,6 +187,21 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
                     }
                 }
             }
+            SynthParameterLabel::RandomSeed => {
+                if let Some(idx) = par.idx {
+                    if let Some(osc) = self.oscillators.get_mut(idx) {
+                        osc.set_parameter(par.label, val);
+                    }
+                } else if let Some(seed) = seed_from_parameter(val) {
+                    // derive different seeds, otherwise all noise oscillators would be the same
+                    for (i, osc) in self.oscillators.iter_mut().enumerate() {
+                        osc.set_parameter(
+                            par.label,
+                            &SynthParameterValue::ScalarUsize((seed as usize).wrapping_add(i)),
+                        );
+                    }
+                }
+            }
             _ => {}
         }
 
@@ -211,6 +236,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
@@ -8,8 +8,9 @@ use crate::building_blocks::EffectType;
 use crate::building_blocks::SampleBuffer;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, Modulator, MonoEffect, MonoSource, Synth,
-    SynthParameterLabel, SynthParameterValue,
+    pass_leftover, set_modulator_on_effects, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType,
+    Modulator, MonoEffect, MonoSource, Synth, SynthParameterLabel, SynthParameterValue,
+    ValueOrModulator,
 };
 use crate::synths::SynthDescription;
 
@@ -120,34 +121,41 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.sampler
-            .set_modulator(par.label, init, modulator.clone());
-
-        for ef in self.pre_filter_effects.iter_mut() {
-            ef.set_modulator(par.label, init, modulator.clone());
-        }
-
-        self.hpf.set_modulator(par.label, init, modulator.clone());
-
-        match par.label {
-            SynthParameterLabel::PeakFrequency
-            | SynthParameterLabel::PeakBandwidth
-            | SynthParameterLabel::PeakGain => match par.idx {
-                Some(1) => self
-                    .peak_eq_2
-                    .set_modulator(par.label, init, modulator.clone()),
-                _ => self
-                    .peak_eq_1
-                    .set_modulator(par.label, init, modulator.clone()),
-            },
-            _ => {}
-        }
+        let result = self
+            .sampler
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| {
+                set_modulator_on_effects(
+                    &mut self.pre_filter_effects,
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| self.hpf.set_modulator(par.label, init, m))
+            .or_else(|m| match par.label {
+                SynthParameterLabel::PeakFrequency
+                | SynthParameterLabel::PeakBandwidth
+                | SynthParameterLabel::PeakGain => match par.idx {
+                    Some(1) => self.peak_eq_2.set_modulator(par.label, init, m),
+                    _ => self.peak_eq_1.set_modulator(par.label, init, m),
+                },
+                _ => Err(m),
+            })
+            .or_else(|m| self.lpf.set_modulator(par.label, init, m))
+            .or_else(|m| self.envelope.set_modulator(par.label, init, m))
+            .or_else(|m| self.balance.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
+    }
 
-        self.lpf.set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .set_modulator(par.label, init, modulator.clone());
-        self.balance.set_modulator(par.label, init, modulator);
+    fn modulator_copies(&self) -> usize {
+        // one for each effect but the first
+        self.pre_filter_effects.len().saturating_sub(1)
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -196,6 +204,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
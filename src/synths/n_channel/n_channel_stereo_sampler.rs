@@ -7,19 +7,74 @@ use crate::building_blocks::EffectType;
 use crate::building_blocks::SampleBuffer;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    waveshaper::Waveshaper, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, Modulator,
-    MonoEffect, StereoSource, Synth, SynthParameterLabel, SynthParameterValue,
+    pass_leftover, waveshaper::Waveshaper, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType,
+    Modulator, ModulatorResult, MonoEffect, StereoSource, Synth, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
 };
 use crate::synths::SynthDescription;
 
+type EffectPair<const BUFSIZE: usize> = (
+    Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
+    Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
+);
+
+/// Both channels need their own modulator, the right one takes a copy.
+fn set_modulator_on_pair<const BUFSIZE: usize>(
+    left: &mut dyn MonoEffect<BUFSIZE>,
+    right: &mut dyn MonoEffect<BUFSIZE>,
+    par: SynthParameterLabel,
+    init: f32,
+    modulator: Modulator<BUFSIZE>,
+    copies: &mut Vec<Modulator<BUFSIZE>>,
+    leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+) -> ModulatorResult<BUFSIZE> {
+    // both channels have the same kind of block, so if
+    // the left one doesn't take it, the right one won't either
+    let replaced = left.set_modulator(par, init, modulator)?;
+    pass_leftover(Ok(replaced), leftover);
+    if let Some(copy) = copies.pop() {
+        pass_leftover(right.set_modulator(par, init, copy), leftover);
+    }
+    Ok(None)
+}
+
+/// like `set_modulator_on_effects`, every pair that takes the modulator gets its own copies
+fn set_modulator_on_effect_pairs<const BUFSIZE: usize>(
+    effects: &mut [EffectPair<BUFSIZE>],
+    par: SynthParameterLabel,
+    init: f32,
+    modulator: Modulator<BUFSIZE>,
+    copies: &mut Vec<Modulator<BUFSIZE>>,
+    leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+) -> ModulatorResult<BUFSIZE> {
+    let mut modulator = Some(modulator);
+    let mut taken = false;
+
+    for ef in effects.iter_mut() {
+        let m = match modulator.take().or_else(|| copies.pop()) {
+            Some(m) => m,
+            None => break, // no copies left
+        };
+        match set_modulator_on_pair(ef.0.as_mut(), ef.1.as_mut(), par, init, m, copies, leftover) {
+            Ok(replaced) => {
+                taken = true;
+                pass_leftover(Ok(replaced), leftover);
+            }
+            Err(m) => modulator = Some(m),
+        }
+    }
+
+    match modulator {
+        Some(modulator) if !taken => Err(modulator),
+        unused => Ok(unused),
+    }
+}
+
 /// a stereo sampler with envelope etc.
 /// here we need everything twice ...
 pub struct NChannelStereoSampler<const BUFSIZE: usize, const NCHAN: usize> {
     sampler: StereoSampler<BUFSIZE>,
-    pre_filter_effects: Vec<(
-        Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
-        Box<dyn MonoEffect<BUFSIZE> + Send + Sync>,
-    )>,
+    pre_filter_effects: Vec<EffectPair<BUFSIZE>>,
     envelope: (
         MultiPointEffectEnvelope<BUFSIZE>,
         MultiPointEffectEnvelope<BUFSIZE>,
@@ -172,51 +227,85 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.sampler
-            .set_modulator(par.label, init, modulator.clone());
-        self.hpf.0.set_modulator(par.label, init, modulator.clone());
-        self.hpf.1.set_modulator(par.label, init, modulator.clone());
-
-        for ef in self.pre_filter_effects.iter_mut() {
-            ef.0.set_modulator(par.label, init, modulator.clone());
-            ef.1.set_modulator(par.label, init, modulator.clone());
-        }
-
-        match par.label {
-            SynthParameterLabel::PeakFrequency
-            | SynthParameterLabel::PeakBandwidth
-            | SynthParameterLabel::PeakGain => match par.idx {
-                Some(1) => {
-                    self.peak_eq_2
-                        .0
-                        .set_modulator(par.label, init, modulator.clone());
-                    self.peak_eq_2
-                        .1
-                        .set_modulator(par.label, init, modulator.clone());
-                }
-                _ => {
-                    self.peak_eq_1
-                        .0
-                        .set_modulator(par.label, init, modulator.clone());
-                    self.peak_eq_1
-                        .1
-                        .set_modulator(par.label, init, modulator.clone());
+        // the single blocks first, as each channel of the
+        // stereo blocks needs its own copy of the modulator
+        let result = self
+            .sampler
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| self.balance.set_modulator(par.label, init, m))
+            .or_else(|m| {
+                set_modulator_on_effect_pairs(
+                    &mut self.pre_filter_effects,
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| {
+                set_modulator_on_pair(
+                    self.hpf.0.as_mut(),
+                    self.hpf.1.as_mut(),
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| {
+                let peak_eq = match par.idx {
+                    Some(1) => &mut self.peak_eq_2,
+                    _ => &mut self.peak_eq_1,
+                };
+                match par.label {
+                    SynthParameterLabel::PeakFrequency
+                    | SynthParameterLabel::PeakBandwidth
+                    | SynthParameterLabel::PeakGain => set_modulator_on_pair(
+                        peak_eq.0.as_mut(),
+                        peak_eq.1.as_mut(),
+                        par.label,
+                        init,
+                        m,
+                        copies,
+                        leftover,
+                    ),
+                    _ => Err(m),
                 }
-            },
-
-            _ => {}
-        }
+            })
+            .or_else(|m| {
+                set_modulator_on_pair(
+                    self.lpf.0.as_mut(),
+                    self.lpf.1.as_mut(),
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| {
+                set_modulator_on_pair(
+                    &mut self.envelope.0,
+                    &mut self.envelope.1,
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            });
+        pass_leftover(result, leftover);
+    }
 
-        self.lpf.0.set_modulator(par.label, init, modulator.clone());
-        self.lpf.1.set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .0
-            .set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .1
-            .set_modulator(par.label, init, modulator.clone());
-        self.balance.set_modulator(par.label, init, modulator);
+    fn modulator_copies(&self) -> usize {
+        // one for the right channel of each block, and two
+        // for each pair of effects but the first
+        (2 * self.pre_filter_effects.len()).max(2) - 1
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -278,6 +367,11 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.envelope.0.is_finished() && self.envelope.1.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.0.release();
+        self.envelope.1.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
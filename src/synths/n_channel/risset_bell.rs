@@ -4,8 +4,8 @@ use crate::building_blocks::oscillators::*;
 use crate::building_blocks::routing::PanChan;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    Modulator, MonoEffect, MonoSource, SampleBuffer, Synth, SynthParameterLabel,
-    SynthParameterValue,
+    pass_leftover, Modulator, MonoEffect, MonoSource, SampleBuffer, Synth, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
 };
 
 /// 11-partial risset bell, modeled after Frederik Oloffson's SuperCollider port
@@ -86,11 +86,15 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        _copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.lpf.set_modulator(par.label, init, modulator.clone());
-        self.main_envelope
-            .set_modulator(par.label, init, modulator.clone());
-        self.balance.set_modulator(par.label, init, modulator);
+        let result = self
+            .lpf
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| self.main_envelope.set_modulator(par.label, init, m))
+            .or_else(|m| self.balance.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -194,6 +198,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.main_envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.main_envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
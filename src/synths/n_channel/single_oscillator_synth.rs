@@ -6,9 +6,9 @@ use crate::building_blocks::routing::PanChan;
 use crate::building_blocks::EffectType;
 use crate::building_blocks::SynthParameterAddress;
 use crate::building_blocks::{
-    waveshaper::Waveshaper, EnvelopeSegmentInfo, EnvelopeSegmentType, FilterType, Modulator,
-    MonoEffect, MonoSource, OscillatorType, SampleBuffer, Synth, SynthParameterLabel,
-    SynthParameterValue,
+    pass_leftover, set_modulator_on_effects, waveshaper::Waveshaper, EnvelopeSegmentInfo,
+    EnvelopeSegmentType, FilterType, Modulator, MonoEffect, MonoSource, OscillatorType,
+    SampleBuffer, Synth, SynthParameterLabel, SynthParameterValue, ValueOrModulator,
 };
 use crate::synths::SynthDescription;
 
@@ -123,21 +123,33 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         par: SynthParameterAddress,
         init: f32,
         modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
     ) {
-        self.oscillator
-            .set_modulator(par.label, init, modulator.clone());
-
-        for ef in self.pre_filter_effects.iter_mut() {
-            ef.set_modulator(par.label, init, modulator.clone());
-        }
+        // a modulator goes to the first block that takes it, so it doesn't need to be cloned
+        let result = self
+            .oscillator
+            .set_modulator(par.label, init, modulator)
+            .or_else(|m| {
+                set_modulator_on_effects(
+                    &mut self.pre_filter_effects,
+                    par.label,
+                    init,
+                    m,
+                    copies,
+                    leftover,
+                )
+            })
+            .or_else(|m| self.lp_filter.set_modulator(par.label, init, m))
+            .or_else(|m| self.hp_filter.set_modulator(par.label, init, m))
+            .or_else(|m| self.envelope.set_modulator(par.label, init, m))
+            .or_else(|m| self.balance.set_modulator(par.label, init, m));
+        pass_leftover(result, leftover);
+    }
 
-        self.lp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.hp_filter
-            .set_modulator(par.label, init, modulator.clone());
-        self.envelope
-            .set_modulator(par.label, init, modulator.clone());
-        self.balance.set_modulator(par.label, init, modulator);
+    fn modulator_copies(&self) -> usize {
+        // one for each effect but the first
+        self.pre_filter_effects.len().saturating_sub(1)
     }
 
     fn set_parameter(&mut self, par: SynthParameterAddress, val: &SynthParameterValue) {
@@ -175,6 +187,10 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN>
         self.envelope.is_finished()
     }
 
+    fn release(&mut self) {
+        self.envelope.release();
+    }
+
     fn get_next_block(
         &mut self,
         start_sample: usize,
@@ -0,0 +1,96 @@
+use crate::building_blocks::routing::{VbapLayout, VbapPanner};
+use crate::building_blocks::{
+    pass_leftover, Modulator, SampleBuffer, Synth, SynthParameterAddress, SynthParameterLabel,
+    SynthParameterValue, ValueOrModulator,
+};
+
+use std::sync::Arc;
+
+/// Pans a mono (channel-based) synth on a speaker layout with VBAP, instead
+/// of the evenly spaced ring of `PanChan`. The direction is set by the
+/// `AmbisonicAzimuth` and `AmbisonicElevation` parameters, the width by
+/// `VbapSpread`, the channel position of the synth is ignored.
+pub struct VbapSynth<const BUFSIZE: usize, const NCHAN: usize> {
+    synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>,
+    panner: VbapPanner<BUFSIZE, NCHAN>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> VbapSynth<BUFSIZE, NCHAN> {
+    pub fn new(synth: Box<dyn Synth<BUFSIZE, 1> + Send + Sync>, layout: Arc<VbapLayout>) -> Self {
+        VbapSynth {
+            synth,
+            panner: VbapPanner::new(layout),
+        }
+    }
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> Synth<BUFSIZE, NCHAN> for VbapSynth<BUFSIZE, NCHAN> {
+    fn set_parameter(&mut self, par: SynthParameterAddress, value: &SynthParameterValue) {
+        match par.label {
+            SynthParameterLabel::AmbisonicAzimuth
+            | SynthParameterLabel::AmbisonicElevation
+            | SynthParameterLabel::VbapSpread => self.panner.set_parameter(par.label, value),
+            // with a single channel, the panner would only attenuate
+            SynthParameterLabel::ChannelPosition => {}
+            _ => self.synth.set_parameter(par, value),
+        }
+    }
+
+    fn set_modulator(
+        &mut self,
+        par: SynthParameterAddress,
+        init: f32,
+        modulator: Modulator<BUFSIZE>,
+        copies: &mut Vec<Modulator<BUFSIZE>>,
+        leftover: &mut dyn FnMut(ValueOrModulator<BUFSIZE>),
+    ) {
+        match par.label {
+            SynthParameterLabel::AmbisonicAzimuth
+            | SynthParameterLabel::AmbisonicElevation
+            | SynthParameterLabel::VbapSpread => pass_leftover(
+                self.panner.set_modulator(par.label, init, modulator),
+                leftover,
+            ),
+            // same as above, the modulator goes back to be disposed of
+            SynthParameterLabel::ChannelPosition => {
+                leftover(ValueOrModulator::Mod(init, modulator))
+            }
+            _ => self
+                .synth
+                .set_modulator(par, init, modulator, copies, leftover),
+        }
+    }
+
+    fn modulator_copies(&self) -> usize {
+        self.synth.modulator_copies()
+    }
+
+    fn finish(&mut self) {
+        self.synth.finish();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.synth.is_finished()
+    }
+
+    fn release(&mut self) {
+        self.synth.release();
+    }
+
+    fn get_next_block(
+        &mut self,
+        start_sample: usize,
+        in_buffers: &[SampleBuffer],
+    ) -> [[f32; BUFSIZE]; NCHAN] {
+        let [mono] = self.synth.get_next_block(start_sample, in_buffers);
+        self.panner.process_block(mono, start_sample, in_buffers)
+    }
+
+    fn reverb_level(&self) -> f32 {
+        self.synth.reverb_level()
+    }
+
+    fn delay_level(&self) -> f32 {
+        self.synth.delay_level()
+    }
+}